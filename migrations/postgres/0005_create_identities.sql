@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS identities (
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (provider, subject)
+);
+
+-- One account per provider for each user
+CREATE UNIQUE INDEX IF NOT EXISTS identities_user_provider_idx ON identities (user_id, provider);
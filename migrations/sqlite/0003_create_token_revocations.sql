@@ -0,0 +1,13 @@
+-- Timestamps are unix seconds so they compare directly with the `iat`/`exp` claims
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti TEXT PRIMARY KEY NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS revoked_tokens_expires_idx ON revoked_tokens (expires_at);
+
+CREATE TABLE IF NOT EXISTS revoked_sessions (
+    user_id TEXT PRIMARY KEY NOT NULL,
+    issued_before INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
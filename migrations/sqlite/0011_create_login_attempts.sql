@@ -0,0 +1,9 @@
+-- Failed logins per account or client, timestamps are unix seconds
+CREATE TABLE IF NOT EXISTS login_attempts (
+    key TEXT PRIMARY KEY NOT NULL,
+    failures INTEGER NOT NULL,
+    last_failure_at INTEGER NOT NULL,
+    locked_until INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS login_attempts_last_failure_idx ON login_attempts (last_failure_at);
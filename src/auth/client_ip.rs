@@ -0,0 +1,36 @@
+use actix_web::HttpRequest;
+
+use crate::models::AppState;
+
+/// The address a request comes from, as told by the proxy when `TRUST_PROXY_HEADERS` is set.
+///
+/// Without the flag the headers are ignored, anyone could send them to pick their own address.
+pub fn client_ip(req: &HttpRequest, data: &AppState) -> String {
+    let info = req.connection_info();
+    let ip = if data.env.trust_proxy_headers {
+        info.realip_remote_addr()
+    } else {
+        info.peer_addr()
+    };
+    ip.unwrap_or("unknown").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn forwarded_headers_only_count_behind_a_trusted_proxy() {
+        let req = TestRequest::default()
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", "203.0.113.7"))
+            .to_http_request();
+        let mut data = AppState::for_tests();
+        assert_eq!(client_ip(&req, &data), "10.0.0.1");
+
+        data.env.trust_proxy_headers = true;
+        assert_eq!(client_ip(&req, &data), "203.0.113.7");
+    }
+}
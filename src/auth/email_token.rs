@@ -0,0 +1,77 @@
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+
+use crate::models::EmailTokenClaims;
+
+pub const VERIFY_EMAIL: &str = "verify-email";
+
+/// Signs a link for `purpose`, valid for `max_age_minutes`.
+///
+/// The claims have no `jti`, so they never pass for an access token signed with the same secret.
+pub fn create(
+    user_id: &str,
+    email: &str,
+    purpose: &str,
+    max_age_minutes: i64,
+    secret: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let claims = EmailTokenClaims {
+        sub: user_id.to_string(),
+        email: email.to_string(),
+        aud: purpose.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(max_age_minutes)).timestamp() as usize,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_ref()),
+    )
+}
+
+pub fn verify(
+    token: &str,
+    purpose: &str,
+    secret: &str,
+) -> Result<EmailTokenClaims, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_audience(&[purpose]);
+
+    decode::<EmailTokenClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_ref()),
+        &validation,
+    )
+    .map(|data| data.claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::errors::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn links_verify_only_for_their_purpose_and_secret() {
+        let token = create("user-1", "alice@example.com", VERIFY_EMAIL, 60, "secret").unwrap();
+
+        let claims = verify(&token, VERIFY_EMAIL, "secret").unwrap();
+        assert_eq!(claims.sub, "user-1");
+        assert_eq!(claims.email, "alice@example.com");
+
+        let err = verify(&token, "reset-password", "secret").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidAudience);
+        let err = verify(&token, VERIFY_EMAIL, "other-secret").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidSignature);
+    }
+
+    #[test]
+    fn expired_links_are_refused() {
+        let token = create("user-1", "alice@example.com", VERIFY_EMAIL, -5, "secret").unwrap();
+
+        let err = verify(&token, VERIFY_EMAIL, "secret").unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::ExpiredSignature);
+    }
+}
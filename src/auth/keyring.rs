@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::{ErrorKind, Result as JwtResult},
+    jwk::JwkSet,
+    TokenData, Validation,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::env::Config;
+
+use super::signing_key::SigningKey;
+
+/// One key signing new access tokens, plus previous keys that keep verifying
+/// the tokens they signed until their retirement date.
+pub struct Keyring {
+    signing: SigningKey,
+    verification: Vec<(SigningKey, DateTime<Utc>)>,
+}
+
+impl Keyring {
+    pub fn from_config(config: &Config) -> Self {
+        let signing = match &config.jwt_private_key_path {
+            Some(path) => SigningKey::from_pem_file(path, config.jwt_key_id.clone())
+                .unwrap_or_else(|e| panic!("JWT_PRIVATE_KEY_PATH is invalid: {}", e)),
+            None => SigningKey::from_secret(&config.jwt_secret, config.jwt_key_id.clone()),
+        };
+
+        let mut keyring = Keyring {
+            signing,
+            verification: Vec::new(),
+        };
+
+        for key_config in &config.jwt_verification_keys {
+            let key = match (&key_config.private_key_path, &key_config.secret) {
+                (Some(path), _) => SigningKey::from_pem_file(path, key_config.kid.clone())
+                    .unwrap_or_else(|e| {
+                        panic!("Verification key `{}` is invalid: {}", key_config.name, e)
+                    }),
+                (None, Some(secret)) => SigningKey::from_secret(secret, key_config.kid.clone()),
+                (None, None) => unreachable!("checked by Config::init"),
+            };
+
+            // The kid is the only thing telling keys apart when decoding
+            assert!(
+                keyring.keys().all(|existing| existing.kid != key.kid),
+                "Verification key `{}` has the same kid as another key",
+                key_config.name
+            );
+            keyring.verification.push((key, key_config.retire_at));
+        }
+        keyring
+    }
+
+    pub fn encode<T: Serialize>(&self, claims: &T) -> JwtResult<String> {
+        encode(&self.signing.header(), claims, self.signing.encoding_key())
+    }
+
+    /// Verifies with the live key matching the token's `kid`, a retired or unknown kid is rejected.
+    pub fn decode<T: DeserializeOwned>(&self, token: &str) -> JwtResult<TokenData<T>> {
+        let header = decode_header(token)?;
+        let key = self
+            .live_keys()
+            .find(|key| key.kid == header.kid)
+            .ok_or(ErrorKind::InvalidSignature)?;
+
+        decode(token, key.decoding_key(), &Validation::new(key.algorithm))
+    }
+
+    /// Public halves of the live asymmetric keys.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.live_keys().filter_map(|key| key.jwk.clone()).collect(),
+        }
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &SigningKey> {
+        std::iter::once(&self.signing).chain(self.verification.iter().map(|(key, _)| key))
+    }
+
+    fn live_keys(&self) -> impl Iterator<Item = &SigningKey> {
+        let now = Utc::now();
+        std::iter::once(&self.signing).chain(
+            self.verification
+                .iter()
+                .filter(move |(_, retire_at)| *retire_at > now)
+                .map(|(key, _)| key),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use openssl::{ec::EcGroup, ec::EcKey, nid::Nid, pkey::PKey};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::config::env::VerificationKeyConfig;
+
+    fn config(secret: &str, kid: &str) -> Config {
+        Config {
+            jwt_secret: secret.to_string(),
+            jwt_key_id: Some(kid.to_string()),
+            ..Config::for_tests()
+        }
+    }
+
+    fn retiring(secret: &str, kid: &str, retire_at: DateTime<Utc>) -> VerificationKeyConfig {
+        VerificationKeyConfig {
+            name: kid.to_string(),
+            private_key_path: None,
+            secret: Some(secret.to_string()),
+            kid: Some(kid.to_string()),
+            retire_at,
+        }
+    }
+
+    fn claims() -> Value {
+        json!({"sub": "user-1", "exp": (Utc::now() + Duration::minutes(5)).timestamp()})
+    }
+
+    #[test]
+    fn tokens_of_a_retiring_key_verify_until_it_retires() {
+        let old_token = Keyring::from_config(&config("old-secret", "old"))
+            .encode(&claims())
+            .unwrap();
+
+        let mut rotated = config("new-secret", "new");
+        rotated.jwt_verification_keys = vec![retiring(
+            "old-secret",
+            "old",
+            Utc::now() + Duration::hours(1),
+        )];
+        let keyring = Keyring::from_config(&rotated);
+        assert_eq!(
+            keyring.decode::<Value>(&old_token).unwrap().claims["sub"],
+            "user-1"
+        );
+
+        // New tokens only ever use the signing key
+        let new_token = keyring.encode(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&new_token).unwrap().kid.as_deref(),
+            Some("new")
+        );
+        assert!(keyring.decode::<Value>(&new_token).is_ok());
+
+        rotated.jwt_verification_keys[0].retire_at = Utc::now() - Duration::seconds(1);
+        let err = Keyring::from_config(&rotated)
+            .decode::<Value>(&old_token)
+            .unwrap_err();
+        assert_eq!(*err.kind(), ErrorKind::InvalidSignature);
+    }
+
+    #[test]
+    fn a_known_kid_with_the_wrong_key_is_rejected() {
+        // Same kid as the live key, but signed with another secret
+        let forged = Keyring::from_config(&config("other-secret", "new"))
+            .encode(&claims())
+            .unwrap();
+        let unknown = Keyring::from_config(&config("new-secret", "unknown"))
+            .encode(&claims())
+            .unwrap();
+
+        let keyring = Keyring::from_config(&config("new-secret", "new"));
+        assert_eq!(
+            *keyring.decode::<Value>(&forged).unwrap_err().kind(),
+            ErrorKind::InvalidSignature
+        );
+        assert_eq!(
+            *keyring.decode::<Value>(&unknown).unwrap_err().kind(),
+            ErrorKind::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn jwks_lists_only_live_public_keys() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let pkey = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!("keyring-{}.pem", Uuid::new_v4()));
+        std::fs::write(&path, pkey.private_key_to_pem_pkcs8().unwrap()).unwrap();
+
+        let mut config = config("new-secret", "new");
+        config.jwt_verification_keys = vec![
+            VerificationKeyConfig {
+                private_key_path: Some(path.to_string_lossy().into_owned()),
+                secret: None,
+                ..retiring("", "ec", Utc::now() + Duration::hours(1))
+            },
+            retiring("old-secret", "old", Utc::now() + Duration::hours(1)),
+        ];
+        let live = Keyring::from_config(&config);
+        config.jwt_verification_keys[0].retire_at = Utc::now() - Duration::seconds(1);
+        let retired = Keyring::from_config(&config);
+        std::fs::remove_file(&path).unwrap();
+
+        let kids: Vec<_> = live
+            .jwks()
+            .keys
+            .into_iter()
+            .map(|jwk| jwk.common.key_id)
+            .collect();
+        assert_eq!(kids, [Some("ec".to_string())]);
+        assert!(retired.jwks().keys.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Verification key `new` has the same kid as another key")]
+    fn duplicate_kids_are_refused() {
+        let mut config = config("new-secret", "new");
+        config.jwt_verification_keys = vec![retiring(
+            "old-secret",
+            "new",
+            Utc::now() + Duration::hours(1),
+        )];
+        Keyring::from_config(&config);
+    }
+}
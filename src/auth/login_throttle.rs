@@ -0,0 +1,119 @@
+use chrono::Utc;
+
+use crate::{
+    models::{AppState, AttemptLimits},
+    repository::RepositoryError,
+};
+
+pub fn account_key(email: &str) -> String {
+    format!("account:{}", email.to_lowercase())
+}
+
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+pub fn mfa_key(user_id: &str) -> String {
+    format!("mfa:{}", user_id)
+}
+
+/// Counts an attempt on `key` before it is checked, returns the seconds to wait when refused.
+async fn reserve(
+    key: &str,
+    limits: AttemptLimits,
+    data: &AppState,
+) -> Result<Option<i64>, RepositoryError> {
+    let now = Utc::now().timestamp();
+    match data.login_attempts.reserve(key, now, &limits).await? {
+        Some(attempts) if attempts.failures > limits.max_attempts => {
+            log::warn!(
+                "Locked out {} after {} failed attempts",
+                attempts.key,
+                limits.max_attempts
+            );
+            Ok(Some(limits.lockout))
+        }
+        Some(_) => Ok(None),
+        None => {
+            let locked_until = data
+                .login_attempts
+                .find(key)
+                .await?
+                .and_then(|attempts| attempts.locked_until)
+                .unwrap_or(now);
+            Ok(Some((locked_until - now).max(1)))
+        }
+    }
+}
+
+/// Counts a password login against the client address and the account before the password is
+/// checked, returns the seconds to wait when either is locked.
+///
+/// Only the account backs off between attempts, so one mistyped password doesn't slow down
+/// everyone behind the same address, which is locked once it goes over its own limit. Until
+/// `login_succeeded`, the attempt counts as a failure.
+pub async fn reserve_login(
+    email: &str,
+    ip: &str,
+    data: &AppState,
+) -> Result<Option<i64>, RepositoryError> {
+    let ip_limits = AttemptLimits {
+        max_attempts: data.env.login_ip_max_attempts,
+        lockout: data.env.login_lockout,
+        backoff_base: 0,
+        backoff_max: 0,
+    };
+    if let Some(retry_after) = reserve(&ip_key(ip), ip_limits, data).await? {
+        return Ok(Some(retry_after));
+    }
+    reserve(&account_key(email), account_limits(data), data).await
+}
+
+/// Takes back a login counted by `reserve_login` once the password checked out.
+///
+/// Only the account starts over, or an attacker could reset the address with an account of
+/// their own.
+pub async fn login_succeeded(
+    email: &str,
+    ip: &str,
+    data: &AppState,
+) -> Result<(), RepositoryError> {
+    unlock_account(email, data).await?;
+    data.login_attempts.release(&ip_key(ip)).await
+}
+
+/// Counts an attempt at the second factor of a user before it is checked, returns the seconds
+/// to wait when refused.
+///
+/// A fresh password login hands out a new `mfa_token` and lifts the account lock, so the
+/// guesses are counted per user to outlast both.
+pub async fn reserve_mfa(user_id: &str, data: &AppState) -> Result<Option<i64>, RepositoryError> {
+    reserve(&mfa_key(user_id), account_limits(data), data).await
+}
+
+fn account_limits(data: &AppState) -> AttemptLimits {
+    AttemptLimits {
+        max_attempts: data.env.login_max_attempts,
+        lockout: data.env.login_lockout,
+        backoff_base: data.env.login_backoff_base,
+        backoff_max: data.env.login_backoff_max,
+    }
+}
+
+/// Lifts the second factor lockout of a user, after a successful 2FA step or by an admin.
+pub async fn unlock_mfa(user_id: &str, data: &AppState) -> Result<bool, RepositoryError> {
+    data.login_attempts.clear(&mfa_key(user_id)).await
+}
+
+/// Lifts the lockout of an account, after a successful login or by an admin.
+pub async fn unlock_account(email: &str, data: &AppState) -> Result<bool, RepositoryError> {
+    data.login_attempts.clear(&account_key(email)).await
+}
+
+/// Drops counters that are neither locked nor recent enough to count anymore.
+pub async fn purge_expired(data: &AppState) -> Result<u64, RepositoryError> {
+    let now = Utc::now().timestamp();
+    data.login_attempts
+        .purge_expired(now - data.env.login_lockout, now)
+        .await
+}
@@ -0,0 +1,172 @@
+use actix_web::{
+    cookie::{time::Duration as ActixWebDuration, Cookie, SameSite},
+    error::ErrorInternalServerError,
+    Result as ActixResult,
+};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
+
+use crate::{
+    models::{AppState, MfaPendingClaims},
+    repository::RepositoryError,
+};
+
+use super::{crypto::sha256_hex, totp};
+
+pub const MFA_PENDING_COOKIE: &str = "mfa_pending";
+
+const MFA_PENDING_AUDIENCE: &str = "mfa-pending";
+// Needed by `/api/auth/mfa/verify` and `/api/auth/webauthn/mfa/*`
+const MFA_PENDING_COOKIE_PATH: &str = "/api/auth";
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+// No 0/o, 1/l/i, so codes survive being written down
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Whether the user has to present a second factor after their password or provider login.
+pub async fn is_enabled(user_id: &str, data: &AppState) -> Result<bool, RepositoryError> {
+    Ok(data
+        .two_factor
+        .find_totp(user_id)
+        .await?
+        .is_some_and(|factor| factor.enabled))
+}
+
+pub fn create_pending_token(user_id: &str, data: &AppState) -> ActixResult<String> {
+    let now = Utc::now();
+    let claims = MfaPendingClaims {
+        sub: user_id.to_string(),
+        aud: MFA_PENDING_AUDIENCE.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(data.env.mfa_pending_max_age)).timestamp() as usize,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(data.env.jwt_secret.as_ref()),
+    )
+    .map_err(|_| ErrorInternalServerError("Failed to create token"))
+}
+
+pub fn verify_pending_token(
+    token: &str,
+    data: &AppState,
+) -> Result<MfaPendingClaims, jsonwebtoken::errors::Error> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.set_audience(&[MFA_PENDING_AUDIENCE]);
+
+    decode::<MfaPendingClaims>(
+        token,
+        &DecodingKey::from_secret(data.env.jwt_secret.as_ref()),
+        &validation,
+    )
+    .map(|data| data.claims)
+}
+
+pub fn pending_cookie(token: String, data: &AppState) -> Cookie<'static> {
+    Cookie::build(MFA_PENDING_COOKIE, token)
+        .same_site(SameSite::Strict)
+        .secure(true)
+        .path(MFA_PENDING_COOKIE_PATH)
+        .max_age(ActixWebDuration::minutes(data.env.mfa_pending_max_age))
+        .http_only(true)
+        .finish()
+}
+
+pub fn pending_removal_cookie() -> Cookie<'static> {
+    Cookie::build(MFA_PENDING_COOKIE, "")
+        .path(MFA_PENDING_COOKIE_PATH)
+        .max_age(ActixWebDuration::new(-1, 0))
+        .http_only(true)
+        .finish()
+}
+
+/// Fresh recovery codes like `k7m2p-x9qrt`, shown to the user once and only stored hashed.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char
+                })
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Codes are random enough that a plain SHA-256 can't be brute forced, unlike passwords.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    sha256_hex(&normalized)
+}
+
+/// Checks an authenticator code or, failing that, uses up a recovery code.
+pub async fn check_second_factor(
+    user_id: &str,
+    code: Option<&str>,
+    recovery_code: Option<&str>,
+    data: &AppState,
+) -> Result<bool, RepositoryError> {
+    if let Some(code) = code {
+        let factor = match data.two_factor.find_totp(user_id).await? {
+            Some(factor) if factor.enabled => factor,
+            _ => return Ok(false),
+        };
+        return match totp::verify(&factor.secret, code) {
+            Some(step) => data.two_factor.mark_totp_used(user_id, step).await,
+            None => Ok(false),
+        };
+    }
+
+    if let Some(recovery_code) = recovery_code {
+        let used = data
+            .two_factor
+            .use_recovery_code(user_id, &hash_recovery_code(recovery_code))
+            .await?;
+        if used {
+            log::info!("User {} used a recovery code", user_id);
+        }
+        return Ok(used);
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth::token, models::User};
+
+    #[test]
+    fn recovery_codes_are_matched_however_they_are_typed() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        let code = &codes[0];
+        assert_eq!(code.len(), 11);
+        assert_eq!(&code[5..6], "-");
+
+        let typed = format!(" {} ", code.replace('-', "").to_uppercase());
+        assert_eq!(hash_recovery_code(&typed), hash_recovery_code(code));
+        assert_ne!(hash_recovery_code(&codes[1]), hash_recovery_code(code));
+    }
+
+    #[test]
+    fn only_pending_tokens_pass_for_the_2fa_step() {
+        let data = AppState::for_tests();
+        let pending = create_pending_token("user-1", &data).unwrap();
+        assert_eq!(verify_pending_token(&pending, &data).unwrap().sub, "user-1");
+
+        // An access token for the same user isn't a pass to skip the password
+        let user = User::for_tests("user-1", "alice@example.com");
+        let access_token = token::create_access_token(&user, &data).unwrap();
+        assert!(verify_pending_token(&access_token, &data).is_err());
+    }
+}
@@ -1,4 +1,19 @@
+pub mod client_ip;
+pub mod crypto;
+pub mod email_token;
+pub mod keyring;
+pub mod login_throttle;
+pub mod mfa;
 pub mod oauth;
+pub mod oauth_state;
+pub mod one_time_token;
+pub mod password;
+pub mod role_guard;
+pub mod role_hierarchy;
+pub mod signing_key;
+pub mod token;
 pub mod token_guard;
+pub mod totp;
+pub mod webauthn;
 
 pub use oauth::*;
@@ -1,72 +1,260 @@
-use actix_web::web;
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
-use std::error::Error;
 
-use crate::models::AppState;
+use crate::{config::env::Config, models::OAuthStateClaims};
 
-use super::BasicOauthToken;
+use super::{
+    provider::{build_authorize_url, request_token, request_user},
+    OAuthError, OAuthProvider, OAuthToken, UserInfo,
+};
 
 #[derive(Deserialize)]
 pub struct GitHubUserResult {
+    pub id: u64,
     pub login: String,
     pub avatar_url: Option<String>,
     pub email: Option<String>,
 }
 
-pub async fn get_github_oauth_token(
-    authorization_code: &str,
-    data: &web::Data<AppState>,
-) -> Result<BasicOauthToken, Box<dyn Error>> {
-    let client_id = data.env.github_oauth_client_id.to_owned();
-    let client_secret = data.env.github_oauth_client_secret.to_owned();
+/// An entry of `GET /user/emails`, the public profile doesn't say whether the email is verified.
+#[derive(Deserialize)]
+pub struct GitHubEmailResult {
+    pub email: String,
+    pub primary: bool,
+    pub verified: bool,
+}
 
-    let root_url = "https://github.com/login/oauth/access_token";
+pub struct GitHubOAuthProvider {
+    client: Client,
+    client_id: String,
+    client_secret: String,
+    redirect_url: String,
+    // https://api.github.com, swapped for a local server in tests
+    api_url: String,
+    pkce: bool,
+}
 
-    let client = Client::new();
+impl GitHubOAuthProvider {
+    pub fn new(config: &Config, client: Client) -> Self {
+        Self {
+            client,
+            client_id: config.github_oauth_client_id.to_owned(),
+            client_secret: config.github_oauth_client_secret.to_owned(),
+            redirect_url: config.github_oauth_redirect_url.to_owned(),
+            api_url: "https://api.github.com".to_string(),
+            pkce: config.github_oauth_pkce,
+        }
+    }
+}
 
-    let params = [
-        ("client_id", client_id.as_str()),
-        ("client_secret", client_secret.as_str()),
-        ("code", authorization_code),
-    ];
+#[async_trait]
+impl OAuthProvider for GitHubOAuthProvider {
+    fn name(&self) -> &str {
+        "github"
+    }
+
+    fn pkce_enabled(&self) -> bool {
+        self.pkce
+    }
 
-    let response = client
-        .post(root_url)
-        .header("Accept", "application/json")
-        .form(&params)
-        .send()
+    async fn authorize_url(&self, flow: &OAuthStateClaims) -> Result<String, OAuthError> {
+        build_authorize_url(
+            "https://github.com/login/oauth/authorize",
+            &[
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_url.as_str()),
+                ("scope", "read:user user:email"),
+            ],
+            flow,
+        )
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        flow: &OAuthStateClaims,
+    ) -> Result<OAuthToken, OAuthError> {
+        let params = [
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("code", code),
+            ("redirect_uri", self.redirect_url.as_str()),
+        ];
+
+        request_token(
+            &self.client,
+            "https://github.com/login/oauth/access_token",
+            &params,
+            flow,
+        )
+        .await
+    }
+
+    async fn fetch_user(
+        &self,
+        token: &OAuthToken,
+        _flow: &OAuthStateClaims,
+    ) -> Result<UserInfo, OAuthError> {
+        let github_user: GitHubUserResult = request_user(
+            &self.client,
+            format!("{}/user", self.api_url),
+            &token.access_token,
+        )
         .await?;
 
-    if response.status().is_success() {
-        let oauth_response = response.json::<BasicOauthToken>().await?;
-        Ok(oauth_response)
-    } else {
-        let message = "An error occurred while trying to retrieve the access token.";
-        Err(From::from(message))
+        let emails: Vec<GitHubEmailResult> = request_user(
+            &self.client,
+            format!("{}/user/emails", self.api_url),
+            &token.access_token,
+        )
+        .await?;
+
+        // The profile email is null when it's kept private, fall back to the primary one
+        let email = match github_user.email.filter(|email| !email.is_empty()) {
+            Some(email) => email,
+            None => emails
+                .iter()
+                .find(|entry| entry.primary && entry.verified)
+                .map(|entry| entry.email.clone())
+                .ok_or("The GitHub account has no verified primary email")?,
+        };
+        let email_verified = emails
+            .iter()
+            .any(|entry| entry.verified && entry.email.eq_ignore_ascii_case(&email));
+
+        Ok(UserInfo {
+            subject: github_user.id.to_string(),
+            name: github_user.login,
+            email,
+            email_verified,
+            photo: github_user.avatar_url,
+            provider: "GitHub".to_string(),
+        })
     }
 }
 
-pub async fn get_github_user(access_token: &str) -> Result<GitHubUserResult, Box<dyn Error>> {
-    let root_url = "https://api.github.com/user";
+#[cfg(test)]
+mod tests {
+    use mockito::{Server, ServerGuard};
 
-    let client = Client::new();
+    use super::*;
+    use crate::auth::oauth_state;
 
-    let response = client
-        .get(root_url)
-        .header(reqwest::header::USER_AGENT, "blog-rs")
-        .bearer_auth(access_token)
-        .send()
-        .await?;
+    fn provider(server: &ServerGuard) -> GitHubOAuthProvider {
+        GitHubOAuthProvider {
+            client: Client::new(),
+            client_id: "client-id".to_string(),
+            client_secret: "client-secret".to_string(),
+            redirect_url: "http://localhost/callback".to_string(),
+            api_url: server.url(),
+            pkce: true,
+        }
+    }
+
+    fn token() -> OAuthToken {
+        OAuthToken {
+            access_token: "gho_token".to_string(),
+            id_token: None,
+        }
+    }
+
+    fn flow() -> OAuthStateClaims {
+        oauth_state::new_flow("github", "/", true, None)
+    }
+
+    async fn mock_profile(server: &mut ServerGuard, email: serde_json::Value) {
+        server
+            .mock("GET", "/user")
+            .match_header("authorization", "Bearer gho_token")
+            .with_header("content-type", "application/json")
+            .with_body(
+                serde_json::json!({"id": 583231, "login": "octocat", "avatar_url": null, "email": email})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+    }
+
+    async fn mock_emails(server: &mut ServerGuard, emails: serde_json::Value) -> mockito::Mock {
+        server
+            .mock("GET", "/user/emails")
+            .match_header("authorization", "Bearer gho_token")
+            .with_header("content-type", "application/json")
+            .with_body(emails.to_string())
+            .create_async()
+            .await
+    }
+
+    #[actix_web::test]
+    async fn public_profile_email_is_verified_by_the_emails_list() {
+        for (verified, expected) in [(true, true), (false, false)] {
+            let mut server = Server::new_async().await;
+            mock_profile(&mut server, "Octocat@GitHub.com".into()).await;
+            mock_emails(
+                &mut server,
+                serde_json::json!([
+                    {"email": "octocat@github.com", "primary": false, "verified": verified},
+                    {"email": "primary@example.com", "primary": true, "verified": true},
+                ]),
+            )
+            .await;
+
+            let user = provider(&server)
+                .fetch_user(&token(), &flow())
+                .await
+                .unwrap();
+            assert_eq!(user.subject, "583231");
+            assert_eq!(user.name, "octocat");
+            assert_eq!(user.email, "Octocat@GitHub.com");
+            assert_eq!(user.email_verified, expected);
+        }
+    }
+
+    #[actix_web::test]
+    async fn private_email_falls_back_to_the_verified_primary() {
+        for hidden in [serde_json::Value::Null, "".into()] {
+            let mut server = Server::new_async().await;
+            mock_profile(&mut server, hidden).await;
+            mock_emails(
+                &mut server,
+                serde_json::json!([
+                    {"email": "old@example.com", "primary": false, "verified": true},
+                    {"email": "octocat@example.com", "primary": true, "verified": true},
+                ]),
+            )
+            .await;
+
+            let user = provider(&server)
+                .fetch_user(&token(), &flow())
+                .await
+                .unwrap();
+            assert_eq!(user.email, "octocat@example.com");
+            assert!(user.email_verified);
+        }
+    }
+
+    #[actix_web::test]
+    async fn no_verified_primary_email_is_rejected() {
+        let mut server = Server::new_async().await;
+        mock_profile(&mut server, serde_json::Value::Null).await;
+        mock_emails(
+            &mut server,
+            serde_json::json!([
+                {"email": "unverified@example.com", "primary": true, "verified": false},
+                {"email": "other@example.com", "primary": false, "verified": true},
+            ]),
+        )
+        .await;
 
-    if response.status().is_success() {
-        let user_info = response.json::<GitHubUserResult>().await?;
-        Ok(user_info)
-    } else {
-        // Read the response text to get the error message
-        let error_text = response.text().await?;
-        println!("Error: {}", error_text);
-        let message = "An error occurred while trying to retrieve user information.";
-        Err(From::from(message))
+        let err = provider(&server)
+            .fetch_user(&token(), &flow())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "The GitHub account has no verified primary email"
+        );
     }
 }
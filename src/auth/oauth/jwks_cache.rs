@@ -0,0 +1,163 @@
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{jwk::JwkSet, DecodingKey};
+use reqwest::{header::CACHE_CONTROL, Client};
+use tokio::sync::RwLock;
+
+use super::OAuthError;
+
+// Used when the JWKS response has no usable Cache-Control max-age
+const DEFAULT_MAX_AGE_SECONDS: i64 = 60 * 60;
+// An unknown `kid` triggers a refetch, at most this often, so forged tokens can't hammer the IdP
+const MIN_REFRESH_INTERVAL_SECONDS: i64 = 60;
+
+struct CachedKeys {
+    keys: JwkSet,
+    fetched_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// A provider's signing keys, cached for as long as its Cache-Control allows and
+/// refetched early when a token mentions a `kid` we don't know yet (key rotation).
+pub struct JwksCache {
+    client: Client,
+    jwks_uri: String,
+    cached: RwLock<Option<CachedKeys>>,
+}
+
+impl JwksCache {
+    pub fn new(jwks_uri: &str, client: Client) -> Self {
+        Self {
+            client,
+            jwks_uri: jwks_uri.to_string(),
+            cached: RwLock::new(None),
+        }
+    }
+
+    pub async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, OAuthError> {
+        let now = Utc::now();
+
+        {
+            let cached = self.cached.read().await;
+            if let Some(cached) = cached.as_ref() {
+                if cached.expires_at > now {
+                    if let Some(jwk) = cached.keys.find(kid) {
+                        return Ok(DecodingKey::from_jwk(jwk)?);
+                    }
+                    if now - cached.fetched_at < Duration::seconds(MIN_REFRESH_INTERVAL_SECONDS) {
+                        return Err(OAuthError::from(format!("Unknown signing key `{}`", kid)));
+                    }
+                }
+            }
+        }
+
+        let mut cached = self.cached.write().await;
+        // Another request may have refreshed the keys while we waited for the lock
+        let fresh = match cached.as_ref() {
+            Some(keys)
+                if now - keys.fetched_at < Duration::seconds(MIN_REFRESH_INTERVAL_SECONDS) =>
+            {
+                keys
+            }
+            _ => cached.insert(self.fetch().await?),
+        };
+
+        let jwk = fresh
+            .keys
+            .find(kid)
+            .ok_or_else(|| OAuthError::from(format!("Unknown signing key `{}`", kid)))?;
+        Ok(DecodingKey::from_jwk(jwk)?)
+    }
+
+    async fn fetch(&self) -> Result<CachedKeys, OAuthError> {
+        let response = self.client.get(&self.jwks_uri).send().await?;
+
+        if !response.status().is_success() {
+            let message = "An error occurred while trying to retrieve the signing keys.";
+            return Err(OAuthError::from(message));
+        }
+
+        let max_age = response
+            .headers()
+            .get(CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_max_age)
+            .unwrap_or(DEFAULT_MAX_AGE_SECONDS);
+        let keys = response.json::<JwkSet>().await?;
+
+        let fetched_at = Utc::now();
+        Ok(CachedKeys {
+            keys,
+            fetched_at,
+            expires_at: fetched_at + Duration::seconds(max_age),
+        })
+    }
+}
+
+fn parse_max_age(cache_control: &str) -> Option<i64> {
+    cache_control
+        .split(',')
+        .filter_map(|directive| directive.trim().strip_prefix("max-age="))
+        .find_map(|seconds| seconds.parse::<i64>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Server;
+
+    use super::*;
+
+    const JWKS: &str = r#"{"keys": [{
+        "kty": "EC", "crv": "P-256", "kid": "test-key", "alg": "ES256",
+        "x": "HsWncjXt_tPTniVFxxdOWjMpRuXzTAD0yeVX6Vyffhk",
+        "y": "1pLoFPf1PEA1NmpThVwqKc5lPh5yKx4wpvN-OQHWy_w"
+    }]}"#;
+
+    #[test]
+    fn max_age_is_read_from_cache_control() {
+        assert_eq!(
+            parse_max_age("public, max-age=21600, must-revalidate"),
+            Some(21600)
+        );
+        assert_eq!(parse_max_age("max-age=60"), Some(60));
+        assert_eq!(parse_max_age("no-cache"), None);
+        assert_eq!(parse_max_age("max-age=soon"), None);
+    }
+
+    #[actix_web::test]
+    async fn keys_are_fetched_once_and_unknown_kids_do_not_refetch_right_away() {
+        let mut server = Server::new_async().await;
+        let jwks = server
+            .mock("GET", "/jwks")
+            .with_header("content-type", "application/json")
+            .with_header("cache-control", "public, max-age=3600")
+            .with_body(JWKS)
+            .expect(1)
+            .create_async()
+            .await;
+        let cache = JwksCache::new(&format!("{}/jwks", server.url()), Client::new());
+
+        assert!(cache.decoding_key("test-key").await.is_ok());
+        assert!(cache.decoding_key("test-key").await.is_ok());
+        let err = cache.decoding_key("forged-key").await.err().unwrap();
+        assert_eq!(err.to_string(), "Unknown signing key `forged-key`");
+
+        jwks.assert_async().await;
+    }
+
+    #[actix_web::test]
+    async fn failing_jwks_endpoint_is_an_error() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("GET", "/jwks")
+            .with_status(503)
+            .create_async()
+            .await;
+        let cache = JwksCache::new(&format!("{}/jwks", server.url()), Client::new());
+
+        let err = cache.decoding_key("test-key").await.err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "An error occurred while trying to retrieve the signing keys."
+        );
+    }
+}
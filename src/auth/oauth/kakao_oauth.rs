@@ -1,13 +1,17 @@
-use actix_web::web;
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::error::Error;
 
-use crate::models::AppState;
+use crate::{config::env::Config, models::OAuthStateClaims};
 
-use super::BasicOauthToken;
+use super::{
+    provider::{build_authorize_url, request_token, request_user},
+    OAuthError, OAuthProvider, OAuthToken, UserInfo,
+};
 
+// Mirrors the /v2/user/me payload, not every field is used
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct KakaoUserResult {
     pub id: u64,
@@ -19,6 +23,7 @@ pub struct KakaoUserResult {
     pub for_partner: Option<Partner>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct KakaoAccount {
     pub profile_needs_agreement: Option<bool>,
@@ -47,6 +52,7 @@ pub struct KakaoAccount {
     pub ci_authenticated_at: Option<String>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct Profile {
     pub nickname: Option<String>,
@@ -55,75 +61,108 @@ pub struct Profile {
     pub is_default_image: Option<bool>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 pub struct Partner {
     pub uuid: Option<String>,
 }
 
-pub async fn get_kakao_oauth_token(
-    authorization_code: &str,
-    data: &web::Data<AppState>,
-) -> Result<BasicOauthToken, Box<dyn Error>> {
-    let redirect_url = data.env.kakao_oauth_redirect_url.to_owned();
-    let client_id: String = data.env.kakao_oauth_client_id.to_owned();
-
-    let root_url = "https://kauth.kakao.com/oauth/token";
-
-    let client = Client::new();
-
-    let params = [
-        ("grant_type", "authorization_code"),
-        ("client_id", client_id.as_str()),
-        ("redirect_uri", redirect_url.as_str()),
-        ("code", authorization_code),
-    ];
-
-    let response = client
-        .post(root_url)
-        .header(
-            "Content-Type",
-            "application/x-www-form-urlencoded; charset=UTF-8",
-        )
-        .form(&params)
-        .send()
-        .await?;
+pub struct KakaoOAuthProvider {
+    client: Client,
+    client_id: String,
+    redirect_url: String,
+    pkce: bool,
+}
 
-    if response.status().is_success() {
-        // let response_value: serde_json::Value = response.json().await?;
-        // println!("Response: {:#?}", response_value);
-        // let oauth_response = serde_json::from_value::<KakaoOauthToken>(response_value)?;
-        let oauth_response = response.json::<BasicOauthToken>().await?;
-        Ok(oauth_response)
-    } else {
-        let message = "An error occurred while trying to retrieve the access token.";
-        Err(From::from(message))
+impl KakaoOAuthProvider {
+    pub fn new(config: &Config, client: Client) -> Self {
+        Self {
+            client,
+            client_id: config.kakao_oauth_client_id.to_owned(),
+            redirect_url: config.kakao_oauth_redirect_url.to_owned(),
+            pkce: config.kakao_oauth_pkce,
+        }
     }
 }
 
-pub async fn get_kakao_user(access_token: &str) -> Result<KakaoUserResult, Box<dyn Error>> {
-    let root_url = "https://kapi.kakao.com/v2/user/me";
+#[async_trait]
+impl OAuthProvider for KakaoOAuthProvider {
+    fn name(&self) -> &str {
+        "kakao"
+    }
+
+    fn pkce_enabled(&self) -> bool {
+        self.pkce
+    }
+
+    async fn authorize_url(&self, flow: &OAuthStateClaims) -> Result<String, OAuthError> {
+        build_authorize_url(
+            "https://kauth.kakao.com/oauth/authorize",
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_url.as_str()),
+            ],
+            flow,
+        )
+    }
 
-    let client = Client::new();
+    async fn exchange_code(
+        &self,
+        code: &str,
+        flow: &OAuthStateClaims,
+    ) -> Result<OAuthToken, OAuthError> {
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("code", code),
+        ];
+
+        request_token(
+            &self.client,
+            "https://kauth.kakao.com/oauth/token",
+            &params,
+            flow,
+        )
+        .await
+    }
 
-    let response = client
-        .get(root_url)
-        .header(reqwest::header::USER_AGENT, "blog-rs")
-        .bearer_auth(access_token)
-        .send()
+    async fn fetch_user(
+        &self,
+        token: &OAuthToken,
+        _flow: &OAuthStateClaims,
+    ) -> Result<UserInfo, OAuthError> {
+        let kakao_user: KakaoUserResult = request_user(
+            &self.client,
+            "https://kapi.kakao.com/v2/user/me",
+            &token.access_token,
+        )
         .await?;
 
-    if response.status().is_success() {
-        // let response_value: serde_json::Value = response.json().await?;
-        // println!("Response: {:#?}", response_value);
-        // let user_info = serde_json::from_value::<KakaoUserResult>(response_value)?;
-
-        let user_info = response.json::<KakaoUserResult>().await?;
-        Ok(user_info)
-    } else {
-        // Read the response text to get the error message
-        let error_text = response.text().await?;
-        println!("Error: {}", error_text);
-        let message = "An error occurred while trying to retrieve user information.";
-        Err(From::from(message))
+        let kakao_account_ref = kakao_user.kakao_account.as_ref();
+        let photo = kakao_account_ref
+            .and_then(|account| account.profile.as_ref())
+            .and_then(|profile| profile.thumbnail_image_url.as_deref())
+            .unwrap_or("default.png");
+
+        let name = kakao_account_ref
+            .and_then(|account| account.profile.as_ref())
+            .and_then(|profile| profile.nickname.as_deref())
+            .unwrap_or("Unknown");
+
+        Ok(UserInfo {
+            subject: kakao_user.id.to_string(),
+            name: name.to_string(),
+            email: kakao_account_ref
+                .and_then(|account| account.email.as_deref())
+                .unwrap_or_default()
+                .to_string(),
+            email_verified: kakao_account_ref
+                .and_then(|account| account.is_email_verified)
+                .unwrap_or(false),
+            photo: Some(photo.to_string()),
+            provider: "Kakao".to_string(),
+        })
     }
 }
@@ -1,31 +1,22 @@
 pub mod github_oauth;
 pub mod google_oauth;
+pub mod jwks_cache;
 pub mod kakao_oauth;
 pub mod naver_oauth;
+pub mod oidc_oauth;
+pub mod provider;
+pub mod registry;
 
-use serde::Deserialize;
-
-#[derive(Deserialize)]
-pub struct GoogleOAuthToken {
-    pub access_token: String,
-    pub id_token: String,
-}
-
-#[derive(Deserialize)]
-pub struct BasicOauthToken {
-    pub access_token: String,
-}
-
-pub enum OAuthTokenResponse {
-    Google(GoogleOAuthToken),
-    GitHub(BasicOauthToken),
-    Kakao(BasicOauthToken),
-    Naver(BasicOauthToken),
-}
+pub use provider::{OAuthError, OAuthProvider, OAuthToken};
+pub use registry::OAuthProviderRegistry;
 
 pub struct UserInfo {
+    /// The provider's stable identifier for the account (`sub` in OpenID Connect).
+    pub subject: String,
     pub name: String,
     pub email: String,
+    /// Whether the provider vouches for `email`, unverified emails never match existing accounts.
+    pub email_verified: bool,
     pub photo: Option<String>,
     pub provider: String,
 }
@@ -1,12 +1,16 @@
-use actix_web::web;
+use async_trait::async_trait;
 use reqwest::Client;
 use serde::Deserialize;
-use std::error::Error;
 
-use crate::models::AppState;
+use crate::{config::env::Config, models::OAuthStateClaims};
 
-use super::BasicOauthToken;
+use super::{
+    provider::{build_authorize_url, request_token, request_user},
+    OAuthError, OAuthProvider, OAuthToken, UserInfo,
+};
 
+// Mirrors the /v1/nid/me payload, not every field is used
+#[allow(dead_code)]
 #[derive(Deserialize)]
 pub struct NaverUserResult {
     pub resultcode: String,
@@ -14,6 +18,7 @@ pub struct NaverUserResult {
     pub response: NaverUserResponse,
 }
 
+#[allow(dead_code)]
 #[derive(Deserialize)]
 pub struct NaverUserResponse {
     pub id: String,
@@ -28,64 +33,90 @@ pub struct NaverUserResponse {
     pub mobile: Option<String>,
 }
 
-pub async fn get_naver_oauth_token(
-    authorization_code: &str,
-    data: &web::Data<AppState>,
-) -> Result<BasicOauthToken, Box<dyn Error>> {
-    let redirect_url = data.env.naver_oauth_redirect_url.to_owned();
-    let client_id = data.env.naver_oauth_client_id.to_owned();
-    let client_secret = data.env.naver_oauth_client_secret.to_owned();
-
-    let root_url = "https://nid.naver.com/oauth2.0/token";
+pub struct NaverOAuthProvider {
+    client: Client,
+    client_id: String,
+    client_secret: String,
+    redirect_url: String,
+    pkce: bool,
+}
 
-    let client = Client::new();
+impl NaverOAuthProvider {
+    pub fn new(config: &Config, client: Client) -> Self {
+        Self {
+            client,
+            client_id: config.naver_oauth_client_id.to_owned(),
+            client_secret: config.naver_oauth_client_secret.to_owned(),
+            redirect_url: config.naver_oauth_redirect_url.to_owned(),
+            pkce: config.naver_oauth_pkce,
+        }
+    }
+}
 
-    let params = [
-        ("grant_type", "authorization_code"),
-        ("client_id", client_id.as_str()),
-        ("client_secret", client_secret.as_str()),
-        ("redirect_uri", redirect_url.as_str()),
-        ("code", authorization_code),
-    ];
+#[async_trait]
+impl OAuthProvider for NaverOAuthProvider {
+    fn name(&self) -> &str {
+        "naver"
+    }
 
-    let response = client.post(root_url).form(&params).send().await?;
+    fn pkce_enabled(&self) -> bool {
+        self.pkce
+    }
 
-    if response.status().is_success() {
-        // let response_value: serde_json::Value = response.json().await?;
-        // println!("Response: {:#?}", response_value);
-        // let oauth_response = serde_json::from_value::<NaverOauthToken>(response_value)?;
-        let oauth_response = response.json::<BasicOauthToken>().await?;
-        Ok(oauth_response)
-    } else {
-        let message = "An error occurred while trying to retrieve the access token.";
-        Err(From::from(message))
+    async fn authorize_url(&self, flow: &OAuthStateClaims) -> Result<String, OAuthError> {
+        build_authorize_url(
+            "https://nid.naver.com/oauth2.0/authorize",
+            &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_url.as_str()),
+            ],
+            flow,
+        )
     }
-}
 
-pub async fn get_naver_user(access_token: &str) -> Result<NaverUserResult, Box<dyn Error>> {
-    let root_url = "https://openapi.naver.com/v1/nid/me";
+    async fn exchange_code(
+        &self,
+        code: &str,
+        flow: &OAuthStateClaims,
+    ) -> Result<OAuthToken, OAuthError> {
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("client_id", self.client_id.as_str()),
+            ("client_secret", self.client_secret.as_str()),
+            ("redirect_uri", self.redirect_url.as_str()),
+            ("code", code),
+        ];
 
-    let client = Client::new();
+        request_token(
+            &self.client,
+            "https://nid.naver.com/oauth2.0/token",
+            &params,
+            flow,
+        )
+        .await
+    }
 
-    let response = client
-        .get(root_url)
-        .header(reqwest::header::USER_AGENT, "blog-rs")
-        .bearer_auth(access_token)
-        .send()
+    async fn fetch_user(
+        &self,
+        token: &OAuthToken,
+        _flow: &OAuthStateClaims,
+    ) -> Result<UserInfo, OAuthError> {
+        let naver_user: NaverUserResult = request_user(
+            &self.client,
+            "https://openapi.naver.com/v1/nid/me",
+            &token.access_token,
+        )
         .await?;
 
-    if response.status().is_success() {
-        // let response_value: serde_json::Value = response.json().await?;
-        // println!("Response: {:#?}", response_value);
-        // let user_info = serde_json::from_value::<NaverUserResult>(response_value)?;
-
-        let user_info = response.json::<NaverUserResult>().await?;
-        Ok(user_info)
-    } else {
-        // Read the response text to get the error message
-        let error_text = response.text().await?;
-        println!("Error: {}", error_text);
-        let message = "An error occurred while trying to retrieve user information.";
-        Err(From::from(message))
+        Ok(UserInfo {
+            subject: naver_user.response.id,
+            name: naver_user.response.nickname,
+            email: naver_user.response.email,
+            // Naver doesn't tell whether the email was verified
+            email_verified: false,
+            photo: Some(naver_user.response.profile_image),
+            provider: "Naver".to_string(),
+        })
     }
 }
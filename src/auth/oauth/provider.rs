@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use reqwest::{Client, IntoUrl};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::error::Error;
+
+use crate::{auth::crypto::pkce_challenge, models::OAuthStateClaims};
+
+use super::UserInfo;
+
+pub type OAuthError = Box<dyn Error + Send + Sync>;
+
+/// Token endpoint response, providers add more fields but these are all we use.
+#[derive(Deserialize)]
+pub struct OAuthToken {
+    pub access_token: String,
+    /// Only returned by OpenID Connect providers.
+    pub id_token: Option<String>,
+}
+
+/// An authorization code flow provider reachable at `/api/sessions/oauth/{name}`.
+#[async_trait]
+pub trait OAuthProvider: Send + Sync {
+    /// The `{provider}` path segment, e.g. `google`.
+    fn name(&self) -> &str;
+
+    /// Whether logins send a PKCE challenge, see `{PROVIDER}_OAUTH_PKCE`.
+    fn pkce_enabled(&self) -> bool;
+
+    /// Where the browser is sent to grant access for this flow.
+    async fn authorize_url(&self, flow: &OAuthStateClaims) -> Result<String, OAuthError>;
+
+    /// Trades the authorization code from the callback for an access token.
+    async fn exchange_code(
+        &self,
+        code: &str,
+        flow: &OAuthStateClaims,
+    ) -> Result<OAuthToken, OAuthError>;
+
+    async fn fetch_user(
+        &self,
+        token: &OAuthToken,
+        flow: &OAuthStateClaims,
+    ) -> Result<UserInfo, OAuthError>;
+}
+
+/// Posts the token request form shared by every provider,
+/// adding the flow's PKCE `code_verifier` when there is one.
+pub async fn request_token(
+    client: &Client,
+    token_url: &str,
+    params: &[(&str, &str)],
+    flow: &OAuthStateClaims,
+) -> Result<OAuthToken, OAuthError> {
+    let mut params = params.to_vec();
+    if let Some(code_verifier) = flow.code_verifier.as_deref() {
+        params.push(("code_verifier", code_verifier));
+    }
+
+    let response = client
+        .post(token_url)
+        // GitHub answers with a form encoded body unless asked for JSON
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&params)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        let oauth_response = response.json::<OAuthToken>().await?;
+        Ok(oauth_response)
+    } else {
+        let message = "An error occurred while trying to retrieve the access token.";
+        Err(From::from(message))
+    }
+}
+
+/// Fetches a user profile with the access token as bearer.
+pub async fn request_user<T: DeserializeOwned>(
+    client: &Client,
+    url: impl IntoUrl,
+    access_token: &str,
+) -> Result<T, OAuthError> {
+    let response = client
+        .get(url)
+        .header(reqwest::header::USER_AGENT, "blog-rs")
+        .bearer_auth(access_token)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        let user_info = response.json::<T>().await?;
+        Ok(user_info)
+    } else {
+        // Read the response text to get the error message
+        let error_text = response.text().await?;
+        log::warn!("Error: {}", error_text);
+        let message = "An error occurred while trying to retrieve user information.";
+        Err(From::from(message))
+    }
+}
+
+/// Builds an authorization URL from its endpoint and query parameters,
+/// adding the flow's `state` and PKCE challenge.
+pub fn build_authorize_url(
+    endpoint: &str,
+    params: &[(&str, &str)],
+    flow: &OAuthStateClaims,
+) -> Result<String, OAuthError> {
+    let mut url = reqwest::Url::parse(endpoint)
+        .map_err(|e| format!("Invalid authorization endpoint {}: {}", endpoint, e))?;
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+        query.append_pair("state", &flow.state);
+        if let Some(code_verifier) = flow.code_verifier.as_deref() {
+            query.append_pair("code_challenge", &pkce_challenge(code_verifier));
+            query.append_pair("code_challenge_method", "S256");
+        }
+    }
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+    use crate::auth::oauth_state;
+
+    #[test]
+    fn authorize_url_appends_encoded_params_and_the_state() {
+        let mut flow = oauth_state::new_flow("test", "/", false, None);
+        flow.state = "abc".to_string();
+
+        let url = build_authorize_url(
+            "https://provider.example/authorize?prompt=consent",
+            &[
+                ("redirect_uri", "https://app.example/callback?x=1"),
+                ("scope", "openid email"),
+            ],
+            &flow,
+        )
+        .unwrap();
+        assert_eq!(
+            url,
+            "https://provider.example/authorize?prompt=consent\
+             &redirect_uri=https%3A%2F%2Fapp.example%2Fcallback%3Fx%3D1&scope=openid+email\
+             &state=abc"
+        );
+    }
+
+    #[test]
+    fn authorize_url_carries_the_s256_challenge_of_the_flow() {
+        let mut flow = oauth_state::new_flow("test", "/", true, None);
+        flow.state = "abc".to_string();
+        flow.code_verifier = Some("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string());
+
+        let url = build_authorize_url("https://provider.example/authorize", &[], &flow).unwrap();
+        assert_eq!(
+            url,
+            "https://provider.example/authorize?state=abc\
+             &code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM&code_challenge_method=S256"
+        );
+    }
+
+    #[test]
+    fn invalid_authorize_endpoint_is_an_error() {
+        let flow = oauth_state::new_flow("test", "/", false, None);
+        let err = build_authorize_url("not a url", &[], &flow).unwrap_err();
+        assert!(err.to_string().contains("not a url"), "{}", err);
+    }
+
+    #[actix_web::test]
+    async fn token_request_sends_the_code_verifier_only_with_pkce() {
+        for pkce in [true, false] {
+            let flow = oauth_state::new_flow("test", "/", pkce, None);
+            let verifier = Matcher::UrlEncoded(
+                "code_verifier".to_string(),
+                flow.code_verifier.clone().unwrap_or_default(),
+            );
+
+            let mut server = mockito::Server::new_async().await;
+            let token_endpoint = server
+                .mock("POST", "/token")
+                .match_body(if pkce {
+                    Matcher::AllOf(vec![
+                        Matcher::UrlEncoded("code".to_string(), "abc".to_string()),
+                        verifier,
+                    ])
+                } else {
+                    Matcher::Regex("^code=abc$".to_string())
+                })
+                .with_header("content-type", "application/json")
+                .with_body(r#"{"access_token": "at", "token_type": "bearer"}"#)
+                .create_async()
+                .await;
+
+            let token = request_token(
+                &Client::new(),
+                &format!("{}/token", server.url()),
+                &[("code", "abc")],
+                &flow,
+            )
+            .await
+            .unwrap();
+            assert_eq!(token.access_token, "at");
+            token_endpoint.assert_async().await;
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use reqwest::Client;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::config::env::Config;
+
+use super::{
+    github_oauth::GitHubOAuthProvider, google_oauth::GoogleOAuthProvider,
+    kakao_oauth::KakaoOAuthProvider, naver_oauth::NaverOAuthProvider,
+    oidc_oauth::OidcOAuthProvider, OAuthProvider,
+};
+
+/// Providers keyed by the `{provider}` path segment.
+#[derive(Default)]
+pub struct OAuthProviderRegistry {
+    providers: HashMap<String, Arc<dyn OAuthProvider>>,
+}
+
+impl OAuthProviderRegistry {
+    pub fn from_config(config: &Config) -> Self {
+        let client = Client::new();
+
+        let mut registry = Self::default();
+        registry.register(GoogleOAuthProvider::new(config, client.clone()));
+        registry.register(GitHubOAuthProvider::new(config, client.clone()));
+        registry.register(NaverOAuthProvider::new(config, client.clone()));
+        registry.register(KakaoOAuthProvider::new(config, client.clone()));
+
+        for oidc_config in &config.oidc_providers {
+            assert!(
+                registry.get(&oidc_config.name).is_none(),
+                "OIDC provider name `{}` is already taken",
+                oidc_config.name
+            );
+            registry.register(OidcOAuthProvider::new(oidc_config.clone(), client.clone()));
+        }
+        registry
+    }
+
+    pub fn register(&mut self, provider: impl OAuthProvider + 'static) {
+        self.providers
+            .insert(provider.name().to_string(), Arc::new(provider));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn OAuthProvider>> {
+        self.providers.get(name).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::{
+        auth::oauth::{OAuthError, OAuthToken, UserInfo},
+        models::OAuthStateClaims,
+    };
+
+    struct StubProvider(&'static str);
+
+    #[async_trait]
+    impl OAuthProvider for StubProvider {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn pkce_enabled(&self) -> bool {
+            false
+        }
+
+        async fn authorize_url(&self, _flow: &OAuthStateClaims) -> Result<String, OAuthError> {
+            Err(From::from("not used"))
+        }
+
+        async fn exchange_code(
+            &self,
+            _code: &str,
+            _flow: &OAuthStateClaims,
+        ) -> Result<OAuthToken, OAuthError> {
+            Err(From::from("not used"))
+        }
+
+        async fn fetch_user(
+            &self,
+            _token: &OAuthToken,
+            _flow: &OAuthStateClaims,
+        ) -> Result<UserInfo, OAuthError> {
+            Err(From::from("not used"))
+        }
+    }
+
+    #[test]
+    fn providers_are_found_by_name() {
+        let mut registry = OAuthProviderRegistry::default();
+        registry.register(StubProvider("first"));
+        registry.register(StubProvider("second"));
+
+        assert_eq!(registry.get("first").unwrap().name(), "first");
+        assert_eq!(registry.get("second").unwrap().name(), "second");
+        assert!(registry.get("Second").is_none());
+        assert!(registry.get("unknown").is_none());
+    }
+}
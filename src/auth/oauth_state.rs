@@ -0,0 +1,152 @@
+use actix_web::cookie::{time::Duration as ActixWebDuration, Cookie, SameSite};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+
+use crate::models::OAuthStateClaims;
+
+use super::crypto::random_token;
+
+pub const OAUTH_STATE_COOKIE: &str = "oauth_state";
+
+// Long enough to sign in at the provider, short enough to be useless if leaked
+const OAUTH_STATE_MAX_AGE_MINUTES: i64 = 10;
+
+pub fn new_flow(
+    provider: &str,
+    redirect_to: &str,
+    pkce: bool,
+    link_user_id: Option<String>,
+) -> OAuthStateClaims {
+    let now = Utc::now();
+    OAuthStateClaims {
+        state: random_token(32),
+        provider: provider.to_string(),
+        redirect_to: redirect_to.to_string(),
+        // 32 bytes encode to 43 characters, the minimum length RFC 7636 allows
+        code_verifier: pkce.then(|| random_token(32)),
+        nonce: random_token(16),
+        link_user_id,
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(OAUTH_STATE_MAX_AGE_MINUTES)).timestamp() as usize,
+    }
+}
+
+pub fn sign(
+    claims: &OAuthStateClaims,
+    secret: &str,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    encode(
+        &Header::default(),
+        claims,
+        &EncodingKey::from_secret(secret.as_ref()),
+    )
+}
+
+pub fn verify(
+    cookie_value: &str,
+    secret: &str,
+) -> Result<OAuthStateClaims, jsonwebtoken::errors::Error> {
+    decode::<OAuthStateClaims>(
+        cookie_value,
+        &DecodingKey::from_secret(secret.as_ref()),
+        &Validation::new(Algorithm::HS256),
+    )
+    .map(|data| data.claims)
+}
+
+pub fn cookie(value: String) -> Cookie<'static> {
+    Cookie::build(OAUTH_STATE_COOKIE, value)
+        // Lax so the cookie comes back on the provider's top level redirect
+        .same_site(SameSite::Lax)
+        .secure(true)
+        .path("/api/sessions/oauth")
+        .max_age(ActixWebDuration::minutes(OAUTH_STATE_MAX_AGE_MINUTES))
+        .http_only(true)
+        .finish()
+}
+
+pub fn removal_cookie() -> Cookie<'static> {
+    Cookie::build(OAUTH_STATE_COOKIE, "")
+        .path("/api/sessions/oauth")
+        .max_age(ActixWebDuration::new(-1, 0))
+        .http_only(true)
+        .finish()
+}
+
+/// Only paths on our own client are accepted, `//evil.com` or `https://...` are not.
+pub fn is_safe_redirect(path: &str) -> bool {
+    path.starts_with('/')
+        && !path.starts_with("//")
+        && !path.starts_with("/\\")
+        && !path.chars().any(|c| c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_flow_round_trips_only_with_the_same_secret() {
+        let flow = new_flow("github", "/posts/1", false, None);
+        let signed = sign(&flow, "secret").unwrap();
+
+        let verified = verify(&signed, "secret").unwrap();
+        assert_eq!(verified.state, flow.state);
+        assert_eq!(verified.provider, "github");
+        assert_eq!(verified.redirect_to, "/posts/1");
+
+        assert!(verify(&signed, "other-secret").is_err());
+        assert!(verify(&format!("{}x", signed), "secret").is_err());
+    }
+
+    #[test]
+    fn every_flow_gets_its_own_state() {
+        assert_ne!(
+            new_flow("google", "/", false, None).state,
+            new_flow("google", "/", false, None).state
+        );
+    }
+
+    #[test]
+    fn expired_flow_is_rejected() {
+        let mut flow = new_flow("google", "/", false, None);
+        let past = (Utc::now() - Duration::minutes(OAUTH_STATE_MAX_AGE_MINUTES + 5)).timestamp();
+        flow.iat = past as usize;
+        flow.exp = (past + 60) as usize;
+
+        let signed = sign(&flow, "secret").unwrap();
+        assert!(verify(&signed, "secret").is_err());
+    }
+
+    #[test]
+    fn only_client_paths_are_safe_redirects() {
+        for path in ["/", "/posts/1?tab=comments", "/a//b"] {
+            assert!(is_safe_redirect(path), "{}", path);
+        }
+        for path in [
+            "",
+            "posts",
+            "//evil.com",
+            "/\\evil.com",
+            "https://evil.com",
+            "/posts\n/1",
+        ] {
+            assert!(!is_safe_redirect(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn pkce_verifier_is_only_created_when_asked_for() {
+        let flow = new_flow("google", "/", true, None);
+        let verifier = flow.code_verifier.clone().unwrap();
+        assert_eq!(verifier.len(), 43);
+
+        let signed = sign(&flow, "secret").unwrap();
+        assert_eq!(
+            verify(&signed, "secret").unwrap().code_verifier,
+            Some(verifier)
+        );
+
+        assert!(new_flow("naver", "/", false, None).code_verifier.is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::{Duration, Utc};
+
+use crate::{
+    models::{AppState, OneTimeToken},
+    repository::RepositoryError,
+};
+
+use super::crypto::{random_token, sha256_hex};
+
+pub const PASSWORD_RESET: &str = "password-reset";
+pub const EMAIL_CHANGE: &str = "email-change";
+pub const EMAIL_CHANGE_APPROVAL: &str = "email-change-approval";
+pub const MAGIC_LINK: &str = "magic-link";
+pub const WEBAUTHN_REGISTRATION: &str = "webauthn-registration";
+pub const WEBAUTHN_LOGIN: &str = "webauthn-login";
+pub const WEBAUTHN_MFA: &str = "webauthn-mfa";
+
+/// Creates a token for `purpose` and returns the plain value to mail, only its hash is kept.
+pub async fn issue(
+    purpose: &str,
+    user_id: Option<&str>,
+    email: &str,
+    max_age_minutes: i64,
+    data: &AppState,
+) -> Result<String, RepositoryError> {
+    issue_with_payload(purpose, user_id, email, None, max_age_minutes, data).await
+}
+
+/// Like `issue`, with state that comes back from `redeem` in `OneTimeToken::payload`.
+pub async fn issue_with_payload(
+    purpose: &str,
+    user_id: Option<&str>,
+    email: &str,
+    payload: Option<String>,
+    max_age_minutes: i64,
+    data: &AppState,
+) -> Result<String, RepositoryError> {
+    let token = random_token(32);
+    let now = Utc::now();
+
+    data.one_time_tokens
+        .insert(OneTimeToken {
+            token_hash: sha256_hex(&token),
+            purpose: purpose.to_string(),
+            user_id: user_id.map(str::to_string),
+            email: email.to_string(),
+            payload,
+            created_at: now,
+            expires_at: now + Duration::minutes(max_age_minutes),
+        })
+        .await?;
+    Ok(token)
+}
+
+/// Uses up the token, `None` when it is unknown, already used, for another purpose or expired.
+pub async fn redeem(
+    token: &str,
+    purpose: &str,
+    data: &AppState,
+) -> Result<Option<OneTimeToken>, RepositoryError> {
+    let stored = data
+        .one_time_tokens
+        .consume(&sha256_hex(token), purpose)
+        .await?;
+    Ok(stored.filter(|stored| stored.expires_at > Utc::now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn only_the_plain_token_redeems_once_before_expiry() {
+        let data = AppState::for_tests();
+
+        let token = issue(PASSWORD_RESET, None, "alice@example.com", 5, &data)
+            .await
+            .unwrap();
+        assert!(redeem(&sha256_hex(&token), PASSWORD_RESET, &data)
+            .await
+            .unwrap()
+            .is_none());
+        let stored = redeem(&token, PASSWORD_RESET, &data).await.unwrap();
+        assert_eq!(stored.unwrap().email, "alice@example.com");
+        assert!(redeem(&token, PASSWORD_RESET, &data)
+            .await
+            .unwrap()
+            .is_none());
+
+        let expired = issue(PASSWORD_RESET, None, "alice@example.com", -1, &data)
+            .await
+            .unwrap();
+        assert!(redeem(&expired, PASSWORD_RESET, &data)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
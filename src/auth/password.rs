@@ -0,0 +1,134 @@
+use argon2::{
+    password_hash::{
+        self, rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Algorithm, Argon2, Params, Version,
+};
+use std::sync::OnceLock;
+
+pub enum PasswordCheck {
+    Invalid,
+    Valid,
+    /// The password matched but was hashed with outdated parameters.
+    ValidNeedsRehash,
+}
+
+fn argon2(params: Params) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+pub fn hash_password(password: &str, params: Params) -> Result<String, password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2(params).hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+/// Verifies `password` against a PHC string produced by [`hash_password`].
+///
+/// The comparison itself is constant-time, and the hash's embedded parameters
+/// are used so that passwords hashed with older settings keep working.
+pub fn verify_password(password: &str, password_hash: &str, params: Params) -> PasswordCheck {
+    let parsed = match PasswordHash::new(password_hash) {
+        Ok(parsed) => parsed,
+        Err(_) => return PasswordCheck::Invalid,
+    };
+
+    if argon2(params.clone())
+        .verify_password(password.as_bytes(), &parsed)
+        .is_err()
+    {
+        return PasswordCheck::Invalid;
+    }
+
+    if needs_rehash(&parsed, &params) {
+        PasswordCheck::ValidNeedsRehash
+    } else {
+        PasswordCheck::Valid
+    }
+}
+
+/// Burns the same amount of work as a real verification, so that looking up
+/// an unknown email takes as long as a wrong password.
+pub fn dummy_verify(password: &str, params: Params) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+    let hash = DUMMY_HASH.get_or_init(|| {
+        hash_password("dummy-password", params.clone()).expect("Failed to hash dummy password")
+    });
+    let _ = verify_password(password, hash, params);
+}
+
+fn needs_rehash(hash: &PasswordHash, params: &Params) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident() || hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+
+    match Params::try_from(hash) {
+        Ok(current) => {
+            current.m_cost() != params.m_cost()
+                || current.t_cost() != params.t_cost()
+                || current.p_cost() != params.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Far below the production cost, these only need to be distinguishable
+    fn params(m_cost: u32) -> Params {
+        Params::new(m_cost, 1, 1, None).unwrap()
+    }
+
+    #[test]
+    fn hashes_verify_only_the_original_password() {
+        let hash = hash_password("correct horse", params(64)).unwrap();
+        assert!(hash.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+        assert_ne!(hash, hash_password("correct horse", params(64)).unwrap());
+
+        assert!(matches!(
+            verify_password("correct horse", &hash, params(64)),
+            PasswordCheck::Valid
+        ));
+        assert!(matches!(
+            verify_password("battery staple", &hash, params(64)),
+            PasswordCheck::Invalid
+        ));
+    }
+
+    #[test]
+    fn malformed_or_empty_hashes_never_match() {
+        for stored in ["", "plaintext", "$argon2id$v=19$garbage"] {
+            assert!(matches!(
+                verify_password("", stored, params(64)),
+                PasswordCheck::Invalid
+            ));
+        }
+    }
+
+    #[test]
+    fn outdated_parameters_ask_for_a_rehash() {
+        let hash = hash_password("correct horse", params(64)).unwrap();
+        assert!(matches!(
+            verify_password("correct horse", &hash, params(128)),
+            PasswordCheck::ValidNeedsRehash
+        ));
+        // A wrong password is rejected before the parameters are looked at
+        assert!(matches!(
+            verify_password("battery staple", &hash, params(128)),
+            PasswordCheck::Invalid
+        ));
+
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, params(64))
+            .hash_password(b"correct horse", &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+        assert!(matches!(
+            verify_password("correct horse", &argon2i, params(64)),
+            PasswordCheck::ValidNeedsRehash
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use actix_web::{
+    dev::Payload,
+    error::{Error as ActixWebError, ErrorForbidden, ErrorUnauthorized},
+    web, FromRequest, HttpRequest,
+};
+use serde_json::json;
+use std::{future::Future, marker::PhantomData, ops::Deref, pin::Pin};
+
+use crate::models::AppState;
+
+use super::{mfa, token_guard::AuthenticationGuard};
+
+/// A role a route can demand through `RequireRole`.
+pub trait RoleRequirement {
+    const ROLE: &'static str;
+}
+
+pub struct Admin;
+
+impl RoleRequirement for Admin {
+    const ROLE: &'static str = "admin";
+}
+
+/// `AuthenticationGuard` that also requires the token's role to grant `R::ROLE`
+/// according to the configured `RoleHierarchy`, e.g. `_: RequireRole<Admin>`.
+pub struct RequireRole<R: RoleRequirement> {
+    pub auth: AuthenticationGuard,
+    _role: PhantomData<R>,
+}
+
+impl<R: RoleRequirement> Deref for RequireRole<R> {
+    type Target = AuthenticationGuard;
+
+    fn deref(&self) -> &Self::Target {
+        &self.auth
+    }
+}
+
+impl<R: RoleRequirement + 'static> FromRequest for RequireRole<R> {
+    type Error = ActixWebError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let auth = AuthenticationGuard::from_request(req, payload);
+        let app_data = req.app_data::<web::Data<AppState>>().cloned();
+
+        Box::pin(async move {
+            let auth = auth.await?;
+
+            let data = app_data.ok_or_else(|| {
+                ErrorUnauthorized(json!({"status": "fail", "message": "Internal Server Error"}))
+            })?;
+
+            if !data.roles.grants(&auth.claims.role, R::ROLE) {
+                return Err(ErrorForbidden(json!({
+                    "status": "fail",
+                    "message": "You don't have permission to access this resource"
+                })));
+            }
+
+            // Roles that must use 2FA can still sign in, but only to set it up
+            if data.env.mfa_required_roles.contains(&auth.claims.role)
+                && !mfa::is_enabled(&auth.user_id, &data).await?
+            {
+                return Err(ErrorForbidden(json!({
+                    "status": "fail",
+                    "message": "Set up two-factor authentication to access this resource"
+                })));
+            }
+
+            Ok(RequireRole {
+                auth,
+                _role: PhantomData,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App, HttpResponse};
+    use chrono::Utc;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        auth::token,
+        models::{TotpFactor, User},
+    };
+
+    async fn admin_only(guard: RequireRole<Admin>) -> HttpResponse {
+        HttpResponse::Ok().body(guard.user_id.clone())
+    }
+
+    #[actix_web::test]
+    async fn missing_tokens_are_unauthorized_and_lacking_roles_forbidden() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .route("/admin", web::get().to(admin_only)),
+        )
+        .await;
+
+        let res =
+            test::call_service(&app, test::TestRequest::get().uri("/admin").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        for (role, status) in [("user", StatusCode::FORBIDDEN), ("admin", StatusCode::OK)] {
+            let user = data
+                .db
+                .insert(User {
+                    role: role.to_string(),
+                    ..User::for_tests(role, &format!("{}@example.com", role))
+                })
+                .await
+                .unwrap();
+            let access_token = token::create_access_token(&user, &data).unwrap();
+
+            let req = test::TestRequest::get()
+                .uri("/admin")
+                .insert_header(("Authorization", format!("Bearer {}", access_token)))
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), status, "role {}", role);
+            if status == StatusCode::FORBIDDEN {
+                let body: Value = test::read_body_json(res).await;
+                assert_eq!(
+                    body["message"],
+                    "You don't have permission to access this resource"
+                );
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn roles_that_need_2fa_are_refused_until_it_is_on() {
+        let mut data = AppState::for_tests();
+        data.env.mfa_required_roles = vec!["admin".to_string()];
+        let data = web::Data::new(data);
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .route("/admin", web::get().to(admin_only)),
+        )
+        .await;
+        let admin = data
+            .db
+            .insert(User {
+                role: "admin".to_string(),
+                ..User::for_tests("admin", "admin@example.com")
+            })
+            .await
+            .unwrap();
+        let access_token = token::create_access_token(&admin, &data).unwrap();
+        let request = || {
+            test::TestRequest::get()
+                .uri("/admin")
+                .insert_header(("Authorization", format!("Bearer {}", access_token)))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, request()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        data.two_factor
+            .save_totp(TotpFactor {
+                user_id: "admin".to_string(),
+                secret: "JBSWY3DPEHPK3PXP".to_string(),
+                enabled: true,
+                last_used_step: None,
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let res = test::call_service(&app, request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
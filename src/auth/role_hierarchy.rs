@@ -0,0 +1,106 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::config::env::Config;
+
+/// Which roles include the permissions of which others, from `ROLE_HIERARCHY`.
+#[derive(Default)]
+pub struct RoleHierarchy {
+    // role -> roles it directly inherits
+    inherits: HashMap<String, Vec<String>>,
+}
+
+impl RoleHierarchy {
+    pub fn from_config(config: &Config) -> Self {
+        let mut hierarchy = Self::default();
+        for (role, inherited) in &config.role_hierarchy {
+            hierarchy
+                .inherits
+                .entry(role.clone())
+                .or_default()
+                .push(inherited.clone());
+        }
+        hierarchy
+    }
+
+    /// Roles mentioned in `ROLE_HIERARCHY`, the only ones that can be assigned.
+    pub fn is_known(&self, role: &str) -> bool {
+        self.inherits
+            .iter()
+            .any(|(parent, inherited)| parent == role || inherited.iter().any(|r| r == role))
+    }
+
+    /// Whether `role` is `required` or inherits it, directly or through other roles.
+    pub fn grants(&self, role: &str, required: &str) -> bool {
+        let mut pending = vec![role];
+        let mut seen = HashSet::new();
+
+        while let Some(current) = pending.pop() {
+            if current == required {
+                return true;
+            }
+            // A misconfigured cycle must not loop forever
+            if !seen.insert(current) {
+                continue;
+            }
+            if let Some(inherited) = self.inherits.get(current) {
+                pending.extend(inherited.iter().map(String::as_str));
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hierarchy(rules: &[(&str, &str)]) -> RoleHierarchy {
+        RoleHierarchy::from_config(&Config {
+            role_hierarchy: rules
+                .iter()
+                .map(|(role, inherited)| (role.to_string(), inherited.to_string()))
+                .collect(),
+            ..Config::for_tests()
+        })
+    }
+
+    #[test]
+    fn roles_grant_what_they_inherit_transitively() {
+        let roles = hierarchy(&[("admin", "moderator"), ("moderator", "user")]);
+
+        for required in ["admin", "moderator", "user"] {
+            assert!(roles.grants("admin", required));
+        }
+        assert!(roles.grants("moderator", "moderator"));
+        assert!(roles.grants("moderator", "user"));
+        assert!(!roles.grants("moderator", "admin"));
+        assert!(!roles.grants("user", "moderator"));
+    }
+
+    #[test]
+    fn unknown_or_missing_roles_are_denied() {
+        let roles = hierarchy(&[("admin", "user")]);
+
+        assert!(!roles.grants("superuser", "user"));
+        // Tokens from before roles existed
+        assert!(!roles.grants("", "user"));
+    }
+
+    #[test]
+    fn only_configured_roles_are_known() {
+        let roles = hierarchy(&[("admin", "moderator"), ("moderator", "user")]);
+
+        for role in ["admin", "moderator", "user"] {
+            assert!(roles.is_known(role));
+        }
+        assert!(!roles.is_known("root"));
+    }
+
+    #[test]
+    fn a_cycle_does_not_loop_forever() {
+        let roles = hierarchy(&[("a", "b"), ("b", "a")]);
+
+        assert!(roles.grants("a", "b"));
+        assert!(!roles.grants("a", "admin"));
+    }
+}
@@ -0,0 +1,272 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+        RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header,
+};
+use openssl::{
+    bn::{BigNum, BigNumContext},
+    nid::Nid,
+    pkey::{Id, PKey, Private},
+};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+
+pub type KeyError = Box<dyn Error + Send + Sync>;
+
+/// Key used to sign and verify our own access tokens.
+///
+/// HS256 keys are shared secrets and never published, RSA (RS256), P-256 (ES256)
+/// and Ed25519 (EdDSA) keys expose their public half through `jwk`.
+pub struct SigningKey {
+    pub kid: Option<String>,
+    pub algorithm: Algorithm,
+    pub jwk: Option<Jwk>,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl SigningKey {
+    pub fn from_secret(secret: &str, kid: Option<String>) -> SigningKey {
+        SigningKey {
+            kid,
+            algorithm: Algorithm::HS256,
+            jwk: None,
+            encoding: EncodingKey::from_secret(secret.as_ref()),
+            decoding: DecodingKey::from_secret(secret.as_ref()),
+        }
+    }
+
+    /// Loads a PEM private key, the algorithm follows from the key type.
+    ///
+    /// Without an explicit `kid` the RFC 7638 thumbprint of the public key is used.
+    pub fn from_pem_file(path: &str, kid: Option<String>) -> Result<SigningKey, KeyError> {
+        let pem = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let pkey = PKey::private_key_from_pem(&pem)?;
+        // jsonwebtoken only reads PKCS#8 for EC and Ed25519 keys
+        let pkcs8 = pkey.private_key_to_pem_pkcs8()?;
+
+        let (algorithm, params, encoding) = match pkey.id() {
+            Id::RSA => (
+                Algorithm::RS256,
+                rsa_parameters(&pkey)?,
+                EncodingKey::from_rsa_pem(&pkcs8)?,
+            ),
+            Id::EC => (
+                Algorithm::ES256,
+                ec_parameters(&pkey)?,
+                EncodingKey::from_ec_pem(&pkcs8)?,
+            ),
+            Id::ED25519 => (
+                Algorithm::EdDSA,
+                ed25519_parameters(&pkey)?,
+                EncodingKey::from_ed_pem(&pkcs8)?,
+            ),
+            _ => return Err(format!("Unsupported key type in {}", path).into()),
+        };
+
+        let kid = kid.unwrap_or_else(|| thumbprint(&params));
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                algorithm: Some(algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+
+        Ok(SigningKey {
+            kid: Some(kid),
+            algorithm,
+            decoding: DecodingKey::from_jwk(&jwk)?,
+            jwk: Some(jwk),
+            encoding,
+        })
+    }
+
+    pub fn header(&self) -> Header {
+        Header {
+            kid: self.kid.clone(),
+            ..Header::new(self.algorithm)
+        }
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding
+    }
+}
+
+fn b64(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn rsa_parameters(pkey: &PKey<Private>) -> Result<AlgorithmParameters, KeyError> {
+    let rsa = pkey.rsa()?;
+    Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: b64(&rsa.n().to_vec()),
+        e: b64(&rsa.e().to_vec()),
+    }))
+}
+
+fn ec_parameters(pkey: &PKey<Private>) -> Result<AlgorithmParameters, KeyError> {
+    let ec = pkey.ec_key()?;
+    if ec.group().curve_name() != Some(Nid::X9_62_PRIME256V1) {
+        return Err("Only P-256 EC keys are supported (ES256)".into());
+    }
+
+    let mut x = BigNum::new()?;
+    let mut y = BigNum::new()?;
+    let mut ctx = BigNumContext::new()?;
+    ec.public_key()
+        .affine_coordinates(ec.group(), &mut x, &mut y, &mut ctx)?;
+
+    // Coordinates are fixed width, keep the leading zeros `to_vec` would drop
+    Ok(AlgorithmParameters::EllipticCurve(
+        EllipticCurveKeyParameters {
+            key_type: EllipticCurveKeyType::EC,
+            curve: EllipticCurve::P256,
+            x: b64(&x.to_vec_padded(32)?),
+            y: b64(&y.to_vec_padded(32)?),
+        },
+    ))
+}
+
+fn ed25519_parameters(pkey: &PKey<Private>) -> Result<AlgorithmParameters, KeyError> {
+    Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+        key_type: OctetKeyPairType::OctetKeyPair,
+        curve: EllipticCurve::Ed25519,
+        x: b64(&pkey.raw_public_key()?),
+    }))
+}
+
+/// JWK thumbprint (RFC 7638), the required members in lexicographic order.
+fn thumbprint(params: &AlgorithmParameters) -> String {
+    let canonical = match params {
+        AlgorithmParameters::RSA(p) => format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, p.e, p.n),
+        AlgorithmParameters::EllipticCurve(p) => format!(
+            r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+            p.x, p.y
+        ),
+        AlgorithmParameters::OctetKeyPair(p) => {
+            format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, p.x)
+        }
+        AlgorithmParameters::OctetKey(p) => format!(r#"{{"k":"{}","kty":"oct"}}"#, p.value),
+    };
+    b64(&Sha256::digest(canonical.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{decode, encode, Validation};
+    use openssl::{ec::EcGroup, ec::EcKey, rsa::Rsa};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn pem_file(pkey: PKey<Private>) -> String {
+        let path = std::env::temp_dir().join(format!("signing-key-{}.pem", Uuid::new_v4()));
+        std::fs::write(&path, pkey.private_key_to_pem_pkcs8().unwrap()).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn round_trip(key: &SigningKey) -> Value {
+        let token = encode(&key.header(), &json!({"sub": "user-1"}), key.encoding_key()).unwrap();
+        let mut validation = Validation::new(key.algorithm);
+        validation.required_spec_claims.clear();
+        validation.validate_exp = false;
+        decode::<Value>(&token, key.decoding_key(), &validation)
+            .unwrap()
+            .claims
+    }
+
+    #[test]
+    fn every_key_type_signs_tokens_it_can_verify() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let keys = [
+            (
+                PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(),
+                Algorithm::RS256,
+            ),
+            (
+                PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap(),
+                Algorithm::ES256,
+            ),
+            (PKey::generate_ed25519().unwrap(), Algorithm::EdDSA),
+        ];
+
+        for (pkey, algorithm) in keys {
+            let path = pem_file(pkey);
+            let key = SigningKey::from_pem_file(&path, None).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(key.algorithm, algorithm);
+            assert_eq!(round_trip(&key)["sub"], "user-1");
+
+            let jwk = key.jwk.as_ref().unwrap();
+            assert_eq!(jwk.common.key_id, key.kid);
+            assert_eq!(key.header().kid, key.kid);
+            // The published half verifies on its own
+            assert!(DecodingKey::from_jwk(jwk).is_ok());
+        }
+    }
+
+    #[test]
+    fn an_explicit_kid_replaces_the_thumbprint() {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let path = pem_file(PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap());
+        let key = SigningKey::from_pem_file(&path, Some("2026-10".to_string())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(key.kid.as_deref(), Some("2026-10"));
+        assert_eq!(key.jwk.unwrap().common.key_id.as_deref(), Some("2026-10"));
+    }
+
+    #[test]
+    fn unsupported_or_missing_keys_are_rejected() {
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        let path = pem_file(PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap());
+        let err = SigningKey::from_pem_file(&path, None).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(err.to_string(), "Only P-256 EC keys are supported (ES256)");
+
+        assert!(SigningKey::from_pem_file("/nonexistent/key.pem", None).is_err());
+    }
+
+    #[test]
+    fn the_secret_key_is_hs256_and_never_published() {
+        let key = SigningKey::from_secret("test-secret", None);
+
+        assert_eq!(key.algorithm, Algorithm::HS256);
+        assert!(key.jwk.is_none() && key.kid.is_none());
+        assert_eq!(round_trip(&key)["sub"], "user-1");
+    }
+
+    #[test]
+    fn thumbprint_matches_rfc_7638() {
+        // The example key of RFC 7638 section 3.1
+        let params = AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tS\
+                oc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQ\
+                R0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTW\
+                hAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw"
+                .to_string(),
+            e: "AQAB".to_string(),
+        });
+
+        assert_eq!(
+            thumbprint(&params),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use actix_web::{
+    cookie::{time::Duration as ActixWebDuration, Cookie, SameSite},
+    error::ErrorInternalServerError,
+    Result as ActixResult,
+};
+use chrono::{prelude::*, Duration};
+use uuid::Uuid;
+
+use crate::models::{AppState, RefreshToken, TokenClaims, User};
+
+use super::{
+    crypto::{random_token, sha256_hex},
+    login_throttle,
+};
+
+pub const ACCESS_TOKEN_COOKIE: &str = "token";
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+
+// The refresh cookie is only needed by `/api/auth/refresh` and `/api/auth/logout`
+const REFRESH_TOKEN_COOKIE_PATH: &str = "/api/auth";
+
+/// An access token plus the refresh token that can renew it.
+pub struct Session {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+pub fn create_access_token(user: &User, data: &AppState) -> ActixResult<String> {
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: user.id.to_owned().unwrap(),
+        jti: Uuid::new_v4().to_string(),
+        role: user.role.to_owned(),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::minutes(data.env.jwt_max_age)).timestamp() as usize,
+    };
+
+    data.keyring
+        .encode(&claims)
+        .map_err(|_| ErrorInternalServerError("Failed to create token"))
+}
+
+/// Stores a new refresh token, continuing `family_id` on rotation or starting a new family on login.
+pub async fn create_refresh_token(
+    user_id: &str,
+    family_id: Option<String>,
+    data: &AppState,
+) -> ActixResult<String> {
+    let token = random_token(32);
+    let now = Utc::now();
+
+    data.refresh_tokens
+        .insert(RefreshToken {
+            token_hash: sha256_hex(&token),
+            family_id: family_id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            user_id: user_id.to_string(),
+            created_at: now,
+            expires_at: now + Duration::minutes(data.env.refresh_token_max_age),
+            rotated_at: None,
+            revoked_at: None,
+        })
+        .await?;
+
+    Ok(token)
+}
+
+pub async fn issue_session(
+    user: &User,
+    family_id: Option<String>,
+    data: &AppState,
+) -> ActixResult<Session> {
+    Ok(Session {
+        access_token: create_access_token(user, data)?,
+        refresh_token: create_refresh_token(user.id.as_ref().unwrap(), family_id, data).await?,
+    })
+}
+
+/// Puts a single access token on the revocation list until it expires.
+pub async fn revoke_access_token(claims: &TokenClaims, data: &AppState) -> ActixResult<()> {
+    data.revoked_tokens
+        .revoke(&claims.jti, claims.exp as i64)
+        .await?;
+    Ok(())
+}
+
+/// Signs the user out everywhere: every access token issued so far and every refresh token.
+pub async fn revoke_all_sessions(user_id: &str, data: &AppState) -> ActixResult<()> {
+    let now = Utc::now();
+    // Anything issued before now is expired by `now + TOKEN_MAXAGE`, so the entry can go then
+    let expires_at = now + Duration::minutes(data.env.jwt_max_age);
+
+    data.revoked_tokens
+        .revoke_user(user_id, now.timestamp(), expires_at.timestamp())
+        .await?;
+    data.refresh_tokens.revoke_user(user_id).await?;
+    Ok(())
+}
+
+/// Signs the user out everywhere and returns a fresh session for the current device.
+pub async fn renew_all_sessions(user: &User, data: &AppState) -> ActixResult<Session> {
+    revoke_all_sessions(user.id.as_ref().unwrap(), data).await?;
+
+    // The revocation covers the whole current second, `iat` can't tell a token issued
+    // right after it apart from the revoked ones
+    let revoked_at = Utc::now();
+    let next_second = 1_000 - revoked_at.timestamp_subsec_millis().min(999) as u64;
+    actix_web::rt::time::sleep(std::time::Duration::from_millis(next_second)).await;
+
+    issue_session(user, None, data).await
+}
+
+/// Drops revocation entries, tokens and login counters that have expired on their own.
+pub async fn purge_expired_tokens(data: &AppState) {
+    match data
+        .revoked_tokens
+        .purge_expired(Utc::now().timestamp())
+        .await
+    {
+        Ok(0) => {}
+        Ok(count) => log::info!("Purged {} expired token revocations", count),
+        Err(e) => log::warn!("Failed to purge token revocations: {}", e),
+    }
+    match data.refresh_tokens.purge_expired().await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Purged {} expired refresh tokens", count),
+        Err(e) => log::warn!("Failed to purge refresh tokens: {}", e),
+    }
+    match data.one_time_tokens.purge_expired().await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Purged {} expired one-time tokens", count),
+        Err(e) => log::warn!("Failed to purge one-time tokens: {}", e),
+    }
+    match login_throttle::purge_expired(data).await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Purged {} stale login attempt counters", count),
+        Err(e) => log::warn!("Failed to purge login attempts: {}", e),
+    }
+}
+
+pub fn access_token_cookie(token: String, data: &AppState) -> Cookie<'static> {
+    Cookie::build(ACCESS_TOKEN_COOKIE, token)
+        .same_site(SameSite::Lax)
+        .secure(true)
+        .path("/")
+        .max_age(ActixWebDuration::minutes(data.env.jwt_max_age))
+        .http_only(true)
+        .finish()
+}
+
+pub fn refresh_token_cookie(token: String, data: &AppState) -> Cookie<'static> {
+    Cookie::build(REFRESH_TOKEN_COOKIE, token)
+        .same_site(SameSite::Strict)
+        .secure(true)
+        .path(REFRESH_TOKEN_COOKIE_PATH)
+        .max_age(ActixWebDuration::minutes(data.env.refresh_token_max_age))
+        .http_only(true)
+        .finish()
+}
+
+pub fn session_cookies(session: &Session, data: &AppState) -> [Cookie<'static>; 2] {
+    [
+        access_token_cookie(session.access_token.clone(), data),
+        refresh_token_cookie(session.refresh_token.clone(), data),
+    ]
+}
+
+pub fn removal_cookies() -> [Cookie<'static>; 2] {
+    [
+        Cookie::build(ACCESS_TOKEN_COOKIE, "")
+            .path("/")
+            .max_age(ActixWebDuration::new(-1, 0))
+            .http_only(true)
+            .finish(),
+        Cookie::build(REFRESH_TOKEN_COOKIE, "")
+            .path(REFRESH_TOKEN_COOKIE_PATH)
+            .max_age(ActixWebDuration::new(-1, 0))
+            .http_only(true)
+            .finish(),
+    ]
+}
@@ -1,16 +1,30 @@
 use actix_web::{
     dev::Payload,
-    error::{Error as ActixWebError, ErrorUnauthorized},
+    error::{Error as ActixWebError, ErrorForbidden, ErrorUnauthorized},
     http, web, FromRequest, HttpRequest,
 };
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
 use serde_json::json;
 use std::{future::Future, pin::Pin};
 
 use crate::models::{AppState, TokenClaims};
 
+use super::token::ACCESS_TOKEN_COOKIE;
+
+/// The access token of a request, from the cookie or an `Authorization: Bearer` header.
+pub fn request_token(req: &HttpRequest) -> Option<String> {
+    req.cookie(ACCESS_TOKEN_COOKIE)
+        .map(|c| c.value().to_string())
+        .or_else(|| {
+            req.headers()
+                .get(http::header::AUTHORIZATION)
+                .and_then(|header| header.to_str().ok())
+                .map(|h| h.trim_start_matches("Bearer ").to_string())
+        })
+}
+
 pub struct AuthenticationGuard {
     pub user_id: String,
+    pub claims: TokenClaims,
 }
 
 impl FromRequest for AuthenticationGuard {
@@ -19,15 +33,7 @@ impl FromRequest for AuthenticationGuard {
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>; // Box::pin
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let extracted_token = req
-            .cookie("token")
-            .map(|c| c.value().to_string())
-            .or_else(|| {
-                req.headers()
-                    .get(http::header::AUTHORIZATION)
-                    .and_then(|header| header.to_str().ok())
-                    .map(|h| h.trim_start_matches("Bearer ").to_string())
-            });
+        let extracted_token = request_token(req);
 
         let app_data = req.app_data::<web::Data<AppState>>().cloned();
 
@@ -43,27 +49,33 @@ impl FromRequest for AuthenticationGuard {
                 ErrorUnauthorized(json!({"status": "fail", "message": "Internal Server Error"}))
             })?;
 
-            let token_data = decode::<TokenClaims>(
-                &token,
-                &DecodingKey::from_secret(data.env.jwt_secret.as_ref()),
-                &Validation::new(Algorithm::HS256),
-            )
-            .map_err(|e| match e.kind() {
-                jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
-                    ErrorUnauthorized(json!({"status": "fail", "message": "Token has expired"}))
-                }
-                _ => ErrorUnauthorized(json!({"status": "fail", "message": "Invalid token"})),
-            })
-            .unwrap();
+            let token_data =
+                data.keyring
+                    .decode::<TokenClaims>(&token)
+                    .map_err(|e| match e.kind() {
+                        jsonwebtoken::errors::ErrorKind::ExpiredSignature => ErrorUnauthorized(
+                            json!({"status": "fail", "message": "Token has expired"}),
+                        ),
+                        _ => {
+                            ErrorUnauthorized(json!({"status": "fail", "message": "Invalid token"}))
+                        }
+                    })?;
 
-            let vec = data.db.lock().await;
+            if data.revoked_tokens.is_revoked(&token_data.claims).await? {
+                return Err(ErrorUnauthorized(
+                    json!({"status": "fail", "message": "Token has been revoked"}),
+                ));
+            }
+
+            let user = data.db.find_by_id(&token_data.claims.sub).await?;
 
-            match vec
-                .iter()
-                .find(|user| user.id.as_ref() == Some(&token_data.claims.sub))
-            {
+            match user {
+                Some(user) if user.suspended => Err(ErrorForbidden(
+                    json!({"status": "fail", "message": "Your account has been suspended"}),
+                )),
                 Some(_) => Ok(AuthenticationGuard {
-                    user_id: token_data.claims.sub,
+                    user_id: token_data.claims.sub.clone(),
+                    claims: token_data.claims,
                 }),
                 None => Err(ErrorUnauthorized(
                     json!({"status": "fail", "message": "User belonging to this token no longer exists"}),
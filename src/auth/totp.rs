@@ -0,0 +1,94 @@
+use chrono::Utc;
+use rand::RngCore;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::crypto::constant_time_eq;
+
+// RFC 6238 defaults, the only ones every authenticator app understands
+const DIGITS: usize = 6;
+const STEP_SECONDS: u64 = 30;
+// Accept the previous and next code too, for clocks that drifted a little
+const SKEW_STEPS: i64 = 1;
+
+/// A new random secret, 160 bits as RFC 4226 recommends, base32 encoded.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+fn build(secret: &str, issuer: &str, account_name: &str) -> Option<TOTP> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(
+        Algorithm::SHA1,
+        DIGITS,
+        SKEW_STEPS as u8,
+        STEP_SECONDS,
+        secret,
+        Some(issuer.to_string()),
+        // ':' separates issuer and account in the URI label
+        account_name.replace(':', ""),
+    )
+    .ok()
+}
+
+/// The `otpauth://totp/...` URI authenticator apps import, usually shown as a QR code.
+pub fn otpauth_url(secret: &str, issuer: &str, account_name: &str) -> Option<String> {
+    build(secret, issuer, account_name).map(|totp| totp.get_url())
+}
+
+/// Returns the time step `code` belongs to, so the caller can refuse to accept it twice.
+pub fn verify(secret: &str, code: &str) -> Option<i64> {
+    let totp = build(secret, "", "")?;
+    let code = code.trim();
+    let current = Utc::now().timestamp() / STEP_SECONDS as i64;
+
+    (current - SKEW_STEPS..=current + SKEW_STEPS).find(|step| {
+        let expected = totp.generate((*step as u64) * STEP_SECONDS);
+        constant_time_eq(&expected, code)
+    })
+}
+
+/// The code an authenticator app shows during `step`.
+#[cfg(test)]
+pub fn code_at(secret: &str, step: i64) -> String {
+    build(secret, "", "")
+        .unwrap()
+        .generate(step as u64 * STEP_SECONDS)
+}
+
+/// The current time step.
+#[cfg(test)]
+pub fn current_step() -> i64 {
+    Utc::now().timestamp() / STEP_SECONDS as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_of_neighbouring_steps_are_accepted() {
+        let secret = generate_secret();
+        let step = current_step();
+
+        for offset in -SKEW_STEPS..=SKEW_STEPS {
+            let code = code_at(&secret, step + offset);
+            assert_eq!(verify(&secret, &format!(" {} ", code)), Some(step + offset));
+        }
+        assert_eq!(verify(&secret, &code_at(&secret, step + 2)), None);
+        assert_eq!(verify(&secret, "not a code"), None);
+        assert_eq!(verify("not base32!", "123456"), None);
+    }
+
+    #[test]
+    fn the_uri_names_issuer_and_account() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), 32);
+
+        let url = otpauth_url(&secret, "blog-rs", "alice:ex@example.com").unwrap();
+        assert!(url.starts_with("otpauth://totp/blog-rs:aliceex%40example.com?"));
+        assert!(url.contains(&format!("secret={}", secret)));
+        assert!(url.contains("issuer=blog-rs"));
+    }
+}
@@ -0,0 +1,121 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use webauthn_rs::prelude::{
+    DiscoverableAuthentication, Passkey, PasskeyAuthentication, RequestChallengeResponse, Url,
+    Webauthn, WebauthnBuilder,
+};
+
+use crate::{
+    config::env::Config,
+    models::{AppState, WebauthnCredential},
+    repository::RepositoryError,
+};
+
+use super::one_time_token;
+
+// Matches the timeout webauthn-rs puts in the options sent to the browser
+const CEREMONY_MAX_AGE: i64 = 5;
+
+/// Server half of a passkey login, for a known user or one found from the passkey itself.
+#[derive(Serialize, Deserialize)]
+pub enum AuthenticationCeremony {
+    Passkey(PasskeyAuthentication),
+    Discoverable(DiscoverableAuthentication),
+}
+
+/// The relying party configured by `WEBAUTHN_RP_*`.
+pub fn from_config(env: &Config) -> Webauthn {
+    let rp_origin = Url::parse(&env.webauthn_rp_origin).expect("WEBAUTHN_RP_ORIGIN must be a URL");
+
+    WebauthnBuilder::new(&env.webauthn_rp_id, &rp_origin)
+        .and_then(|builder| builder.rp_name(&env.webauthn_rp_name).build())
+        .expect("WEBAUTHN_RP_ID must be the host of WEBAUTHN_RP_ORIGIN or a parent domain")
+}
+
+/// Keeps the server half of a ceremony until the browser answers, returns the id to hand out.
+///
+/// Ceremonies are single use one-time tokens so a signed challenge can't be replayed.
+pub async fn save_ceremony<T: Serialize>(
+    purpose: &str,
+    user_id: Option<&str>,
+    state: &T,
+    data: &AppState,
+) -> Result<String, RepositoryError> {
+    let payload =
+        serde_json::to_string(state).map_err(|e| RepositoryError::Backend(e.to_string()))?;
+    one_time_token::issue_with_payload(purpose, user_id, "", Some(payload), CEREMONY_MAX_AGE, data)
+        .await
+}
+
+/// Takes back the ceremony and the user it was started for, `None` when it is unknown,
+/// expired, already used or was started for another purpose.
+pub async fn take_ceremony<T: DeserializeOwned>(
+    ceremony: &str,
+    purpose: &str,
+    data: &AppState,
+) -> Result<Option<(Option<String>, T)>, RepositoryError> {
+    let token = match one_time_token::redeem(ceremony, purpose, data).await? {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+
+    let state = token
+        .payload
+        .as_deref()
+        .and_then(|payload| serde_json::from_str(payload).ok());
+    Ok(state.map(|state| (token.user_id, state)))
+}
+
+/// How credential ids are stored and shown, like in the browser's `PublicKeyCredential.id`.
+pub fn credential_id(raw_id: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(raw_id)
+}
+
+/// Dresses up a discoverable request as the one a user with a passkey gets, for emails
+/// without passkeys, so `login/start` doesn't tell which accounts exist.
+///
+/// The made up credential is derived from the email, asking twice shows the same one.
+pub fn decoy_options(
+    options: RequestChallengeResponse,
+    email: &str,
+    env: &Config,
+) -> Result<Value, serde_json::Error> {
+    let raw_id = Sha256::new()
+        .chain_update(env.jwt_secret.as_bytes())
+        .chain_update(b"webauthn-decoy:")
+        .chain_update(email.as_bytes())
+        .finalize();
+
+    let mut options = serde_json::to_value(options)?;
+    if let Some(object) = options.as_object_mut() {
+        object.remove("mediation");
+    }
+    if let Some(public_key) = options.get_mut("publicKey").and_then(Value::as_object_mut) {
+        public_key.remove("extensions");
+        public_key.insert(
+            "allowCredentials".to_string(),
+            json!([{
+                "type": "public-key",
+                "id": credential_id(&raw_id),
+                "transports": ["internal", "hybrid"]
+            }]),
+        );
+    }
+    Ok(options)
+}
+
+pub fn passkey(credential: &WebauthnCredential) -> Option<Passkey> {
+    match serde_json::from_str(&credential.passkey) {
+        Ok(passkey) => Some(passkey),
+        Err(e) => {
+            log::warn!(
+                "Unreadable WebAuthn credential {}: {}",
+                credential.credential_id,
+                e
+            );
+            None
+        }
+    }
+}
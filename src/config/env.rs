@@ -1,45 +1,297 @@
+use chrono::{DateTime, Utc};
+use webauthn_rs::prelude::Url;
+
+/// A generic OpenID Connect provider, reachable at `/api/sessions/oauth/{name}`.
+#[derive(Debug, Clone)]
+pub struct OidcProviderConfig {
+    pub name: String,
+    /// Stored as `User::provider`, e.g. "Keycloak".
+    pub display_name: String,
+    /// Base URL serving `/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+    pub scopes: String,
+    pub pkce: bool,
+}
+
+/// A previous signing key, still accepted for verification until `retire_at`.
+#[derive(Debug, Clone)]
+pub struct VerificationKeyConfig {
+    pub name: String,
+    /// PEM private key, like `JWT_PRIVATE_KEY_PATH`.
+    pub private_key_path: Option<String>,
+    /// HS256 secret, for tokens signed before moving off `JWT_SECRET`.
+    pub secret: Option<String>,
+    pub kid: Option<String>,
+    pub retire_at: DateTime<Utc>,
+}
+
+/// What a rate limit counts requests by, anonymous requests always count by address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    Ip,
+    /// The `sub` of a valid access token.
+    User,
+    /// The `X-API-Key` header, as sent, so only for routes where something else checks it.
+    ApiKey,
+}
+
+/// A token bucket per client for a group of routes, e.g. `RATE_LIMITS=auth` then `RATE_LIMIT_AUTH_*`.
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    pub name: String,
+    /// Path prefixes, a request counts against the first group listing one of them.
+    pub paths: Vec<String>,
+    /// Requests allowed at once, the size of the bucket.
+    pub burst: u32,
+    /// Requests given back per minute.
+    pub per_minute: u32,
+    pub key: RateLimitKey,
+}
+
+/// How emails leave the server, `MAILER=console|file|smtp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailerKind {
+    Console,
+    File,
+    Smtp,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub client_origin: String,
+    // Public URL of this server, used for links in emails
+    pub api_origin: String,
+    pub database_url: Option<String>,
+    pub database_max_connections: u32,
     pub jwt_secret: String,
+    // PEM private key (RSA, P-256 or Ed25519) for access tokens, HS256 with `jwt_secret` when unset
+    pub jwt_private_key_path: Option<String>,
+    pub jwt_key_id: Option<String>,
+    pub jwt_verification_keys: Vec<VerificationKeyConfig>,
+    #[allow(dead_code)]
     pub jwt_expires_in: String,
     pub jwt_max_age: i64,
+    pub refresh_token_max_age: i64,
+    // Seconds between sweeps of expired revocations and refresh tokens
+    pub token_gc_interval: u64,
+    // (role, inherited role) pairs, e.g. admin inherits everything user can do
+    pub role_hierarchy: Vec<(String, String)>,
+    // Two-factor authentication
+    pub totp_issuer: String,
+    pub mfa_pending_max_age: i64,
+    // Roles that may only use `RequireRole` routes once 2FA is set up
+    pub mfa_required_roles: Vec<String>,
+    // WebAuthn relying party, passkeys only work on `webauthn_rp_origin` and its domain
+    pub webauthn_rp_id: String,
+    pub webauthn_rp_origin: String,
+    pub webauthn_rp_name: String,
+    // Argon2id cost used for local passwords
+    pub argon2_params: argon2::Params,
+    // Failed password logins allowed per account and per client before a lockout
+    pub login_max_attempts: i64,
+    pub login_ip_max_attempts: i64,
+    // Seconds, failures double the wait up to `login_backoff_max` until the lockout
+    pub login_backoff_base: i64,
+    pub login_backoff_max: i64,
+    pub login_lockout: i64,
+    // Take the client address from `Forwarded`/`X-Forwarded-For`, only behind a proxy that sets them
+    pub trust_proxy_headers: bool,
+    // Email
+    pub mailer: MailerKind,
+    pub mail_from: String,
+    pub mail_dir: String,
+    pub smtp_url: Option<String>,
+    pub email_verification_max_age: i64,
+    pub email_resend_interval: i64,
+    pub require_email_verification: bool,
+    pub password_reset_max_age: i64,
+    pub magic_link_max_age: i64,
     // Google
     pub google_oauth_client_id: String,
     pub google_oauth_client_secret: String,
     pub google_oauth_redirect_url: String,
+    pub google_oauth_pkce: bool,
     // Github
     pub github_oauth_client_id: String,
     pub github_oauth_client_secret: String,
     pub github_oauth_redirect_url: String,
+    pub github_oauth_pkce: bool,
     // Naver
     pub naver_oauth_client_id: String,
     pub naver_oauth_client_secret: String,
     pub naver_oauth_redirect_url: String,
+    pub naver_oauth_pkce: bool,
     // Kakao
     pub kakao_oauth_client_id: String,
     pub kakao_oauth_redirect_url: String,
+    pub kakao_oauth_pkce: bool,
+    // Generic OpenID Connect
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    pub rate_limits: Vec<RateLimitConfig>,
 }
 
 impl Config {
     pub fn init() -> Config {
         let client_origin = std::env::var("CLIENT_ORIGIN").expect("CLIENT_ORIGIN must be set");
+        let api_origin = std::env::var("API_ORIGIN")
+            .unwrap_or_else(|_| "https://localhost:8080".to_string())
+            .trim_end_matches('/')
+            .to_string();
+        // Users are kept in memory unless a database is configured
+        let database_url = std::env::var("DATABASE_URL")
+            .ok()
+            .filter(|url| !url.is_empty());
+        let database_max_connections = std::env::var("DATABASE_MAX_CONNECTIONS")
+            .map(|v| v.parse::<u32>().unwrap())
+            .unwrap_or(10);
         let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
+        let jwt_private_key_path = std::env::var("JWT_PRIVATE_KEY_PATH")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let jwt_key_id = std::env::var("JWT_KEY_ID")
+            .ok()
+            .filter(|kid| !kid.is_empty());
+        // Keys being rotated out, e.g. JWT_VERIFICATION_KEYS=old then JWT_VERIFICATION_KEY_OLD_*
+        let jwt_verification_keys = std::env::var("JWT_VERIFICATION_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(verification_key_config)
+            .collect();
         let jwt_expires_in =
             std::env::var("TOKEN_EXPIRED_IN").expect("TOKEN_EXPIRED_IN must be set");
         let jwt_max_age = std::env::var("TOKEN_MAXAGE").expect("TOKEN_MAXAGE must be set");
+        // Minutes, like TOKEN_MAXAGE, defaults to 30 days
+        let refresh_token_max_age = std::env::var("REFRESH_TOKEN_MAXAGE")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(60 * 24 * 30);
+        let token_gc_interval = std::env::var("TOKEN_GC_INTERVAL")
+            .map(|v| v.parse::<u64>().unwrap())
+            .unwrap_or(60 * 60);
+        // ROLE_HIERARCHY=admin>moderator,moderator>user
+        let role_hierarchy = std::env::var("ROLE_HIERARCHY")
+            .unwrap_or_else(|_| "admin>user".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| {
+                let (role, inherited) = rule
+                    .split_once('>')
+                    .unwrap_or_else(|| panic!("ROLE_HIERARCHY rule `{}` must be role>role", rule));
+                (role.trim().to_string(), inherited.trim().to_string())
+            })
+            .collect();
+        // Shown next to the code in authenticator apps, it can't contain ':'
+        let totp_issuer = std::env::var("TOTP_ISSUER").unwrap_or_else(|_| "blog-rs".to_string());
+        assert!(!totp_issuer.contains(':'), "TOTP_ISSUER can't contain ':'");
+        // Minutes to enter the second factor after the password
+        let mfa_pending_max_age = std::env::var("MFA_PENDING_MAXAGE")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(5);
+        let mfa_required_roles = std::env::var("MFA_REQUIRED_ROLES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|role| !role.is_empty())
+            .map(str::to_string)
+            .collect();
+        // Passkeys are bound to the page the browser runs them on, the client by default
+        let webauthn_rp_origin = std::env::var("WEBAUTHN_RP_ORIGIN")
+            .unwrap_or_else(|_| client_origin.clone())
+            .trim_end_matches('/')
+            .to_string();
+        let webauthn_rp_id = std::env::var("WEBAUTHN_RP_ID").unwrap_or_else(|_| {
+            Url::parse(&webauthn_rp_origin)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .expect("WEBAUTHN_RP_ORIGIN must be a URL")
+        });
+        let webauthn_rp_name =
+            std::env::var("WEBAUTHN_RP_NAME").unwrap_or_else(|_| totp_issuer.clone());
+        // Defaults follow the OWASP recommendation for Argon2id
+        let argon2_memory_kib = std::env::var("ARGON2_MEMORY_KIB")
+            .map(|v| v.parse::<u32>().unwrap())
+            .unwrap_or(19 * 1024);
+        let argon2_iterations = std::env::var("ARGON2_ITERATIONS")
+            .map(|v| v.parse::<u32>().unwrap())
+            .unwrap_or(2);
+        let argon2_parallelism = std::env::var("ARGON2_PARALLELISM")
+            .map(|v| v.parse::<u32>().unwrap())
+            .unwrap_or(1);
+        let argon2_params = argon2::Params::new(
+            argon2_memory_kib,
+            argon2_iterations,
+            argon2_parallelism,
+            None,
+        )
+        .expect("ARGON2_* parameters are invalid");
+        let login_max_attempts = std::env::var("LOGIN_MAX_ATTEMPTS")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(5);
+        // Higher, many users can share an address
+        let login_ip_max_attempts = std::env::var("LOGIN_IP_MAX_ATTEMPTS")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(50);
+        let login_backoff_base = std::env::var("LOGIN_BACKOFF_BASE")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(1);
+        let login_backoff_max = std::env::var("LOGIN_BACKOFF_MAX")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(60);
+        // Seconds, also how long failures are remembered
+        let login_lockout = std::env::var("LOGIN_LOCKOUT")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(15 * 60);
+        let trust_proxy_headers = env_flag("TRUST_PROXY_HEADERS", false);
+        let mailer = match std::env::var("MAILER")
+            .unwrap_or_else(|_| "console".to_string())
+            .to_lowercase()
+            .as_str()
+        {
+            "console" => MailerKind::Console,
+            "file" => MailerKind::File,
+            "smtp" => MailerKind::Smtp,
+            other => panic!("MAILER `{}` must be console, file or smtp", other),
+        };
+        let mail_from = std::env::var("MAIL_FROM")
+            .unwrap_or_else(|_| "blog-rs <no-reply@localhost>".to_string());
+        let mail_dir = std::env::var("MAIL_DIR").unwrap_or_else(|_| "mail".to_string());
+        let smtp_url = std::env::var("SMTP_URL").ok().filter(|url| !url.is_empty());
+        // Minutes, defaults to a day
+        let email_verification_max_age = std::env::var("EMAIL_VERIFICATION_MAXAGE")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(60 * 24);
+        // Seconds between two verification emails to the same account
+        let email_resend_interval = std::env::var("EMAIL_RESEND_INTERVAL")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(60);
+        let require_email_verification = env_flag("REQUIRE_EMAIL_VERIFICATION", false);
+        // Minutes a password reset link stays valid
+        let password_reset_max_age = std::env::var("PASSWORD_RESET_MAXAGE")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(30);
+        // Minutes a sign-in link stays valid
+        let magic_link_max_age = std::env::var("MAGIC_LINK_MAXAGE")
+            .map(|v| v.parse::<i64>().unwrap())
+            .unwrap_or(15);
         let google_oauth_client_id =
             std::env::var("GOOGLE_OAUTH_CLIENT_ID").expect("GOOGLE_OAUTH_CLIENT_ID must be set");
         let google_oauth_client_secret = std::env::var("GOOGLE_OAUTH_CLIENT_SECRET")
             .expect("GOOGLE_OAUTH_CLIENT_SECRET must be set");
         let google_oauth_redirect_url = std::env::var("GOOGLE_OAUTH_REDIRECT_URL")
             .expect("GOOGLE_OAUTH_REDIRECT_URL must be set");
+        let google_oauth_pkce = env_flag("GOOGLE_OAUTH_PKCE", true);
         let github_oauth_client_id =
             std::env::var("GITHUB_OAUTH_CLIENT_ID").expect("GITHUB_OAUTH_CLIENT_ID must be set");
         let github_oauth_client_secret = std::env::var("GITHUB_OAUTH_CLIENT_SECRET")
             .expect("GITHUB_OAUTH_CLIENT_SECRET must be set");
         let github_oauth_redirect_url = std::env::var("GITHUB_OAUTH_REDIRECT_URL")
             .expect("GITHUB_OAUTH_REDIRECT_URL must be set");
+        let github_oauth_pkce = env_flag("GITHUB_OAUTH_PKCE", true);
 
         let naver_oauth_client_id =
             std::env::var("NAVER_OAUTH_CLIENT_ID").expect("NAVER_OAUTH_CLIENT_ID must be set");
@@ -50,6 +302,9 @@ impl Config {
         let naver_oauth_redirect_url = std::env::var("NAVER_OAUTH_REDIRECT_URL")
             .expect("NAVER_OAUTH_REDIRECT_URL must be set");
 
+        // Naver's login API doesn't implement PKCE
+        let naver_oauth_pkce = env_flag("NAVER_OAUTH_PKCE", false);
+
         // Kakao
         let kakao_oauth_client_id =
             std::env::var("KAKAO_OAUTH_CLIENT_ID").expect("KAKAO_OAUTH_CLIENT_ID must be set");
@@ -57,22 +312,265 @@ impl Config {
         let kakao_oauth_redirect_url = std::env::var("KAKAO_OAUTH_REDIRECT_URL")
             .expect("KAKAO_OAUTH_REDIRECT_URL must be set");
 
+        let kakao_oauth_pkce = env_flag("KAKAO_OAUTH_PKCE", true);
+
+        // OpenID Connect, e.g. OIDC_PROVIDERS=keycloak then OIDC_KEYCLOAK_* for each name
+        let oidc_providers = std::env::var("OIDC_PROVIDERS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(oidc_provider_config)
+            .collect();
+
+        // Checked in order, so narrower groups go first
+        let rate_limits = std::env::var("RATE_LIMITS")
+            .unwrap_or_else(|_| "auth,oauth,api".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(rate_limit_config)
+            .collect();
+
         Config {
             client_origin,
+            api_origin,
+            database_url,
+            database_max_connections,
             jwt_secret,
+            jwt_private_key_path,
+            jwt_key_id,
+            jwt_verification_keys,
             jwt_expires_in,
             jwt_max_age: jwt_max_age.parse::<i64>().unwrap(),
+            refresh_token_max_age,
+            token_gc_interval,
+            role_hierarchy,
+            totp_issuer,
+            mfa_pending_max_age,
+            mfa_required_roles,
+            webauthn_rp_id,
+            webauthn_rp_origin,
+            webauthn_rp_name,
+            argon2_params,
+            login_max_attempts,
+            login_ip_max_attempts,
+            login_backoff_base,
+            login_backoff_max,
+            login_lockout,
+            trust_proxy_headers,
+            mailer,
+            mail_from,
+            mail_dir,
+            smtp_url,
+            email_verification_max_age,
+            email_resend_interval,
+            require_email_verification,
+            password_reset_max_age,
+            magic_link_max_age,
             google_oauth_client_id,
             google_oauth_client_secret,
             google_oauth_redirect_url,
+            google_oauth_pkce,
             github_oauth_client_id,
             github_oauth_client_secret,
             github_oauth_redirect_url,
+            github_oauth_pkce,
             naver_oauth_client_id,
             naver_oauth_client_secret,
             naver_oauth_redirect_url,
+            naver_oauth_pkce,
             kakao_oauth_client_id,
             kakao_oauth_redirect_url,
+            kakao_oauth_pkce,
+            oidc_providers,
+            rate_limits,
+        }
+    }
+}
+
+fn oidc_provider_config(name: &str) -> OidcProviderConfig {
+    assert!(
+        name.chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+        "OIDC provider name `{}` must be lowercase letters, digits or '-'",
+        name
+    );
+
+    let prefix = format!("OIDC_{}_", name.to_uppercase().replace('-', "_"));
+    let var = |key: &str| std::env::var(format!("{}{}", prefix, key)).ok();
+    let required = |key: &str| var(key).unwrap_or_else(|| panic!("{}{} must be set", prefix, key));
+
+    OidcProviderConfig {
+        name: name.to_string(),
+        display_name: var("DISPLAY_NAME").unwrap_or_else(|| name.to_string()),
+        issuer: required("ISSUER"),
+        client_id: required("CLIENT_ID"),
+        client_secret: var("CLIENT_SECRET").filter(|secret| !secret.is_empty()),
+        redirect_url: required("REDIRECT_URL"),
+        scopes: var("SCOPES").unwrap_or_else(|| "openid email profile".to_string()),
+        pkce: env_flag(&format!("{}PKCE", prefix), true),
+    }
+}
+
+fn rate_limit_config(name: &str) -> RateLimitConfig {
+    let prefix = format!("RATE_LIMIT_{}_", name.to_uppercase().replace('-', "_"));
+    let var = |key: &str| {
+        std::env::var(format!("{}{}", prefix, key))
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+    let number = |key: &str, default: u32| match var(key) {
+        Some(value) => value
+            .parse::<u32>()
+            .ok()
+            .filter(|value| *value > 0)
+            .unwrap_or_else(|| panic!("{}{} must be a positive number", prefix, key)),
+        None => default,
+    };
+
+    // The groups enabled by default, any other name needs its own PATHS
+    let (paths, burst, per_minute, key) = match name {
+        "auth" => (
+            Some(concat!(
+                "/api/auth/login,/api/auth/register,/api/auth/refresh,/api/auth/magic-link,",
+                "/api/auth/forgot-password,/api/auth/reset-password,/api/auth/verify-email/resend,",
+                "/api/auth/mfa/verify,/api/auth/mfa/totp,/api/auth/mfa/recovery-codes,",
+                "/api/auth/webauthn/login,/api/auth/webauthn/mfa",
+            )),
+            10,
+            10,
+            "ip",
+        ),
+        "oauth" => (Some("/api/sessions/oauth"), 20, 20, "ip"),
+        "api" => (Some("/api"), 120, 600, "user"),
+        _ => (None, 60, 60, "ip"),
+    };
+
+    let paths = var("PATHS")
+        .or_else(|| paths.map(str::to_string))
+        .unwrap_or_else(|| panic!("{}PATHS must be set", prefix))
+        .split(',')
+        .map(|path| path.trim().trim_end_matches('/').to_string())
+        .filter(|path| !path.is_empty())
+        .collect();
+    let key = match var("KEY").as_deref().unwrap_or(key) {
+        "ip" => RateLimitKey::Ip,
+        "user" => RateLimitKey::User,
+        "api_key" => RateLimitKey::ApiKey,
+        other => panic!("{}KEY `{}` must be ip, user or api_key", prefix, other),
+    };
+
+    RateLimitConfig {
+        name: name.to_string(),
+        paths,
+        burst: number("BURST", burst),
+        per_minute: number("PER_MINUTE", per_minute),
+        key,
+    }
+}
+
+fn verification_key_config(name: &str) -> VerificationKeyConfig {
+    let prefix = format!(
+        "JWT_VERIFICATION_KEY_{}_",
+        name.to_uppercase().replace('-', "_")
+    );
+    let var = |key: &str| {
+        std::env::var(format!("{}{}", prefix, key))
+            .ok()
+            .filter(|value| !value.is_empty())
+    };
+
+    let private_key_path = var("PATH");
+    let secret = var("SECRET");
+    assert!(
+        private_key_path.is_some() != secret.is_some(),
+        "Exactly one of {}PATH or {}SECRET must be set",
+        prefix,
+        prefix
+    );
+
+    let retire_at = var("RETIRE_AT").unwrap_or_else(|| panic!("{}RETIRE_AT must be set", prefix));
+    let retire_at = DateTime::parse_from_rfc3339(&retire_at)
+        .unwrap_or_else(|_| panic!("{}RETIRE_AT must be an RFC 3339 date", prefix))
+        .with_timezone(&Utc);
+
+    VerificationKeyConfig {
+        name: name.to_string(),
+        private_key_path,
+        secret,
+        kid: var("KID"),
+        retire_at,
+    }
+}
+
+fn env_flag(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        Err(_) => default,
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// A complete configuration for handler tests, built without touching the environment.
+    pub fn for_tests() -> Config {
+        let redirect_url =
+            |provider: &str| format!("https://localhost:8080/api/sessions/oauth/{}", provider);
+        Config {
+            client_origin: "https://localhost:3000".to_string(),
+            api_origin: "https://localhost:8080".to_string(),
+            database_url: None,
+            database_max_connections: 1,
+            jwt_secret: "test-secret".to_string(),
+            jwt_private_key_path: None,
+            jwt_key_id: None,
+            jwt_verification_keys: Vec::new(),
+            jwt_expires_in: "60m".to_string(),
+            jwt_max_age: 60,
+            refresh_token_max_age: 60 * 24,
+            token_gc_interval: 60 * 60,
+            role_hierarchy: vec![("admin".to_string(), "user".to_string())],
+            // Far below the production cost, tests only need a valid hash
+            argon2_params: argon2::Params::new(64, 1, 1, None).unwrap(),
+            mailer: MailerKind::Console,
+            mail_from: "blog-rs <no-reply@localhost>".to_string(),
+            mail_dir: "mail".to_string(),
+            smtp_url: None,
+            email_verification_max_age: 60 * 24,
+            email_resend_interval: 60,
+            require_email_verification: false,
+            password_reset_max_age: 30,
+            magic_link_max_age: 15,
+            login_max_attempts: 5,
+            login_ip_max_attempts: 50,
+            login_backoff_base: 0,
+            login_backoff_max: 60,
+            login_lockout: 900,
+            trust_proxy_headers: false,
+            rate_limits: vec![],
+            totp_issuer: "blog-rs".to_string(),
+            mfa_pending_max_age: 5,
+            mfa_required_roles: Vec::new(),
+            webauthn_rp_id: "localhost".to_string(),
+            webauthn_rp_origin: "https://localhost:3000".to_string(),
+            webauthn_rp_name: "blog-rs".to_string(),
+            google_oauth_client_id: "google-client".to_string(),
+            google_oauth_client_secret: "google-secret".to_string(),
+            google_oauth_redirect_url: redirect_url("google"),
+            google_oauth_pkce: true,
+            github_oauth_client_id: "github-client".to_string(),
+            github_oauth_client_secret: "github-secret".to_string(),
+            github_oauth_redirect_url: redirect_url("github"),
+            github_oauth_pkce: true,
+            naver_oauth_client_id: "naver-client".to_string(),
+            naver_oauth_client_secret: "naver-secret".to_string(),
+            naver_oauth_redirect_url: redirect_url("naver"),
+            naver_oauth_pkce: false,
+            kakao_oauth_client_id: "kakao-client".to_string(),
+            kakao_oauth_redirect_url: redirect_url("kakao"),
+            kakao_oauth_pkce: true,
+            oidc_providers: Vec::new(),
         }
     }
 }
@@ -0,0 +1,458 @@
+use crate::{
+    auth::{
+        one_time_token::{self, EMAIL_CHANGE, EMAIL_CHANGE_APPROVAL},
+        password::{hash_password, verify_password, PasswordCheck},
+        token,
+        token_guard::AuthenticationGuard,
+    },
+    mail::Email,
+    models::{AppState, ChangeEmailSchema, ChangePasswordSchema, ConfirmEmailQuery, User},
+    repository::RepositoryError,
+};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get, post, web, Error as ActixWebError, HttpResponse, Responder, Result as ActixResult,
+};
+use serde_json::json;
+
+fn bad_request(message: &str) -> ActixWebError {
+    ErrorBadRequest(json!({"status": "fail", "message": message}))
+}
+
+async fn current_user(auth_guard: &AuthenticationGuard, data: &AppState) -> ActixResult<User> {
+    Ok(data
+        .db
+        .find_by_id(&auth_guard.user_id)
+        .await?
+        .ok_or(RepositoryError::NotFound)?)
+}
+
+async fn check_password(password: String, user: &User, data: &AppState) -> ActixResult<bool> {
+    let password_hash = user.password.to_owned();
+    let params = data.env.argon2_params.clone();
+    let check = web::block(move || verify_password(&password, &password_hash, params)).await?;
+    Ok(!matches!(check, PasswordCheck::Invalid))
+}
+
+#[post("/users/me/password")]
+async fn change_password_handler(
+    auth_guard: AuthenticationGuard,
+    body: web::Json<ChangePasswordSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let body = body.into_inner();
+    let user = current_user(&auth_guard, &data).await?;
+
+    if user.password.is_empty() {
+        return Err(bad_request(
+            "Your account signs in through a provider and has no password",
+        ));
+    }
+    if !check_password(body.current_password, &user, &data).await? {
+        return Err(bad_request("Current password is incorrect"));
+    }
+
+    let params = data.env.argon2_params.clone();
+    let new_password = body.new_password;
+    let password_hash = web::block(move || hash_password(&new_password, params))
+        .await?
+        .map_err(|_| ErrorInternalServerError("Failed to hash password"))?;
+
+    // Only swap the hash that was just checked, a concurrent change wins
+    if !data
+        .db
+        .replace_password(&auth_guard.user_id, &user.password, &password_hash)
+        .await?
+    {
+        return Err(bad_request("Current password is incorrect"));
+    }
+
+    // Sign out every other device, this one gets a fresh session
+    let session = token::renew_all_sessions(&user, &data).await?;
+    let [access_cookie, refresh_cookie] = token::session_cookies(&session, &data);
+
+    log::info!("User {} changed their password", auth_guard.user_id);
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(json!({
+            "status": "success",
+            "token": session.access_token,
+            "refresh_token": session.refresh_token
+        })))
+}
+
+#[post("/users/me/email")]
+async fn change_email_handler(
+    auth_guard: AuthenticationGuard,
+    body: web::Json<ChangeEmailSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let body = body.into_inner();
+    let user = current_user(&auth_guard, &data).await?;
+    let email = body.email.trim().to_lowercase();
+
+    if email == user.email {
+        return Err(bad_request("This is already your email"));
+    }
+    if data.db.find_by_email(&email).await?.is_some() {
+        return Err(RepositoryError::Conflict("Email already in use".to_string()).into());
+    }
+
+    // A stolen session alone must not be enough to move the account to another address,
+    // without a password to ask for the current inbox has to approve the change first
+    if user.password.is_empty() {
+        data.one_time_tokens
+            .revoke_user(&auth_guard.user_id, EMAIL_CHANGE_APPROVAL)
+            .await?;
+        let token = one_time_token::issue(
+            EMAIL_CHANGE_APPROVAL,
+            Some(&auth_guard.user_id),
+            &email,
+            data.env.email_verification_max_age,
+            &data,
+        )
+        .await?;
+
+        data.mailer
+            .send(Email {
+                to: user.email.clone(),
+                subject: "Approve your new email".to_string(),
+                body: format!(
+                    "Hi {},\n\nSomeone asked to move your account to {}. If that was you, approve it here:\n\n{}/api/auth/approve-email-change?token={}\n\nA confirmation link is then sent to the new address. The link expires in {} minutes, ignore this email if you didn't ask for the change.\n",
+                    user.name, email, data.env.api_origin, token, data.env.email_verification_max_age
+                ),
+            })
+            .await?;
+
+        return Ok(HttpResponse::Ok().json(json!({
+            "status": "success",
+            "message": "Approve the change from the link sent to your current address"
+        })));
+    }
+
+    let password = body
+        .password
+        .ok_or_else(|| bad_request("Your current password is required"))?;
+    if !check_password(password, &user, &data).await? {
+        return Err(bad_request("Current password is incorrect"));
+    }
+
+    send_email_change_confirmation(&auth_guard.user_id, &user, email, &data).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "Open the link sent to the new address to confirm the change"
+    })))
+}
+
+/// Mails the link that moves `user` to `email`, only the latest one can be confirmed.
+async fn send_email_change_confirmation(
+    user_id: &str,
+    user: &User,
+    email: String,
+    data: &AppState,
+) -> ActixResult<()> {
+    data.one_time_tokens
+        .revoke_user(user_id, EMAIL_CHANGE)
+        .await?;
+    let token = one_time_token::issue(
+        EMAIL_CHANGE,
+        Some(user_id),
+        &email,
+        data.env.email_verification_max_age,
+        data,
+    )
+    .await?;
+
+    data.mailer
+        .send(Email {
+            to: email,
+            subject: "Confirm your new email".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm that your account should use this address from now on:\n\n{}/api/auth/confirm-email?token={}\n\nThe link expires in {} minutes.\n",
+                user.name, data.env.api_origin, token, data.env.email_verification_max_age
+            ),
+        })
+        .await?;
+    Ok(())
+}
+
+#[get("/auth/approve-email-change")]
+async fn approve_email_change_handler(
+    query: web::Query<ConfirmEmailQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let stored = one_time_token::redeem(&query.token, EMAIL_CHANGE_APPROVAL, &data)
+        .await?
+        .ok_or_else(|| bad_request("Invalid or expired approval link"))?;
+    let user_id = stored
+        .user_id
+        .ok_or_else(|| bad_request("Invalid or expired approval link"))?;
+    let user = data
+        .db
+        .find_by_id(&user_id)
+        .await?
+        .ok_or_else(|| bad_request("Invalid or expired approval link"))?;
+
+    if data.db.find_by_email(&stored.email).await?.is_some() {
+        return Err(RepositoryError::Conflict("Email already in use".to_string()).into());
+    }
+    send_email_change_confirmation(&user_id, &user, stored.email, &data).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "Open the link sent to the new address to confirm the change"
+    })))
+}
+
+#[get("/auth/confirm-email")]
+async fn confirm_email_change_handler(
+    query: web::Query<ConfirmEmailQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let stored = one_time_token::redeem(&query.token, EMAIL_CHANGE, &data)
+        .await?
+        .ok_or_else(|| bad_request("Invalid or expired confirmation link"))?;
+    let user_id = stored
+        .user_id
+        .ok_or_else(|| bad_request("Invalid or expired confirmation link"))?;
+
+    let user = data
+        .db
+        .find_by_id(&user_id)
+        .await?
+        .ok_or_else(|| bad_request("Invalid or expired confirmation link"))?;
+
+    // The repository refuses the change with a 409 if someone took the address meanwhile
+    data.db.change_email(&user_id, &stored.email).await?;
+
+    token::revoke_all_sessions(&user_id, &data).await?;
+
+    // Let the previous address know, in case the change wasn't wanted
+    if let Err(e) = data
+        .mailer
+        .send(Email {
+            to: user.email,
+            subject: "Your email was changed".to_string(),
+            body: format!(
+                "Hi {},\n\nYour account now uses {}. If you didn't ask for this, reset your password and contact us.\n",
+                user.name, stored.email
+            ),
+        })
+        .await
+    {
+        log::warn!("Failed to notify the previous email address: {}", e);
+    }
+
+    log::info!("User {} changed their email", user_id);
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "Email changed, log in again"
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{handlers::auth_handler::config, mail::FileMailer};
+
+    async fn app_state(password: &str) -> (web::Data<AppState>, Arc<FileMailer>, String) {
+        let mailer = Arc::new(FileMailer::temporary());
+        let data = AppState {
+            mailer: mailer.clone(),
+            ..AppState::for_tests()
+        };
+        let password = if password.is_empty() {
+            String::new()
+        } else {
+            hash_password(password, data.env.argon2_params.clone()).unwrap()
+        };
+        let user = User {
+            password,
+            ..User::for_tests("user-1", "alice@example.com")
+        };
+        let user = data.db.insert(user).await.unwrap();
+        let access_token = token::create_access_token(&user, &data).unwrap();
+        (web::Data::new(data), mailer, access_token)
+    }
+
+    fn post(uri: &str, access_token: &str, body: Value) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", access_token)))
+            .set_json(body)
+    }
+
+    fn link_token(mail: &str) -> String {
+        let (_, rest) = mail.split_once("token=").expect("no link in the email");
+        rest.split_whitespace().next().unwrap().to_string()
+    }
+
+    fn mail_to(mailer: &FileMailer, to: &str) -> Vec<String> {
+        mailer
+            .sent()
+            .into_iter()
+            .filter(|mail| mail.contains(&format!("To: {}", to)))
+            .collect()
+    }
+
+    fn open(uri: &str) -> test::TestRequest {
+        test::TestRequest::get().uri(uri)
+    }
+
+    #[actix_web::test]
+    async fn a_password_change_signs_out_the_other_devices() {
+        let (data, _, access_token) = app_state("old password").await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        let req = post(
+            "/api/users/me/password",
+            &access_token,
+            json!({"current_password": "wrong", "new_password": "new password"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = post(
+            "/api/users/me/password",
+            &access_token,
+            json!({"current_password": "old password", "new_password": "new password"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        let fresh_token = body["token"].as_str().unwrap();
+
+        for (token, status) in [
+            (access_token.as_str(), StatusCode::UNAUTHORIZED),
+            (fresh_token, StatusCode::OK),
+        ] {
+            let req = test::TestRequest::get()
+                .uri("/api/users/me")
+                .insert_header(("Authorization", format!("Bearer {}", token)));
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status);
+        }
+
+        let stored = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        let params = data.env.argon2_params.clone();
+        assert!(!matches!(
+            verify_password("new password", &stored.password, params),
+            PasswordCheck::Invalid
+        ));
+    }
+
+    #[actix_web::test]
+    async fn the_email_only_changes_once_the_new_address_confirms() {
+        let (data, mailer, access_token) = app_state("password").await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        data.db
+            .insert(User::for_tests("user-2", "bob@example.com"))
+            .await
+            .unwrap();
+
+        for (body, status) in [
+            (
+                json!({"email": "carol@example.com"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                json!({"email": "carol@example.com", "password": "wrong"}),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                json!({"email": "BOB@example.com", "password": "password"}),
+                StatusCode::CONFLICT,
+            ),
+            (
+                json!({"email": "Carol@example.com", "password": "password"}),
+                StatusCode::OK,
+            ),
+        ] {
+            let req = post("/api/users/me/email", &access_token, body.clone());
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status, "{}", body);
+        }
+        let stored = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        assert_eq!(stored.email, "alice@example.com");
+
+        let token = link_token(&mail_to(&mailer, "carol@example.com")[0]);
+        let confirm = || open(&format!("/api/auth/confirm-email?token={}", token)).to_request();
+        let res = test::call_service(&app, confirm()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, confirm()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let stored = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        assert_eq!(stored.email, "carol@example.com");
+        assert!(stored.verified);
+        // The previous address hears about it, and the old session is over
+        assert!(mail_to(&mailer, "alice@example.com")[0].contains("Your email was changed"));
+        let req = test::TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(("Authorization", format!("Bearer {}", access_token)));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn a_confirmation_for_an_address_taken_meanwhile_is_a_conflict() {
+        let (data, mailer, access_token) = app_state("password").await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        let req = post(
+            "/api/users/me/email",
+            &access_token,
+            json!({"email": "carol@example.com", "password": "password"}),
+        );
+        test::call_service(&app, req.to_request()).await;
+        data.db
+            .insert(User::for_tests("user-2", "carol@example.com"))
+            .await
+            .unwrap();
+
+        let token = link_token(&mail_to(&mailer, "carol@example.com")[0]);
+        let req = open(&format!("/api/auth/confirm-email?token={}", token));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        let stored = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        assert_eq!(stored.email, "alice@example.com");
+    }
+
+    #[actix_web::test]
+    async fn accounts_without_a_password_approve_from_the_current_address() {
+        let (data, mailer, access_token) = app_state("").await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        let req = post(
+            "/api/users/me/email",
+            &access_token,
+            json!({"email": "carol@example.com"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        // Nothing reaches the new address before the current one approves
+        assert!(mail_to(&mailer, "carol@example.com").is_empty());
+        let approval = &mail_to(&mailer, "alice@example.com")[0];
+        assert!(approval.contains("/api/auth/approve-email-change?token="));
+
+        let req = open(&format!(
+            "/api/auth/approve-email-change?token={}",
+            link_token(approval)
+        ));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let token = link_token(&mail_to(&mailer, "carol@example.com")[0]);
+        let req = open(&format!("/api/auth/confirm-email?token={}", token));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let stored = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        assert_eq!(stored.email, "carol@example.com");
+    }
+}
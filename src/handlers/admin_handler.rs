@@ -0,0 +1,304 @@
+use crate::{
+    auth::{
+        login_throttle,
+        role_guard::{Admin, RequireRole},
+        token,
+    },
+    handlers::auth_handler::user_to_response,
+    models::{AppState, UpdateUserSchema, User, UserListQuery},
+    repository::{RepositoryError, UserFilter},
+    responses::{UserData, UserListData, UserListResponse, UserResponse},
+};
+use actix_web::{
+    delete, error::ErrorBadRequest, get, patch, post, web, HttpResponse, Responder,
+    Result as ActixResult,
+};
+use serde_json::json;
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+fn user_response(user: &User) -> HttpResponse {
+    HttpResponse::Ok().json(UserResponse {
+        status: "success".to_string(),
+        data: UserData {
+            user: user_to_response(user),
+        },
+    })
+}
+
+async fn find_user(id: &str, data: &AppState) -> Result<User, RepositoryError> {
+    data.db
+        .find_by_id(id)
+        .await?
+        .ok_or(RepositoryError::NotFound)
+}
+
+// Admins can't lock themselves out, another admin has to do it
+fn ensure_not_self(admin: &RequireRole<Admin>, id: &str) -> ActixResult<()> {
+    if admin.user_id == id {
+        return Err(ErrorBadRequest(
+            json!({"status": "fail", "message": "You can't do this to your own account"}),
+        ));
+    }
+    Ok(())
+}
+
+#[get("/admin/users")]
+async fn list_users_handler(
+    _: RequireRole<Admin>,
+    query: web::Query<UserListQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let query = query.into_inner();
+    let page = query.page.unwrap_or(1).max(1);
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let filter = UserFilter {
+        provider: query.provider,
+        role: query.role,
+        verified: query.verified,
+        suspended: query.suspended,
+        search: query.q.filter(|q| !q.trim().is_empty()),
+    };
+    let result = data.db.list(&filter, (page - 1) * limit, limit).await?;
+
+    Ok(HttpResponse::Ok().json(UserListResponse {
+        status: "success".to_string(),
+        data: UserListData {
+            users: result.users.iter().map(user_to_response).collect(),
+            page,
+            limit,
+            total: result.total,
+        },
+    }))
+}
+
+#[get("/admin/users/{id}")]
+async fn get_user_handler(
+    _: RequireRole<Admin>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let user = find_user(&path, &data).await?;
+    Ok(user_response(&user))
+}
+
+#[patch("/admin/users/{id}")]
+async fn update_user_handler(
+    admin: RequireRole<Admin>,
+    path: web::Path<String>,
+    body: web::Json<UpdateUserSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let id = path.into_inner();
+    ensure_not_self(&admin, &id)?;
+
+    let user = find_user(&id, &data).await?;
+    let body = body.into_inner();
+
+    if let Some(role) = &body.role {
+        if !data.roles.is_known(role) {
+            return Err(ErrorBadRequest(json!({
+                "status": "fail",
+                "message": "Unknown role, it must be listed in ROLE_HIERARCHY"
+            })));
+        }
+    }
+
+    // Tokens carry the role, so old ones have to go when it changes
+    let role_changed = body.role.as_ref().is_some_and(|role| *role != user.role);
+    let suspending = body.suspended == Some(true) && !user.suspended;
+
+    // Column updates, a password or email changed meanwhile must survive
+    if let Some(role) = &body.role {
+        data.db.set_role(&id, role).await?;
+    }
+    if let Some(suspended) = body.suspended {
+        data.db.set_suspended(&id, suspended).await?;
+    }
+    let user = find_user(&id, &data).await?;
+
+    if role_changed || suspending {
+        token::revoke_all_sessions(&id, &data).await?;
+    }
+
+    log::info!(
+        "Admin {} updated user {} (role: {}, suspended: {})",
+        admin.user_id,
+        id,
+        user.role,
+        user.suspended
+    );
+    Ok(user_response(&user))
+}
+
+#[post("/admin/users/{id}/logout")]
+async fn force_logout_handler(
+    admin: RequireRole<Admin>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let user = find_user(&path, &data).await?;
+    token::revoke_all_sessions(&path, &data).await?;
+
+    log::info!("Admin {} signed out user {}", admin.user_id, path);
+    Ok(user_response(&user))
+}
+
+#[post("/admin/users/{id}/unlock")]
+async fn unlock_user_handler(
+    admin: RequireRole<Admin>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let user = find_user(&path, &data).await?;
+    if login_throttle::unlock_account(&user.email, &data).await? {
+        log::info!(
+            "Admin {} unlocked password logins of user {}",
+            admin.user_id,
+            path
+        );
+    }
+    if login_throttle::unlock_mfa(&path, &data).await? {
+        log::info!(
+            "Admin {} unlocked the second factor of user {}",
+            admin.user_id,
+            path
+        );
+    }
+    Ok(user_response(&user))
+}
+
+#[delete("/admin/users/{id}")]
+async fn delete_user_handler(
+    admin: RequireRole<Admin>,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let id = path.into_inner();
+    ensure_not_self(&admin, &id)?;
+
+    let user = find_user(&id, &data).await?;
+    token::revoke_all_sessions(&id, &data).await?;
+    data.db.delete(&id).await?;
+
+    log::info!("Admin {} deleted user {}", admin.user_id, id);
+    Ok(user_response(&user))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::handlers::auth_handler::config;
+
+    async fn insert_user(data: &AppState, id: &str, role: &str) -> (User, String) {
+        let user = User {
+            role: role.to_string(),
+            ..User::for_tests(id, &format!("{}@example.com", id))
+        };
+        let user = data.db.insert(user).await.unwrap();
+        let access_token = token::create_access_token(&user, data).unwrap();
+        (user, access_token)
+    }
+
+    fn request(method: &str, uri: &str, access_token: &str) -> test::TestRequest {
+        let req = match method {
+            "GET" => test::TestRequest::get(),
+            "PATCH" => test::TestRequest::patch(),
+            "POST" => test::TestRequest::post(),
+            _ => test::TestRequest::delete(),
+        };
+        req.uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", access_token)))
+    }
+
+    #[actix_web::test]
+    async fn only_admins_list_users() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let (_, admin_token) = insert_user(&data, "admin", "admin").await;
+        let (_, user_token) = insert_user(&data, "bob", "user").await;
+
+        let req = request("GET", "/api/admin/users", &user_token).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let req = request("GET", "/api/admin/users?role=user&q=BOB", &admin_token).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["total"], 1);
+        assert_eq!(body["data"]["users"][0]["id"], "bob");
+    }
+
+    #[actix_web::test]
+    async fn suspending_signs_the_user_out_without_touching_other_columns() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let (_, admin_token) = insert_user(&data, "admin", "admin").await;
+        let (bob, bob_token) = insert_user(&data, "bob", "user").await;
+        assert!(data
+            .db
+            .replace_password("bob", &bob.password, "bob-hash")
+            .await
+            .unwrap());
+
+        let req = request("PATCH", "/api/admin/users/bob", &admin_token)
+            .set_json(json!({"role": "admin", "suspended": true}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["user"]["role"], "admin");
+        assert_eq!(body["data"]["user"]["suspended"], true);
+
+        let stored = data.db.find_by_id("bob").await.unwrap().unwrap();
+        assert_eq!(stored.password, "bob-hash");
+
+        let req = request("GET", "/api/users/me", &bob_token).to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn unknown_roles_and_own_account_are_refused() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let (_, admin_token) = insert_user(&data, "admin", "admin").await;
+        insert_user(&data, "bob", "user").await;
+
+        for (uri, body, message) in [
+            (
+                "/api/admin/users/bob",
+                json!({"role": "root"}),
+                "Unknown role, it must be listed in ROLE_HIERARCHY",
+            ),
+            (
+                "/api/admin/users/admin",
+                json!({"suspended": true}),
+                "You can't do this to your own account",
+            ),
+        ] {
+            let req = request("PATCH", uri, &admin_token)
+                .set_json(body)
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            let body: Value = test::read_body_json(res).await;
+            assert_eq!(body["message"], message);
+        }
+
+        let req = request("PATCH", "/api/admin/users/ghost", &admin_token)
+            .set_json(json!({"suspended": true}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
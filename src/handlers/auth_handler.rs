@@ -1,17 +1,50 @@
 use crate::{
-    auth::token_guard::AuthenticationGuard,
-    models::{AppState, LoginUserSchema, RegisterUserSchema, TokenClaims, User},
+    auth::{
+        client_ip::client_ip,
+        crypto::sha256_hex,
+        login_throttle,
+        password::{dummy_verify, hash_password, verify_password, PasswordCheck},
+        token,
+        token_guard::AuthenticationGuard,
+    },
+    models::{AppState, LoginUserSchema, RefreshTokenSchema, RegisterUserSchema, User},
+    repository::RepositoryError,
     responses::{FilteredUser, UserData, UserResponse},
 };
 use actix_web::{
-    cookie::{time::Duration as ActixWebDuration, Cookie},
-    get, post, web, HttpResponse, Responder,
+    error::ErrorInternalServerError, get, http::header, post, web, HttpRequest, HttpResponse,
+    Responder, Result as ActixResult,
 };
-use chrono::{prelude::*, Duration};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use chrono::prelude::*;
 use uuid::Uuid;
 
-use crate::handlers::oauth_handler::oauth_handler;
+use crate::handlers::{
+    account_handler::{
+        approve_email_change_handler, change_email_handler, change_password_handler,
+        confirm_email_change_handler,
+    },
+    admin_handler::{
+        delete_user_handler, force_logout_handler, get_user_handler, list_users_handler,
+        unlock_user_handler, update_user_handler,
+    },
+    email_verification_handler::{
+        resend_verification_handler, send_verification_email, verify_email_handler,
+    },
+    identity_handler::{list_identities_handler, unlink_identity_handler},
+    jwks_handler::jwks_handler,
+    magic_link_handler::{magic_link_handler, redeem_magic_link_handler},
+    mfa_handler::{
+        mfa_challenge, mfa_status_handler, mfa_verify_handler, recovery_codes_handler,
+        totp_confirm_handler, totp_disable_handler, totp_enroll_handler,
+    },
+    oauth_handler::{oauth_handler, oauth_link_handler, oauth_start_handler},
+    password_reset_handler::{forgot_password_handler, reset_password_handler},
+    webauthn_handler::{
+        delete_credential_handler, list_credentials_handler, login_finish_handler,
+        login_start_handler, mfa_finish_handler, mfa_start_handler, register_finish_handler,
+        register_start_handler,
+    },
+};
 
 const MESSAGE: &str = "OK";
 
@@ -24,31 +57,38 @@ async fn health_checker_handler() -> impl Responder {
 async fn register_user_handler(
     body: web::Json<RegisterUserSchema>,
     data: web::Data<AppState>,
-) -> impl Responder {
-    let mut vec = data.db.lock().await;
-
-    if vec.iter().any(|user| user.email == body.email) {
-        return HttpResponse::Conflict()
-            .json(serde_json::json!({"status": "fail", "message": "Email already exist"}));
-    }
+) -> ActixResult<impl Responder> {
+    // Argon2 is CPU bound, keep it off the async workers
+    let password = body.password.to_owned();
+    let params = data.env.argon2_params.clone();
+    let password_hash = web::block(move || hash_password(&password, params))
+        .await?
+        .map_err(|_| ErrorInternalServerError("Failed to hash password"))?;
 
-    let uuid_id = Uuid::new_v4();
     let datetime = Utc::now();
 
     let user = User {
-        id: Some(uuid_id.to_string()),
+        id: Some(Uuid::new_v4().to_string()),
         name: body.name.to_owned(),
         verified: false,
+        suspended: false,
+        verificationSentAt: None,
         email: body.email.to_owned().to_lowercase(),
         provider: "local".to_string(),
         role: "user".to_string(),
-        password: body.password.to_string(),
+        password: password_hash,
         photo: "default.png".to_string(),
         createdAt: Some(datetime),
         updatedAt: Some(datetime),
     };
 
-    vec.push(user.clone());
+    // The repository rejects duplicated emails with a 409 Conflict
+    let user = data.db.insert(user).await?;
+
+    // The account exists either way, a lost email can be sent again
+    if let Err(e) = send_verification_email(user.clone(), &data).await {
+        log::warn!("Failed to send verification email: {}", e);
+    }
 
     let json_response = UserResponse {
         status: "success".to_string(),
@@ -57,98 +97,266 @@ async fn register_user_handler(
         },
     };
 
-    HttpResponse::Ok().json(json_response)
+    Ok(HttpResponse::Ok().json(json_response))
+}
+
+fn invalid_credentials() -> HttpResponse {
+    HttpResponse::BadRequest()
+        .json(serde_json::json!({"status": "fail", "message": "Invalid email or password"}))
+}
+
+fn account_suspended() -> HttpResponse {
+    HttpResponse::Forbidden()
+        .json(serde_json::json!({"status": "fail", "message": "Your account has been suspended"}))
+}
+
+pub fn too_many_attempts(retry_after: i64) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+        .json(serde_json::json!({
+            "status": "fail",
+            "message": "Too many failed login attempts, try again later"
+        }))
 }
 
 #[post("/auth/login")]
 async fn login_user_handler(
+    req: HttpRequest,
     body: web::Json<LoginUserSchema>,
     data: web::Data<AppState>,
-) -> impl Responder {
-    let vec = data.db.lock().await;
-    let user_opt = vec
-        .iter()
-        .find(|user| user.email == body.email.to_lowercase());
-
-    match user_opt {
-        None => HttpResponse::BadRequest()
-            .json(serde_json::json!({"status": "fail", "message": "Invalid email or password"})),
-        Some(user) => match user.provider.as_str() {
-            "Google" => HttpResponse::Unauthorized().json(
+) -> ActixResult<impl Responder> {
+    let email = body.email.to_lowercase();
+    let ip = client_ip(&req, &data);
+
+    // Unknown emails are throttled the same, so a lockout doesn't tell whether an account exists.
+    // Counting comes first, so parallel requests can't all get in before the failures are known
+    if let Some(retry_after) = login_throttle::reserve_login(&email, &ip, &data).await? {
+        return Ok(too_many_attempts(retry_after));
+    }
+
+    let user_opt = data.db.find_by_email(&email).await?;
+
+    let password = body.password.to_owned();
+    let params = data.env.argon2_params.clone();
+
+    let mut user = match user_opt {
+        Some(user) => user,
+        None => {
+            // Take as long as a wrong password would, so emails can't be probed by timing
+            web::block(move || dummy_verify(&password, params)).await?;
+            return Ok(invalid_credentials());
+        }
+    };
+
+    match user.provider.as_str() {
+        "Google" => {
+            return Ok(HttpResponse::Unauthorized().json(
                 serde_json::json!({"status": "fail", "message": "Use Google OAuth2 instead"}),
-            ),
-            "GitHub" => HttpResponse::Unauthorized()
-                .json(serde_json::json!({"status": "fail", "message": "Use GitHub OAuth instead"})),
-            "Kakao" => HttpResponse::Unauthorized()
-                .json(serde_json::json!({"status": "fail", "message": "Use Kakao OAuth instead"})),
-            "Naver" => HttpResponse::Unauthorized()
-                .json(serde_json::json!({"status": "fail", "message": "Use Naver OAuth instead"})),
-            _ => {
-                let jwt_secret = data.env.jwt_secret.to_owned();
-                let now = Utc::now();
-                let iat = now.timestamp() as usize;
-                let exp = (now + Duration::minutes(data.env.jwt_max_age)).timestamp() as usize;
-                let claims: TokenClaims = TokenClaims {
-                    sub: user.id.as_ref().unwrap().to_string(),
-                    exp,
-                    iat,
-                };
-
-                match encode(
-                    &Header::default(),
-                    &claims,
-                    &EncodingKey::from_secret(jwt_secret.as_ref()),
-                ) {
-                    Ok(token) => {
-                        let cookie = Cookie::build("token", token.clone())
-                            .path("/")
-                            .max_age(ActixWebDuration::new(60 * data.env.jwt_max_age, 0))
-                            .http_only(true)
-                            .finish();
-
-                        HttpResponse::Ok()
-                            .cookie(cookie)
-                            .json(serde_json::json!({"status": "success", "token": token}))
+            ))
+        }
+        "GitHub" => {
+            return Ok(HttpResponse::Unauthorized().json(
+                serde_json::json!({"status": "fail", "message": "Use GitHub OAuth instead"}),
+            ))
+        }
+        "Kakao" => {
+            return Ok(HttpResponse::Unauthorized()
+                .json(serde_json::json!({"status": "fail", "message": "Use Kakao OAuth instead"})))
+        }
+        "Naver" => {
+            return Ok(HttpResponse::Unauthorized()
+                .json(serde_json::json!({"status": "fail", "message": "Use Naver OAuth instead"})))
+        }
+        "email" => {
+            return Ok(HttpResponse::Unauthorized().json(
+                serde_json::json!({"status": "fail", "message": "Use a sign-in link instead"}),
+            ))
+        }
+        _ => {}
+    }
+
+    let password_hash = user.password.to_owned();
+    let check = {
+        let params = params.clone();
+        let password = password.clone();
+        web::block(move || verify_password(&password, &password_hash, params)).await?
+    };
+
+    match check {
+        PasswordCheck::Invalid => {
+            return Ok(invalid_credentials());
+        }
+        PasswordCheck::Valid => {}
+        PasswordCheck::ValidNeedsRehash => {
+            // Upgrade the stored hash to the configured cost, a failure here must not block the login
+            match web::block(move || hash_password(&password, params)).await? {
+                Ok(rehashed) => {
+                    // Only the hash, the rest of `user` may be stale by now
+                    let id = user.id.clone().unwrap_or_default();
+                    match data
+                        .db
+                        .replace_password(&id, &user.password, &rehashed)
+                        .await
+                    {
+                        Ok(true) => user.password = rehashed,
+                        Ok(false) => {}
+                        Err(e) => log::warn!("Failed to store rehashed password: {}", e),
                     }
-                    Err(_) => HttpResponse::InternalServerError().finish(),
                 }
+                Err(e) => log::warn!("Failed to rehash password: {}", e),
             }
+        }
+    }
+
+    login_throttle::login_succeeded(&email, &ip, &data).await?;
+
+    // Only tell the right password holder that the account is suspended
+    if user.suspended {
+        return Ok(account_suspended());
+    }
+
+    if data.env.require_email_verification && !user.verified {
+        return Ok(HttpResponse::Forbidden().json(serde_json::json!({
+            "status": "fail",
+            "message": "Please verify your email before logging in"
+        })));
+    }
+
+    if let Some(challenge) = mfa_challenge(&user, &data).await? {
+        return Ok(challenge);
+    }
+
+    let session = token::issue_session(&user, None, &data).await?;
+    let [access_cookie, refresh_cookie] = token::session_cookies(&session, &data);
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(serde_json::json!({
+            "status": "success",
+            "token": session.access_token,
+            "refresh_token": session.refresh_token
+        })))
+}
+
+fn invalid_refresh_token(message: &str) -> HttpResponse {
+    HttpResponse::Unauthorized().json(serde_json::json!({"status": "fail", "message": message}))
+}
+
+#[post("/auth/refresh")]
+async fn refresh_token_handler(
+    req: HttpRequest,
+    body: Option<web::Json<RefreshTokenSchema>>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let presented = match req.cookie(token::REFRESH_TOKEN_COOKIE) {
+        Some(cookie) => cookie.value().to_string(),
+        None => match body {
+            Some(body) => body.into_inner().refresh_token,
+            None => return Ok(invalid_refresh_token("Refresh token not provided")),
         },
+    };
+
+    let stored = match data
+        .refresh_tokens
+        .find_by_hash(&sha256_hex(&presented))
+        .await?
+    {
+        Some(stored) if stored.revoked_at.is_none() => stored,
+        _ => return Ok(invalid_refresh_token("Invalid refresh token")),
+    };
+
+    // A rotated token coming back means it was copied, so nothing in its family can be trusted
+    if stored.rotated_at.is_some() {
+        data.refresh_tokens.revoke_family(&stored.family_id).await?;
+        log::warn!("Refresh token reuse detected for user {}", stored.user_id);
+        return Ok(invalid_refresh_token("Refresh token reuse detected"));
+    }
+
+    if stored.expires_at <= Utc::now() {
+        return Ok(invalid_refresh_token("Refresh token has expired"));
+    }
+
+    let user = match data.db.find_by_id(&stored.user_id).await? {
+        Some(user) => user,
+        None => {
+            return Ok(invalid_refresh_token(
+                "User belonging to this token no longer exists",
+            ))
+        }
+    };
+
+    if user.suspended {
+        return Ok(account_suspended());
     }
+
+    // Losing this race means the same token was presented twice concurrently
+    if !data.refresh_tokens.mark_rotated(&stored.token_hash).await? {
+        data.refresh_tokens.revoke_family(&stored.family_id).await?;
+        log::warn!("Refresh token reuse detected for user {}", stored.user_id);
+        return Ok(invalid_refresh_token("Refresh token reuse detected"));
+    }
+
+    let session = token::issue_session(&user, Some(stored.family_id), &data).await?;
+    let [access_cookie, refresh_cookie] = token::session_cookies(&session, &data);
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(serde_json::json!({
+            "status": "success",
+            "token": session.access_token,
+            "refresh_token": session.refresh_token
+        })))
 }
 
 #[get("/auth/logout")]
-async fn logout_handler(_: AuthenticationGuard) -> impl Responder {
-    let cookie = Cookie::build("token", "")
-        .path("/")
-        .max_age(ActixWebDuration::new(-1, 0))
-        .http_only(true)
-        .finish();
-
-    HttpResponse::Ok()
-        .cookie(cookie)
-        .json(serde_json::json!({"status": "success"}))
+async fn logout_handler(
+    req: HttpRequest,
+    auth_guard: AuthenticationGuard,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    // The JWT itself stays valid until `exp`, so remember that it was signed out
+    token::revoke_access_token(&auth_guard.claims, &data).await?;
+
+    // End the whole refresh chain, not just the access token held by this browser
+    if let Some(cookie) = req.cookie(token::REFRESH_TOKEN_COOKIE) {
+        if let Some(stored) = data
+            .refresh_tokens
+            .find_by_hash(&sha256_hex(cookie.value()))
+            .await?
+        {
+            data.refresh_tokens.revoke_family(&stored.family_id).await?;
+        }
+    }
+
+    let [access_cookie, refresh_cookie] = token::removal_cookies();
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(serde_json::json!({"status": "success"})))
 }
 
 #[get("/users/me")]
 async fn get_me_handler(
     auth_guard: AuthenticationGuard,
     data: web::Data<AppState>,
-) -> impl Responder {
-    let vec = data.db.lock().await;
-
-    let user = vec
-        .iter()
-        .find(|user| user.id == Some(auth_guard.user_id.to_owned()));
+) -> ActixResult<impl Responder> {
+    let user = data
+        .db
+        .find_by_id(&auth_guard.user_id)
+        .await?
+        .ok_or(RepositoryError::NotFound)?;
 
     let json_response = UserResponse {
         status: "success".to_string(),
         data: UserData {
-            user: user_to_response(user.unwrap()),
+            user: user_to_response(&user),
         },
     };
 
-    HttpResponse::Ok().json(json_response)
+    Ok(HttpResponse::Ok().json(json_response))
 }
 
 pub fn user_to_response(user: &User) -> FilteredUser {
@@ -157,6 +365,7 @@ pub fn user_to_response(user: &User) -> FilteredUser {
         name: user.name.to_owned(),
         email: user.email.to_owned(),
         verified: user.verified.to_owned(),
+        suspended: user.suspended,
         photo: user.photo.to_owned(),
         provider: user.provider.to_owned(),
         role: user.role.to_owned(),
@@ -170,9 +379,248 @@ pub fn config(conf: &mut web::ServiceConfig) {
         .service(health_checker_handler)
         .service(register_user_handler)
         .service(login_user_handler)
+        .service(refresh_token_handler)
+        .service(verify_email_handler)
+        .service(resend_verification_handler)
+        .service(forgot_password_handler)
+        .service(reset_password_handler)
+        .service(magic_link_handler)
+        .service(redeem_magic_link_handler)
         .service(logout_handler)
+        .service(mfa_status_handler)
+        .service(totp_enroll_handler)
+        .service(totp_confirm_handler)
+        .service(totp_disable_handler)
+        .service(recovery_codes_handler)
+        .service(mfa_verify_handler)
+        .service(login_start_handler)
+        .service(login_finish_handler)
+        .service(mfa_start_handler)
+        .service(mfa_finish_handler)
+        .service(register_start_handler)
+        .service(register_finish_handler)
+        .service(list_credentials_handler)
+        .service(delete_credential_handler)
         .service(get_me_handler)
-        .service(oauth_handler);
+        .service(change_password_handler)
+        .service(change_email_handler)
+        .service(confirm_email_change_handler)
+        .service(approve_email_change_handler)
+        .service(list_identities_handler)
+        .service(unlink_identity_handler)
+        .service(oauth_start_handler)
+        .service(oauth_link_handler)
+        .service(oauth_handler)
+        .service(list_users_handler)
+        .service(get_user_handler)
+        .service(update_user_handler)
+        .service(force_logout_handler)
+        .service(unlock_user_handler)
+        .service(delete_user_handler);
+
+    conf.service(scope).service(jwks_handler);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    async fn insert_user(data: &AppState) -> User {
+        let user = User::for_tests(&Uuid::new_v4().to_string(), "alice@example.com");
+        data.db.insert(user).await.unwrap()
+    }
+
+    #[actix_web::test]
+    async fn refresh_rotates_and_a_replayed_token_burns_the_family() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let user = insert_user(&data).await;
+        let first = token::issue_session(&user, None, &data).await.unwrap();
+
+        let refresh = |refresh_token: &str| {
+            test::TestRequest::post()
+                .uri("/api/auth/refresh")
+                .set_json(json!({ "refresh_token": refresh_token }))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, refresh(&first.refresh_token)).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        let second = body["refresh_token"].as_str().unwrap().to_string();
+        assert_ne!(second, first.refresh_token);
+
+        // The rotated out token coming back means it leaked
+        let res = test::call_service(&app, refresh(&first.refresh_token)).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["message"], "Refresh token reuse detected");
+
+        // ...which also ends the session that was rotated into
+        let res = test::call_service(&app, refresh(&second)).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["message"], "Invalid refresh token");
+    }
+
+    #[actix_web::test]
+    async fn refresh_needs_a_known_token() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
 
-    conf.service(scope);
+        for (body, message) in [
+            (None, "Refresh token not provided"),
+            (Some("made-up"), "Invalid refresh token"),
+        ] {
+            let mut req = test::TestRequest::post().uri("/api/auth/refresh");
+            if let Some(refresh_token) = body {
+                req = req.set_json(json!({ "refresh_token": refresh_token }));
+            }
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            let body: Value = test::read_body_json(res).await;
+            assert_eq!(body["message"], message);
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_signed_out_access_token_is_refused() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let user = insert_user(&data).await;
+        let access_token = token::create_access_token(&user, &data).unwrap();
+
+        let get = |uri: &str| {
+            test::TestRequest::get()
+                .uri(uri)
+                .insert_header(("Authorization", format!("Bearer {}", access_token)))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, get("/api/users/me")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, get("/api/auth/logout")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = test::call_service(&app, get("/api/users/me")).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["message"], "Token has been revoked");
+    }
+
+    #[actix_web::test]
+    async fn revoking_all_sessions_refuses_a_token_from_the_same_second() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let user = insert_user(&data).await;
+        let session = token::issue_session(&user, None, &data).await.unwrap();
+
+        // Almost always the same second as the token above, which must still be covered
+        token::revoke_all_sessions(user.id.as_ref().unwrap(), &data)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/api/users/me")
+            .insert_header(("Authorization", format!("Bearer {}", session.access_token)))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/refresh")
+            .set_json(json!({ "refresh_token": session.refresh_token }))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    fn login(email: &str, password: &str, ip: &str) -> actix_http::Request {
+        test::TestRequest::post()
+            .uri("/api/auth/login")
+            .peer_addr(format!("{}:4000", ip).parse().unwrap())
+            .set_json(json!({ "email": email, "password": password }))
+            .to_request()
+    }
+
+    #[actix_web::test]
+    async fn repeated_wrong_passwords_lock_the_account_until_an_admin_unlocks_it() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let password = hash_password("password", data.env.argon2_params.clone()).unwrap();
+        let user = data
+            .db
+            .insert(User {
+                password,
+                ..User::for_tests("alice", "alice@example.com")
+            })
+            .await
+            .unwrap();
+        let admin = data
+            .db
+            .insert(User {
+                role: "admin".to_string(),
+                ..User::for_tests("admin", "admin@example.com")
+            })
+            .await
+            .unwrap();
+
+        for _ in 0..data.env.login_max_attempts {
+            let res =
+                test::call_service(&app, login("alice@example.com", "wrong", "1.1.1.1")).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+        // Locked out even with the right password, from any address
+        let res = test::call_service(&app, login("Alice@example.com", "password", "2.2.2.2")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = res.headers().get(header::RETRY_AFTER).unwrap();
+        assert_eq!(
+            retry_after.to_str().unwrap(),
+            data.env.login_lockout.to_string()
+        );
+
+        let admin_token = token::create_access_token(&admin, &data).unwrap();
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/api/admin/users/{}/unlock",
+                user.id.as_deref().unwrap()
+            ))
+            .insert_header(("Authorization", format!("Bearer {}", admin_token)))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = test::call_service(&app, login("alice@example.com", "password", "2.2.2.2")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn unknown_emails_and_busy_addresses_are_throttled_too() {
+        let mut state = AppState::for_tests();
+        state.env.login_ip_max_attempts = 8;
+        let data = web::Data::new(state);
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        // A lockout must not tell whether the account exists
+        for _ in 0..data.env.login_max_attempts {
+            let res = test::call_service(&app, login("nobody@example.com", "x", "1.1.1.1")).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+        let res = test::call_service(&app, login("nobody@example.com", "x", "1.1.1.1")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // Spreading guesses over accounts runs into the limit of the address
+        for n in 0..2 {
+            let email = format!("user{}@example.com", n);
+            let res = test::call_service(&app, login(&email, "x", "1.1.1.1")).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+        let res = test::call_service(&app, login("user3@example.com", "x", "1.1.1.1")).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let res = test::call_service(&app, login("user3@example.com", "x", "2.2.2.2")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
 }
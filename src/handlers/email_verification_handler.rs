@@ -0,0 +1,242 @@
+use crate::{
+    auth::email_token::{self, VERIFY_EMAIL},
+    mail::Email,
+    models::{AppState, ResendVerificationSchema, User, VerifyEmailQuery},
+    repository::RepositoryError,
+};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    get, post, web, Error as ActixWebError, HttpResponse, Responder, Result as ActixResult,
+};
+use chrono::prelude::*;
+use serde_json::json;
+
+/// Mails `user` a link to `GET /api/auth/verify-email` and remembers when, for the resend throttle.
+pub async fn send_verification_email(user: User, data: &AppState) -> ActixResult<()> {
+    let user_id = user.id.clone().ok_or(RepositoryError::NotFound)?;
+    let token = email_token::create(
+        &user_id,
+        &user.email,
+        VERIFY_EMAIL,
+        data.env.email_verification_max_age,
+        &data.env.jwt_secret,
+    )
+    .map_err(|_| ErrorInternalServerError("Failed to create verification link"))?;
+
+    data.mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: "Verify your email".to_string(),
+            body: format!(
+                "Hi {},\n\nConfirm your email address by opening this link:\n\n{}/api/auth/verify-email?token={}\n\nThe link expires in {} minutes.\n",
+                user.name, data.env.api_origin, token, data.env.email_verification_max_age
+            ),
+        })
+        .await?;
+
+    // The user may have been verified, suspended or changed while the mail went out
+    data.db.mark_verification_sent(&user_id, Utc::now()).await?;
+    Ok(())
+}
+
+fn invalid_link(message: &str) -> ActixWebError {
+    ErrorBadRequest(json!({"status": "fail", "message": message}))
+}
+
+#[get("/auth/verify-email")]
+async fn verify_email_handler(
+    query: web::Query<VerifyEmailQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let claims = email_token::verify(&query.token, VERIFY_EMAIL, &data.env.jwt_secret).map_err(
+        |e| match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => {
+                invalid_link("Verification link has expired")
+            }
+            _ => invalid_link("Invalid verification link"),
+        },
+    )?;
+
+    let user = data
+        .db
+        .find_by_id(&claims.sub)
+        .await?
+        .ok_or_else(|| invalid_link("Invalid verification link"))?;
+
+    // The link vouches for the address it was sent to, not for whatever the account uses now
+    if user.email != claims.email {
+        return Err(invalid_link("Invalid verification link"));
+    }
+
+    if !user.verified {
+        // Only the verified column, and only while the email is still the one the link went to
+        if !data.db.mark_verified(&claims.sub, &claims.email).await? {
+            return Err(invalid_link("Invalid verification link"));
+        }
+        log::info!("User {} verified their email", claims.sub);
+    }
+
+    Ok(HttpResponse::Ok().json(json!({"status": "success", "message": "Email verified"})))
+}
+
+#[post("/auth/verify-email/resend")]
+async fn resend_verification_handler(
+    body: web::Json<ResendVerificationSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let user = data.db.find_by_email(&body.email.to_lowercase()).await?;
+
+    // Answer the same whatever happens, so this can't be used to find out who has an account
+    if let Some(user) = user.filter(|user| !user.verified) {
+        let throttled = user.verificationSentAt.is_some_and(|sent_at| {
+            Utc::now() < sent_at + chrono::Duration::seconds(data.env.email_resend_interval)
+        });
+
+        if throttled {
+            log::info!("Throttled verification email for user {:?}", user.id);
+        } else if let Err(e) = send_verification_email(user, &data).await {
+            log::warn!("Failed to resend verification email: {}", e);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "If this account still needs verifying, a new link is on its way"
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{handlers::auth_handler::config, mail::FileMailer};
+
+    fn app_state() -> (web::Data<AppState>, Arc<FileMailer>) {
+        let mailer = Arc::new(FileMailer::temporary());
+        let data = AppState {
+            mailer: mailer.clone(),
+            ..AppState::for_tests()
+        };
+        (web::Data::new(data), mailer)
+    }
+
+    fn link_token(mail: &str) -> String {
+        let (_, rest) = mail.split_once("token=").expect("no link in the email");
+        rest.split_whitespace().next().unwrap().to_string()
+    }
+
+    fn verify(token: &str) -> test::TestRequest {
+        test::TestRequest::get().uri(&format!("/api/auth/verify-email?token={}", token))
+    }
+
+    #[actix_web::test]
+    async fn the_emailed_link_verifies_a_new_account() {
+        let (data, mailer) = app_state();
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/register")
+            .set_json(
+                json!({"name": "Alice", "email": "Alice@Example.com", "password": "correct horse"}),
+            )
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let sent = mailer.sent();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].contains("To: alice@example.com"), "{}", sent[0]);
+        let user = data
+            .db
+            .find_by_email("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!user.verified);
+        assert!(user.verificationSentAt.is_some());
+
+        let res = test::call_service(&app, verify(&link_token(&sent[0])).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let user = data
+            .db
+            .find_by_email("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(user.verified);
+    }
+
+    #[actix_web::test]
+    async fn a_link_to_a_previous_address_is_refused() {
+        let (data, mailer) = app_state();
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let user = User {
+            verified: false,
+            ..User::for_tests("user-1", "old@example.com")
+        };
+        let user = data.db.insert(user).await.unwrap();
+        send_verification_email(user.clone(), &data).await.unwrap();
+        data.db
+            .update(User {
+                email: "new@example.com".to_string(),
+                ..user
+            })
+            .await
+            .unwrap();
+
+        let res =
+            test::call_service(&app, verify(&link_token(&mailer.sent()[0])).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["message"], "Invalid verification link");
+        assert!(
+            !data
+                .db
+                .find_by_id("user-1")
+                .await
+                .unwrap()
+                .unwrap()
+                .verified
+        );
+
+        let res = test::call_service(&app, verify("garbage").to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn resends_are_throttled_and_never_reveal_accounts() {
+        let (data, mailer) = app_state();
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let user = User {
+            verified: false,
+            ..User::for_tests("user-1", "alice@example.com")
+        };
+        data.db.insert(user).await.unwrap();
+
+        let resend = |email: &str| {
+            test::TestRequest::post()
+                .uri("/api/auth/verify-email/resend")
+                .set_json(json!({ "email": email }))
+                .to_request()
+        };
+
+        let mut messages = Vec::new();
+        for email in [
+            "ALICE@example.com",
+            "alice@example.com",
+            "nobody@example.com",
+        ] {
+            let res = test::call_service(&app, resend(email)).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            let body: Value = test::read_body_json(res).await;
+            messages.push(body["message"].clone());
+        }
+
+        // The second request came within EMAIL_RESEND_INTERVAL
+        assert_eq!(mailer.sent().len(), 1);
+        assert!(messages.iter().all(|message| *message == messages[0]));
+    }
+}
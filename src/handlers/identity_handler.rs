@@ -0,0 +1,131 @@
+use crate::{
+    auth::token_guard::AuthenticationGuard,
+    models::{AppState, Identity},
+    repository::RepositoryError,
+    responses::{FilteredIdentity, IdentityListData, IdentityListResponse},
+};
+use actix_web::{
+    delete, error::ErrorBadRequest, get, web, HttpResponse, Responder, Result as ActixResult,
+};
+use serde_json::json;
+
+fn identity_list_response(identities: &[Identity]) -> HttpResponse {
+    HttpResponse::Ok().json(IdentityListResponse {
+        status: "success".to_string(),
+        data: IdentityListData {
+            identities: identities.iter().map(identity_to_response).collect(),
+        },
+    })
+}
+
+fn identity_to_response(identity: &Identity) -> FilteredIdentity {
+    FilteredIdentity {
+        provider: identity.provider.to_owned(),
+        email: identity.email.to_owned(),
+        createdAt: identity.created_at,
+    }
+}
+
+#[get("/users/me/identities")]
+async fn list_identities_handler(
+    auth_guard: AuthenticationGuard,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let identities = data.identities.list_for_user(&auth_guard.user_id).await?;
+    Ok(identity_list_response(&identities))
+}
+
+#[delete("/users/me/identities/{provider}")]
+async fn unlink_identity_handler(
+    auth_guard: AuthenticationGuard,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let provider = path.into_inner();
+    let user = data
+        .db
+        .find_by_id(&auth_guard.user_id)
+        .await?
+        .ok_or(RepositoryError::NotFound)?;
+    let identities = data.identities.list_for_user(&auth_guard.user_id).await?;
+
+    if !identities
+        .iter()
+        .any(|identity| identity.provider == provider)
+    {
+        return Err(RepositoryError::NotFound.into());
+    }
+
+    // Keep at least one way to sign in, a password or another provider
+    if user.password.is_empty() && identities.len() == 1 {
+        return Err(ErrorBadRequest(json!({
+            "status": "fail",
+            "message": "You can't unlink your only sign in method"
+        })));
+    }
+
+    data.identities
+        .delete(&auth_guard.user_id, &provider)
+        .await?;
+
+    log::info!("User {} unlinked {}", auth_guard.user_id, provider);
+
+    let identities = data.identities.list_for_user(&auth_guard.user_id).await?;
+    Ok(identity_list_response(&identities))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use chrono::Utc;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{auth::token, handlers::auth_handler::config, models::User};
+
+    async fn link(data: &AppState, provider: &str) {
+        data.identities
+            .insert(Identity {
+                provider: provider.to_string(),
+                subject: format!("{}-1", provider),
+                user_id: "user-1".to_string(),
+                email: "alice@example.com".to_string(),
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[actix_web::test]
+    async fn the_only_sign_in_method_stays_linked() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        // No password, so the providers are the only way in
+        let user = data
+            .db
+            .insert(User::for_tests("user-1", "alice@example.com"))
+            .await
+            .unwrap();
+        let access_token = token::create_access_token(&user, &data).unwrap();
+        link(&data, "google").await;
+        link(&data, "github").await;
+
+        let unlink = |provider: &str| {
+            test::TestRequest::delete()
+                .uri(&format!("/api/users/me/identities/{}", provider))
+                .insert_header(("Authorization", format!("Bearer {}", access_token)))
+                .to_request()
+        };
+
+        let res = test::call_service(&app, unlink("google")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["identities"][0]["provider"], "github");
+
+        let res = test::call_service(&app, unlink("github")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let res = test::call_service(&app, unlink("google")).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
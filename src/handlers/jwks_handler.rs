@@ -0,0 +1,35 @@
+use crate::models::AppState;
+use actix_web::{get, http::header::CACHE_CONTROL, web, HttpResponse, Responder};
+
+/// Public keys for verifying our access tokens, HS256 secrets are never listed.
+#[get("/.well-known/jwks.json")]
+async fn jwks_handler(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "public, max-age=300"))
+        .json(data.keyring.jwks())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn the_hs256_secret_is_never_published() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = test::init_service(App::new().app_data(data).service(jwks_handler)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/.well-known/jwks.json")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(CACHE_CONTROL).unwrap(),
+            "public, max-age=300"
+        );
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["keys"], serde_json::json!([]));
+    }
+}
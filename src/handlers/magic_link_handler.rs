@@ -0,0 +1,451 @@
+use crate::{
+    auth::{
+        oauth_state,
+        one_time_token::{self, MAGIC_LINK},
+        token,
+    },
+    handlers::mfa_handler::mfa_challenge,
+    mail::Email,
+    models::{AppState, MagicLinkSchema, RedeemMagicLinkSchema, User},
+};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError},
+    post, web, HttpResponse, Responder, Result as ActixResult,
+};
+use chrono::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+
+async fn send_magic_link(email: String, redirect_to: String, data: &AppState) -> ActixResult<()> {
+    let user_id = data
+        .db
+        .find_by_email(&email)
+        .await?
+        .and_then(|user| user.id);
+
+    // Only the latest link of an account works
+    if let Some(user_id) = &user_id {
+        data.one_time_tokens
+            .revoke_user(user_id, MAGIC_LINK)
+            .await?;
+    }
+    let token = one_time_token::issue(
+        MAGIC_LINK,
+        user_id.as_deref(),
+        &email,
+        data.env.magic_link_max_age,
+        data,
+    )
+    .await?;
+
+    data.mailer
+        .send(Email {
+            to: email,
+            subject: "Your sign-in link".to_string(),
+            body: format!(
+                "Hi,\n\nUse this link to sign in:\n\n{}\n\nThe link expires in {} minutes and works once. If you didn't ask for it, you can ignore this email.\n",
+                client_page(&token, Some(&redirect_to), data)?,
+                data.env.magic_link_max_age
+            ),
+        })
+        .await?;
+    Ok(())
+}
+
+/// The client page that posts the token back, opening a link must not sign anyone in on its own.
+fn client_page(token: &str, redirect_to: Option<&str>, data: &AppState) -> ActixResult<String> {
+    let mut url = reqwest::Url::parse(&format!("{}/magic-link", data.env.client_origin))
+        .map_err(|_| ErrorInternalServerError("CLIENT_ORIGIN is not a valid URL"))?;
+    url.query_pairs_mut().append_pair("token", token);
+    if let Some(redirect_to) = redirect_to {
+        url.query_pairs_mut()
+            .append_pair("redirect_to", redirect_to);
+    }
+    Ok(url.to_string())
+}
+
+/// First sign-in of an address without an account.
+async fn create_email_user(email: &str, data: &AppState) -> ActixResult<User> {
+    let datetime = Utc::now();
+    let name = email.split('@').next().unwrap_or(email).to_string();
+
+    let user = data
+        .db
+        .insert(User {
+            id: Some(Uuid::new_v4().to_string()),
+            name,
+            verified: true,
+            suspended: false,
+            verificationSentAt: None,
+            email: email.to_string(),
+            provider: "email".to_string(),
+            role: "user".to_string(),
+            password: "".to_string(),
+            photo: "default.png".to_string(),
+            createdAt: Some(datetime),
+            updatedAt: Some(datetime),
+        })
+        .await?;
+
+    log::info!(
+        "User {} signed up with a magic link",
+        user.id.as_deref().unwrap_or_default()
+    );
+    Ok(user)
+}
+
+/// Removes the password, second factors and linked providers of an unverified account, so
+/// only the owner of the address signs in from now on. Returns `false` if the password
+/// changed in the meantime.
+async fn drop_sign_in_methods(user: &User, data: &AppState) -> ActixResult<bool> {
+    let user_id = user.id.as_deref().unwrap_or_default();
+    if !user.password.is_empty()
+        && !data
+            .db
+            .replace_password(user_id, &user.password, "")
+            .await?
+    {
+        return Ok(false);
+    }
+
+    data.two_factor.delete(user_id).await?;
+    for credential in data.webauthn_credentials.list_for_user(user_id).await? {
+        data.webauthn_credentials
+            .delete(user_id, &credential.credential_id)
+            .await?;
+    }
+    for identity in data.identities.list_for_user(user_id).await? {
+        data.identities.delete(user_id, &identity.provider).await?;
+    }
+
+    log::info!(
+        "Magic link took over unverified user {}, other sign-in methods removed",
+        user_id
+    );
+    Ok(true)
+}
+
+#[post("/auth/magic-link")]
+async fn magic_link_handler(
+    body: web::Json<MagicLinkSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let body = body.into_inner();
+    let email = body.email.trim().to_lowercase();
+    // Anyone who can read the link gets an account, so it has to be an address we can mail
+    if email.parse::<lettre::Address>().is_err() {
+        return Err(ErrorBadRequest(
+            json!({"status": "fail", "message": "Invalid email"}),
+        ));
+    }
+
+    let redirect_to = body.redirect_to.unwrap_or_else(|| "/".to_string());
+    if !oauth_state::is_safe_redirect(&redirect_to) {
+        return Err(ErrorBadRequest(
+            json!({"status": "fail", "message": "redirect_to must be a path on the client"}),
+        ));
+    }
+
+    // Mail in the background so the response takes as long whether the account exists or not
+    let data = data.clone();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = send_magic_link(email, redirect_to, &data).await {
+            log::warn!("Failed to send magic link: {}", e);
+        }
+    });
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "Check your inbox for a link to sign in"
+    })))
+}
+
+#[post("/auth/magic-link/callback")]
+async fn redeem_magic_link_handler(
+    body: web::Json<RedeemMagicLinkSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let invalid_link =
+        || ErrorBadRequest(json!({"status": "fail", "message": "Invalid or expired sign-in link"}));
+
+    let stored = one_time_token::redeem(&body.token, MAGIC_LINK, &data)
+        .await?
+        .ok_or_else(invalid_link)?;
+
+    let user = match &stored.user_id {
+        // The link was sent to an address the account no longer uses
+        Some(user_id) => data
+            .db
+            .find_by_id(user_id)
+            .await?
+            .filter(|user| user.email == stored.email)
+            .ok_or_else(invalid_link)?,
+        // Nobody had the address then, but it may have signed up since
+        None => match data.db.find_by_email(&stored.email).await? {
+            Some(user) => user,
+            None => create_email_user(&stored.email, &data).await?,
+        },
+    };
+
+    if user.suspended {
+        return Err(ErrorForbidden(
+            json!({"status": "fail", "message": "Your account has been suspended"}),
+        ));
+    }
+
+    // Opening the link proves the address works, and whoever registered it before can't
+    // have been its owner
+    let user_id = user.id.as_deref().unwrap_or_default();
+    let first_verification = !user.verified;
+    if first_verification && !drop_sign_in_methods(&user, &data).await? {
+        return Err(invalid_link());
+    }
+    if !data.db.mark_verified(user_id, &stored.email).await? {
+        return Err(invalid_link());
+    }
+
+    if let Some(challenge) = mfa_challenge(&user, &data).await? {
+        return Ok(challenge);
+    }
+
+    let session = if first_verification {
+        token::renew_all_sessions(&user, &data).await?
+    } else {
+        token::issue_session(&user, None, &data).await?
+    };
+    let [access_cookie, refresh_cookie] = token::session_cookies(&session, &data);
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(json!({
+            "status": "success",
+            "token": session.access_token,
+            "refresh_token": session.refresh_token
+        })))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+    use std::{sync::Arc, time::Duration};
+
+    use super::*;
+    use crate::{
+        auth::password::hash_password,
+        handlers::auth_handler::config,
+        mail::FileMailer,
+        models::{Identity, TotpFactor},
+    };
+
+    fn app_state() -> (web::Data<AppState>, Arc<FileMailer>) {
+        let mailer = Arc::new(FileMailer::temporary());
+        let data = AppState {
+            mailer: mailer.clone(),
+            ..AppState::for_tests()
+        };
+        (web::Data::new(data), mailer)
+    }
+
+    // The link is mailed in the background
+    async fn wait_for_mail(mailer: &FileMailer, count: usize) -> Vec<String> {
+        for _ in 0..100 {
+            let sent = mailer.sent();
+            if sent.len() >= count {
+                return sent;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected {} emails", count);
+    }
+
+    fn link_token(mail: &str) -> String {
+        let (_, rest) = mail.split_once("token=").expect("no link in the email");
+        rest.split(['&', ' ', '\r', '\n'])
+            .next()
+            .unwrap()
+            .to_string()
+    }
+
+    fn post(uri: &str, body: Value) -> test::TestRequest {
+        test::TestRequest::post().uri(uri).set_json(body)
+    }
+
+    #[actix_web::test]
+    async fn the_emailed_link_signs_a_new_address_up_once() {
+        let (data, mailer) = app_state();
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        let req = post(
+            "/api/auth/magic-link",
+            json!({"email": "Carol@example.com", "redirect_to": "/posts/1"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let mail = &wait_for_mail(&mailer, 1).await[0];
+        assert!(mail.contains("https://localhost:3000/magic-link?token="));
+        assert!(mail.contains("redirect_to=%2Fposts%2F1"));
+
+        // Opening the page mustn't be enough, only the client's POST redeems the link
+        let req = test::TestRequest::get().uri(&format!(
+            "/api/auth/magic-link/callback?token={}",
+            link_token(mail)
+        ));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let redeem = || {
+            post(
+                "/api/auth/magic-link/callback",
+                json!({"token": link_token(mail)}),
+            )
+        };
+        let res = test::call_service(&app, redeem().to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert!(body["token"].is_string());
+        let res = test::call_service(&app, redeem().to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let user = data.db.find_by_email("carol@example.com").await.unwrap();
+        let user = user.unwrap();
+        assert_eq!(user.provider, "email");
+        assert!(user.verified);
+    }
+
+    #[actix_web::test]
+    async fn links_need_a_mailable_address_and_a_client_path() {
+        let (data, mailer) = app_state();
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        for body in [
+            json!({"email": "not an address"}),
+            json!({"email": "carol@example.com", "redirect_to": "https://evil.example"}),
+            json!({"email": "carol@example.com", "redirect_to": "//evil.example"}),
+        ] {
+            let res =
+                test::call_service(&app, post("/api/auth/magic-link", body).to_request()).await;
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+        assert!(mailer.sent().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn the_owner_of_a_pre_registered_address_takes_the_account_over() {
+        let (data, mailer) = app_state();
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        // Someone registered the address with their own password, 2FA and GitHub account
+        let password = hash_password("squatter password", data.env.argon2_params.clone()).unwrap();
+        let user = data
+            .db
+            .insert(User {
+                password,
+                verified: false,
+                ..User::for_tests("user-1", "alice@example.com")
+            })
+            .await
+            .unwrap();
+        data.two_factor
+            .save_totp(TotpFactor {
+                user_id: "user-1".to_string(),
+                secret: "JBSWY3DPEHPK3PXP".to_string(),
+                enabled: true,
+                last_used_step: None,
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        data.identities
+            .insert(Identity {
+                provider: "github".to_string(),
+                subject: "squatter".to_string(),
+                user_id: "user-1".to_string(),
+                email: "squatter@example.com".to_string(),
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let squatter_session = token::issue_session(&user, None, &data).await.unwrap();
+
+        let req = post(
+            "/api/auth/magic-link",
+            json!({"email": "alice@example.com"}),
+        );
+        test::call_service(&app, req.to_request()).await;
+        let token = link_token(&wait_for_mail(&mailer, 1).await[0]);
+
+        let req = post("/api/auth/magic-link/callback", json!({"token": token}));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        // No TOTP challenge from a factor the owner never set up
+        assert_eq!(body["status"], "success");
+        let access_token = body["token"].as_str().unwrap();
+
+        let stored = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        assert!(stored.verified);
+        assert!(stored.password.is_empty());
+        assert!(data.two_factor.find_totp("user-1").await.unwrap().is_none());
+        assert!(data
+            .identities
+            .list_for_user("user-1")
+            .await
+            .unwrap()
+            .is_empty());
+
+        let req = post(
+            "/api/auth/login",
+            json!({"email": "alice@example.com", "password": "squatter password"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let req = post(
+            "/api/auth/refresh",
+            json!({"refresh_token": squatter_session.refresh_token}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        for (token, status) in [
+            (
+                squatter_session.access_token.as_str(),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (access_token, StatusCode::OK),
+        ] {
+            let req = test::TestRequest::get()
+                .uri("/api/users/me")
+                .insert_header(("Authorization", format!("Bearer {}", token)));
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_link_to_a_previous_address_is_refused() {
+        let (data, mailer) = app_state();
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        data.db
+            .insert(User::for_tests("user-1", "alice@example.com"))
+            .await
+            .unwrap();
+
+        let req = post(
+            "/api/auth/magic-link",
+            json!({"email": "alice@example.com"}),
+        );
+        test::call_service(&app, req.to_request()).await;
+        let token = link_token(&wait_for_mail(&mailer, 1).await[0]);
+        data.db
+            .change_email("user-1", "alice@new.example")
+            .await
+            .unwrap();
+
+        let req = post("/api/auth/magic-link/callback", json!({"token": token}));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,562 @@
+use crate::{
+    auth::{login_throttle, mfa, token, token_guard::AuthenticationGuard, totp},
+    handlers::auth_handler::too_many_attempts,
+    models::{AppState, MfaCodeSchema, MfaPendingClaims, MfaVerifySchema, TotpFactor, User},
+    repository::RepositoryError,
+};
+use actix_web::{
+    delete,
+    error::{ErrorBadRequest, ErrorInternalServerError, ErrorUnauthorized},
+    get, post, web, Error as ActixWebError, HttpRequest, HttpResponse, Responder,
+    Result as ActixResult,
+};
+use chrono::prelude::*;
+use serde_json::json;
+
+fn bad_request(message: &str) -> ActixWebError {
+    ErrorBadRequest(json!({"status": "fail", "message": message}))
+}
+
+fn invalid_code() -> ActixWebError {
+    ErrorUnauthorized(json!({"status": "fail", "message": "Invalid authentication code"}))
+}
+
+/// What `login_user_handler` answers instead of a session while the second factor is due.
+pub async fn mfa_challenge(user: &User, data: &AppState) -> ActixResult<Option<HttpResponse>> {
+    let user_id = user.id.as_deref().unwrap_or_default();
+    if !mfa::is_enabled(user_id, data).await? {
+        return Ok(None);
+    }
+
+    let mut methods = vec!["totp", "recovery_code"];
+    if !data
+        .webauthn_credentials
+        .list_for_user(user_id)
+        .await?
+        .is_empty()
+    {
+        methods.push("webauthn");
+    }
+
+    let mfa_token = mfa::create_pending_token(user_id, data)?;
+    Ok(Some(
+        HttpResponse::Ok()
+            .cookie(mfa::pending_cookie(mfa_token.clone(), data))
+            .json(json!({"status": "mfa_required", "mfa_token": mfa_token, "methods": methods})),
+    ))
+}
+
+/// The login waiting for its second factor, from the `mfa_pending` cookie or the body.
+pub fn pending_claims(
+    req: &HttpRequest,
+    mfa_token: Option<String>,
+    data: &AppState,
+) -> ActixResult<MfaPendingClaims> {
+    let mfa_token = match req.cookie(mfa::MFA_PENDING_COOKIE) {
+        Some(cookie) => cookie.value().to_string(),
+        None => mfa_token.ok_or_else(|| bad_request("mfa_token not provided"))?,
+    };
+
+    mfa::verify_pending_token(&mfa_token, data).map_err(|_| {
+        ErrorUnauthorized(
+            json!({"status": "fail", "message": "Log in again, the 2FA step expired"}),
+        )
+    })
+}
+
+/// Finishes a login once the second factor checked out.
+pub async fn mfa_session_response(user: &User, data: &AppState) -> ActixResult<HttpResponse> {
+    let session = token::issue_session(user, None, data).await?;
+    let [access_cookie, refresh_cookie] = token::session_cookies(&session, data);
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .cookie(mfa::pending_removal_cookie())
+        .json(json!({
+            "status": "success",
+            "token": session.access_token,
+            "refresh_token": session.refresh_token
+        })))
+}
+
+/// Stores fresh recovery codes, replacing the old ones, and returns them in plain text.
+async fn regenerate_recovery_codes(
+    user_id: &str,
+    data: &AppState,
+) -> Result<Vec<String>, RepositoryError> {
+    let codes = mfa::generate_recovery_codes();
+    data.two_factor
+        .replace_recovery_codes(
+            user_id,
+            codes
+                .iter()
+                .map(|code| mfa::hash_recovery_code(code))
+                .collect(),
+        )
+        .await?;
+    Ok(codes)
+}
+
+#[get("/auth/mfa")]
+async fn mfa_status_handler(
+    auth_guard: AuthenticationGuard,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let totp_enabled = mfa::is_enabled(&auth_guard.user_id, &data).await?;
+    let recovery_codes_left = data
+        .two_factor
+        .count_recovery_codes(&auth_guard.user_id)
+        .await?;
+    let passkeys = data
+        .webauthn_credentials
+        .list_for_user(&auth_guard.user_id)
+        .await?
+        .len();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "data": {
+            "totp_enabled": totp_enabled,
+            "recovery_codes_left": recovery_codes_left,
+            "passkeys": passkeys
+        }
+    })))
+}
+
+#[post("/auth/mfa/totp/enroll")]
+async fn totp_enroll_handler(
+    auth_guard: AuthenticationGuard,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    if mfa::is_enabled(&auth_guard.user_id, &data).await? {
+        return Err(RepositoryError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        )
+        .into());
+    }
+
+    let user = data
+        .db
+        .find_by_id(&auth_guard.user_id)
+        .await?
+        .ok_or(RepositoryError::NotFound)?;
+
+    // Starting over replaces a secret that was never confirmed
+    let secret = totp::generate_secret();
+    let otpauth_url = totp::otpauth_url(&secret, &data.env.totp_issuer, &user.email)
+        .ok_or_else(|| ErrorInternalServerError("Failed to create TOTP secret"))?;
+    data.two_factor
+        .save_totp(TotpFactor {
+            user_id: auth_guard.user_id.clone(),
+            secret: secret.clone(),
+            enabled: false,
+            last_used_step: None,
+            created_at: Utc::now(),
+        })
+        .await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "data": {"secret": secret, "otpauth_url": otpauth_url}
+    })))
+}
+
+#[post("/auth/mfa/totp/confirm")]
+async fn totp_confirm_handler(
+    auth_guard: AuthenticationGuard,
+    body: web::Json<MfaCodeSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let mut factor = match data.two_factor.find_totp(&auth_guard.user_id).await? {
+        Some(factor) if !factor.enabled => factor,
+        Some(_) => return Err(bad_request("Two-factor authentication is already enabled")),
+        None => return Err(bad_request("Start with /api/auth/mfa/totp/enroll")),
+    };
+
+    let code = body
+        .code
+        .as_deref()
+        .ok_or_else(|| bad_request("code is required"))?;
+    // Proves the authenticator app holds the secret before the account depends on it
+    let step = totp::verify(&factor.secret, code).ok_or_else(invalid_code)?;
+
+    factor.enabled = true;
+    factor.last_used_step = Some(step);
+    data.two_factor.save_totp(factor).await?;
+    let recovery_codes = regenerate_recovery_codes(&auth_guard.user_id, &data).await?;
+
+    // Sessions opened before, maybe by someone who only knew the password, are ended
+    let user = data
+        .db
+        .find_by_id(&auth_guard.user_id)
+        .await?
+        .ok_or(RepositoryError::NotFound)?;
+    let session = token::renew_all_sessions(&user, &data).await?;
+    let [access_cookie, refresh_cookie] = token::session_cookies(&session, &data);
+
+    log::info!(
+        "User {} enabled two-factor authentication",
+        auth_guard.user_id
+    );
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(json!({
+            "status": "success",
+            "data": {"recovery_codes": recovery_codes},
+            "token": session.access_token,
+            "refresh_token": session.refresh_token
+        })))
+}
+
+#[delete("/auth/mfa/totp")]
+async fn totp_disable_handler(
+    auth_guard: AuthenticationGuard,
+    body: web::Json<MfaCodeSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    // Counted like the login step, a stolen session mustn't guess codes any faster
+    if let Some(retry_after) = login_throttle::reserve_mfa(&auth_guard.user_id, &data).await? {
+        return Ok(too_many_attempts(retry_after));
+    }
+
+    let verified = mfa::check_second_factor(
+        &auth_guard.user_id,
+        body.code.as_deref(),
+        body.recovery_code.as_deref(),
+        &data,
+    )
+    .await?;
+    if !verified {
+        return Err(invalid_code());
+    }
+    login_throttle::unlock_mfa(&auth_guard.user_id, &data).await?;
+
+    data.two_factor.delete(&auth_guard.user_id).await?;
+
+    log::info!(
+        "User {} disabled two-factor authentication",
+        auth_guard.user_id
+    );
+    Ok(HttpResponse::Ok().json(json!({"status": "success"})))
+}
+
+#[post("/auth/mfa/recovery-codes")]
+async fn recovery_codes_handler(
+    auth_guard: AuthenticationGuard,
+    body: web::Json<MfaCodeSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let code = body
+        .code
+        .as_deref()
+        .ok_or_else(|| bad_request("code is required"))?;
+    if let Some(retry_after) = login_throttle::reserve_mfa(&auth_guard.user_id, &data).await? {
+        return Ok(too_many_attempts(retry_after));
+    }
+    if !mfa::check_second_factor(&auth_guard.user_id, Some(code), None, &data).await? {
+        return Err(invalid_code());
+    }
+    login_throttle::unlock_mfa(&auth_guard.user_id, &data).await?;
+
+    let recovery_codes = regenerate_recovery_codes(&auth_guard.user_id, &data).await?;
+
+    log::info!(
+        "User {} regenerated their recovery codes",
+        auth_guard.user_id
+    );
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "data": {"recovery_codes": recovery_codes}
+    })))
+}
+
+#[post("/auth/mfa/verify")]
+async fn mfa_verify_handler(
+    req: HttpRequest,
+    body: web::Json<MfaVerifySchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let body = body.into_inner();
+    let claims = pending_claims(&req, body.mfa_token, &data)?;
+
+    let user = data
+        .db
+        .find_by_id(&claims.sub)
+        .await?
+        .ok_or_else(invalid_code)?;
+    if user.suspended {
+        return Ok(HttpResponse::Forbidden()
+            .json(json!({"status": "fail", "message": "Your account has been suspended"})));
+    }
+
+    if let Some(retry_after) = login_throttle::reserve_mfa(&claims.sub, &data).await? {
+        return Ok(too_many_attempts(retry_after));
+    }
+
+    let verified = mfa::check_second_factor(
+        &claims.sub,
+        body.code.as_deref(),
+        body.recovery_code.as_deref(),
+        &data,
+    )
+    .await?;
+    if !verified {
+        return Err(invalid_code());
+    }
+    login_throttle::unlock_mfa(&claims.sub, &data).await?;
+
+    mfa_session_response(&user, &data).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{header, StatusCode},
+        test, App,
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{auth::password::hash_password, handlers::auth_handler::config};
+
+    async fn app_state() -> (web::Data<AppState>, String) {
+        let data = AppState::for_tests();
+        let password = hash_password("password", data.env.argon2_params.clone()).unwrap();
+        let user = User {
+            password,
+            ..User::for_tests("user-1", "alice@example.com")
+        };
+        let user = data.db.insert(user).await.unwrap();
+        let access_token = token::create_access_token(&user, &data).unwrap();
+        (web::Data::new(data), access_token)
+    }
+
+    /// Turns 2FA on without going through the handlers, with a single known recovery code.
+    async fn enable(data: &AppState) -> String {
+        let secret = totp::generate_secret();
+        data.two_factor
+            .save_totp(TotpFactor {
+                user_id: "user-1".to_string(),
+                secret: secret.clone(),
+                enabled: true,
+                last_used_step: None,
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        data.two_factor
+            .replace_recovery_codes("user-1", vec![mfa::hash_recovery_code("abcde-fghjk")])
+            .await
+            .unwrap();
+        secret
+    }
+
+    fn request(method: &str, uri: &str, access_token: &str, body: Value) -> test::TestRequest {
+        let req = match method {
+            "DELETE" => test::TestRequest::delete(),
+            _ => test::TestRequest::post(),
+        };
+        req.uri(uri)
+            .insert_header(("Authorization", format!("Bearer {}", access_token)))
+            .set_json(body)
+    }
+
+    #[actix_web::test]
+    async fn once_enrolled_logins_need_a_fresh_code() {
+        let (data, access_token) = app_state().await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        let req = request(
+            "POST",
+            "/api/auth/mfa/totp/enroll",
+            &access_token,
+            json!({}),
+        );
+        let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        let secret = body["data"]["secret"].as_str().unwrap().to_string();
+        assert!(body["data"]["otpauth_url"]
+            .as_str()
+            .unwrap()
+            .starts_with("otpauth://totp/blog-rs:alice%40example.com"));
+
+        let step = totp::current_step();
+        let req = request(
+            "POST",
+            "/api/auth/mfa/totp/confirm",
+            &access_token,
+            json!({"code": totp::code_at(&secret, step)}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["recovery_codes"].as_array().unwrap().len(), 10);
+        let recovery_code = body["data"]["recovery_codes"][0]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        let req = test::TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(json!({"email": "alice@example.com", "password": "password"}));
+        let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        assert_eq!(body["status"], "mfa_required");
+        assert!(body.get("token").is_none());
+        let mfa_token = body["mfa_token"].as_str().unwrap();
+
+        for (second_factor, status) in [
+            // Already used to confirm the enrollment
+            (
+                json!({"code": totp::code_at(&secret, step)}),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                json!({"code": totp::code_at(&secret, step + 1)}),
+                StatusCode::OK,
+            ),
+            (json!({"recovery_code": recovery_code}), StatusCode::OK),
+            (
+                json!({"recovery_code": recovery_code}),
+                StatusCode::UNAUTHORIZED,
+            ),
+        ] {
+            let mut body = second_factor.clone();
+            body["mfa_token"] = json!(mfa_token);
+            let req = test::TestRequest::post()
+                .uri("/api/auth/mfa/verify")
+                .set_json(body);
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status, "{}", second_factor);
+        }
+    }
+
+    #[actix_web::test]
+    async fn the_2fa_step_refuses_suspended_users_and_other_tokens() {
+        let (data, access_token) = app_state().await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        enable(&data).await;
+
+        // A session token can't stand in for the pending one
+        let req = test::TestRequest::post()
+            .uri("/api/auth/mfa/verify")
+            .set_json(json!({"mfa_token": access_token, "recovery_code": "abcde-fghjk"}));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        data.db.set_suspended("user-1", true).await.unwrap();
+        let mfa_token = mfa::create_pending_token("user-1", &data).unwrap();
+        let req = test::TestRequest::post()
+            .uri("/api/auth/mfa/verify")
+            .set_json(json!({"mfa_token": mfa_token, "recovery_code": "abcde-fghjk"}));
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn turning_2fa_off_or_renewing_codes_needs_a_second_factor() {
+        let (data, access_token) = app_state().await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let secret = enable(&data).await;
+
+        // Recovery codes can't be used to mint new ones
+        let req = request(
+            "POST",
+            "/api/auth/mfa/recovery-codes",
+            &access_token,
+            json!({"recovery_code": "abcde-fghjk"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let req = request(
+            "POST",
+            "/api/auth/mfa/recovery-codes",
+            &access_token,
+            json!({"code": totp::code_at(&secret, totp::current_step())}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        let recovery_code = body["data"]["recovery_codes"][0]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        for (second_factor, status) in [
+            (json!({"code": "000000"}), StatusCode::UNAUTHORIZED),
+            // Replaced by the new codes
+            (
+                json!({"recovery_code": "abcde-fghjk"}),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (json!({"recovery_code": recovery_code}), StatusCode::OK),
+        ] {
+            let req = request(
+                "DELETE",
+                "/api/auth/mfa/totp",
+                &access_token,
+                second_factor.clone(),
+            );
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status, "{}", second_factor);
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/api/auth/mfa")
+            .insert_header(("Authorization", format!("Bearer {}", access_token)));
+        let body: Value = test::call_and_read_body_json(&app, req.to_request()).await;
+        assert_eq!(body["data"]["totp_enabled"], false);
+        assert_eq!(body["data"]["recovery_codes_left"], 0);
+    }
+
+    #[actix_web::test]
+    async fn wrong_second_factors_lock_every_2fa_step_until_one_succeeds() {
+        let (data, access_token) = app_state().await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let secret = enable(&data).await;
+        let mfa_token = mfa::create_pending_token("user-1", &data).unwrap();
+        let verify = |body: Value| {
+            let mut body = body;
+            body["mfa_token"] = json!(mfa_token);
+            test::TestRequest::post()
+                .uri("/api/auth/mfa/verify")
+                .set_json(body)
+                .to_request()
+        };
+
+        // A code that can never be right, whatever the current step
+        let wrong = json!({"code": "abcdef"});
+        for _ in 0..data.env.login_max_attempts {
+            let res = test::call_service(&app, verify(wrong.clone())).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+        let res = test::call_service(&app, verify(json!({"recovery_code": "abcde-fghjk"}))).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.headers().contains_key(header::RETRY_AFTER));
+
+        // The signed in steps share the lock, a session can't be used to keep guessing
+        for (method, uri) in [
+            ("DELETE", "/api/auth/mfa/totp"),
+            ("POST", "/api/auth/mfa/recovery-codes"),
+        ] {
+            let code = json!({"code": totp::code_at(&secret, totp::current_step())});
+            let req = request(method, uri, &access_token, code);
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS, "{}", uri);
+        }
+
+        // Below the limit, a success starts the count over
+        login_throttle::unlock_mfa("user-1", &data).await.unwrap();
+        for _ in 0..data.env.login_max_attempts - 1 {
+            let res = test::call_service(&app, verify(wrong.clone())).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+        let res = test::call_service(&app, verify(json!({"recovery_code": "abcde-fghjk"}))).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        for _ in 0..data.env.login_max_attempts {
+            let res = test::call_service(&app, verify(wrong.clone())).await;
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+}
@@ -1,2 +1,11 @@
+pub mod account_handler;
+pub mod admin_handler;
 pub mod auth_handler;
+pub mod email_verification_handler;
+pub mod identity_handler;
+pub mod jwks_handler;
+pub mod magic_link_handler;
+pub mod mfa_handler;
 pub mod oauth_handler;
+pub mod password_reset_handler;
+pub mod webauthn_handler;
@@ -1,239 +1,431 @@
-use std::{error::Error, str::FromStr};
-
 use crate::{
-    auth::github_oauth::{get_github_oauth_token, get_github_user},
-    auth::kakao_oauth::{get_kakao_oauth_token, get_kakao_user},
-    auth::naver_oauth::{get_naver_oauth_token, get_naver_user},
-    auth::OAuthTokenResponse,
     auth::{
-        google_oauth::{get_google_oauth_token, get_google_user},
+        crypto::constant_time_eq, mfa, oauth_state, token, token_guard::AuthenticationGuard,
         UserInfo,
     },
-    models::{AppState, QueryCode, TokenClaims, User},
+    models::{AppState, Identity, OAuthStartQuery, QueryCode, User},
+    repository::RepositoryError,
 };
 use actix_web::{
-    cookie::{time::Duration as ActixWebDuration, Cookie},
-    error::ErrorBadRequest,
-    get, web, HttpResponse, Responder, Result as ActixResult,
+    error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, InternalError},
+    get, web, Error as ActixWebError, HttpRequest, HttpResponse, HttpResponseBuilder, Responder,
+    Result as ActixResult,
 };
-use chrono::{prelude::*, Duration};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use chrono::prelude::*;
 use reqwest::header::LOCATION;
 use uuid::Uuid;
 
 // A helper function to simplify the error conversion
-fn to_bad_gateway<E: std::fmt::Display>(err: E) -> HttpResponse {
-    HttpResponse::BadGateway().json(serde_json::json!({
+fn to_bad_gateway<E: std::fmt::Display>(err: E) -> ActixWebError {
+    let response = HttpResponse::BadGateway().json(serde_json::json!({
         "status": "fail",
         "message": err.to_string()
-    }))
+    }));
+    InternalError::from_response(err.to_string(), response).into()
 }
 
-enum OAuthProvider {
-    Google,
-    GitHub,
-    Naver,
-    Kakao,
+fn new_identity(provider: &str, user_id: &str, user_info: &UserInfo) -> Identity {
+    Identity {
+        provider: provider.to_string(),
+        subject: user_info.subject.clone(),
+        user_id: user_id.to_string(),
+        email: user_info.email.to_lowercase(),
+        created_at: Utc::now(),
+    }
 }
 
-// Enum to String Mapping
-impl FromStr for OAuthProvider {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "google" => Ok(OAuthProvider::Google),
-            "github" => Ok(OAuthProvider::GitHub),
-            "kakao" => Ok(OAuthProvider::Kakao),
-            "naver" => Ok(OAuthProvider::Naver),
-            _ => Err(()),
-        }
-    }
+/// An OAuth login may join an account matched by email only when both sides vouch for the
+/// address. Password accounts are never joined, the password holder may not own the email.
+fn can_merge(user: &User, user_info: &UserInfo) -> bool {
+    user_info.email_verified && user.verified && user.password.is_empty()
 }
 
-fn update_user(user: &mut User, user_info: &UserInfo) {
-    user.email = user_info.email.clone();
-    user.provider = user_info.provider.clone();
-    if let Some(photo) = &user_info.photo {
-        user.photo = photo.clone();
+/// Finds the user signing in with `provider`, by the provider's subject first so a changed
+/// email at the provider still lands on the same account.
+async fn resolve_user(
+    provider: &str,
+    user_info: UserInfo,
+    data: &AppState,
+) -> Result<User, RepositoryError> {
+    if let Some(identity) = data.identities.find(provider, &user_info.subject).await? {
+        if let Some(user) = data.db.find_by_id(&identity.user_id).await? {
+            return Ok(user);
+        }
+        // The memory store has no cascading delete, drop what the removed user left behind
+        data.identities.delete(&identity.user_id, provider).await?;
     }
-    user.updatedAt = Some(Utc::now());
-    user.provider = user_info.provider.clone();
-    user.name = user_info.name.clone();
-}
 
-async fn find_or_create_user(user_info: UserInfo, vec: &mut Vec<User>) -> String {
     let email = user_info.email.to_lowercase();
-    match vec.iter_mut().find(|user| user.email == email) {
+    let user = match data.db.find_by_email(&email).await? {
+        Some(user) if can_merge(&user, &user_info) => user,
+        // Anyone can claim an address at some providers, the owner has to link it themselves
         Some(user) => {
-            update_user(user, &user_info);
-            user.id.clone().unwrap()
+            log::warn!(
+                "Refused to merge a {} login into user {} by email",
+                user_info.provider,
+                user.id.as_deref().unwrap_or_default()
+            );
+            return Err(RepositoryError::Conflict(format!(
+                "An account with this email already exists, log in to it and link {} from there",
+                user_info.provider
+            )));
         }
         None => {
             let datetime = Utc::now();
-            let id = Uuid::new_v4().to_string();
-            vec.push(User {
-                id: Some(id.clone()),
-                name: user_info.name,
-                verified: true,
-                email,
-                provider: user_info.provider,
-                role: "user".to_string(),
-                password: "".to_string(),
-                photo: user_info.photo.unwrap_or("default.png".to_string()),
-                createdAt: Some(datetime),
-                updatedAt: Some(datetime),
-            });
-            id
+            data.db
+                .insert(User {
+                    id: Some(Uuid::new_v4().to_string()),
+                    name: user_info.name.clone(),
+                    verified: user_info.email_verified,
+                    suspended: false,
+                    verificationSentAt: None,
+                    email,
+                    provider: user_info.provider.clone(),
+                    role: "user".to_string(),
+                    password: "".to_string(),
+                    photo: user_info.photo.clone().unwrap_or("default.png".to_string()),
+                    createdAt: Some(datetime),
+                    updatedAt: Some(datetime),
+                })
+                .await?
         }
-    }
+    };
+
+    let user_id = user.id.clone().unwrap();
+    data.identities
+        .insert(new_identity(provider, &user_id, &user_info))
+        .await?;
+    Ok(user)
 }
 
-async fn fetch_user_info(
-    provider: &OAuthProvider,
-    token: &str,
-    id_token: Option<&str>,
-) -> Result<UserInfo, Box<dyn Error>> {
-    match provider {
-        OAuthProvider::Google => {
-            let google_user = get_google_user(token, id_token.unwrap_or_default()).await?;
-            Ok(UserInfo {
-                name: google_user.name,
-                email: google_user.email,
-                photo: Some(google_user.picture),
-                provider: "Google".to_string(),
-            })
-        }
-        OAuthProvider::GitHub => {
-            let github_user = get_github_user(token).await?;
-            Ok(UserInfo {
-                name: github_user.login,
-                email: github_user.email,
-                photo: Some(github_user.avatar_url),
-                provider: "GitHub".to_string(),
-            })
-        }
-        OAuthProvider::Kakao => {
-            let kakao_user = get_kakao_user(token).await?;
-            let kakao_account_ref = kakao_user.kakao_account.as_ref();
-            let photo = kakao_account_ref
-                .and_then(|account| account.profile.as_ref())
-                .and_then(|profile| profile.thumbnail_image_url.as_deref())
-                .unwrap_or("default.png");
-
-            let name = kakao_account_ref
-                .and_then(|account| account.profile.as_ref())
-                .and_then(|profile| profile.nickname.as_deref())
-                .unwrap_or("Unknown");
-
-            Ok(UserInfo {
-                name: name.to_string(),
-                email: kakao_account_ref
-                    .and_then(|account| account.email.as_deref())
-                    .unwrap_or_default()
-                    .to_string(),
-                photo: Some(photo.to_string()),
-                provider: "Kakao".to_string(),
-            })
-        }
-        OAuthProvider::Naver => {
-            let naver_user = get_naver_user(token).await?;
-            Ok(UserInfo {
-                name: naver_user.response.nickname,
-                email: naver_user.response.email,
-                photo: Some(naver_user.response.profile_image),
-                provider: "Naver".to_string(),
-            })
+/// Links the provider account to a user who is already logged in.
+async fn link_identity(
+    provider: &str,
+    user_id: &str,
+    user_info: &UserInfo,
+    data: &AppState,
+) -> Result<(), RepositoryError> {
+    match data.identities.find(provider, &user_info.subject).await? {
+        Some(identity) if identity.user_id == user_id => Ok(()),
+        Some(_) => Err(RepositoryError::Conflict(format!(
+            "This {} account is already linked to another user",
+            user_info.provider
+        ))),
+        None => {
+            data.identities
+                .insert(new_identity(provider, user_id, user_info))
+                .await?;
+            log::info!("User {} linked {}", user_id, provider);
+            Ok(())
         }
     }
 }
 
+/// Sends a browser that just signed in back to `redirect_to` on the client with a session,
+/// or to the client's `/mfa` page first when the user has 2FA.
+pub async fn login_redirect(
+    user: &User,
+    redirect_to: &str,
+    data: &AppState,
+) -> ActixResult<HttpResponseBuilder> {
+    // The client's `/mfa` page collects the code and posts it to `/api/auth/mfa/verify`
+    let user_id = user.id.as_deref().unwrap_or_default();
+    if mfa::is_enabled(user_id, data).await? {
+        let mut mfa_url = reqwest::Url::parse(&format!("{}/mfa", data.env.client_origin))
+            .map_err(|_| ErrorInternalServerError("CLIENT_ORIGIN is not a valid URL"))?;
+        mfa_url
+            .query_pairs_mut()
+            .append_pair("redirect_to", redirect_to);
+        let mfa_token = mfa::create_pending_token(user_id, data)?;
+
+        let mut response = HttpResponse::Found();
+        response
+            .append_header((LOCATION, mfa_url.to_string()))
+            .cookie(mfa::pending_cookie(mfa_token, data));
+        return Ok(response);
+    }
+
+    let session = token::issue_session(user, None, data).await?;
+
+    let mut response = HttpResponse::Found();
+    response
+        .append_header((
+            LOCATION,
+            format!("{}{}", data.env.client_origin, redirect_to),
+        ))
+        .cookie(token::access_token_cookie(session.access_token, data))
+        .cookie(token::refresh_token_cookie(session.refresh_token, data));
+    Ok(response)
+}
+
+async fn start_flow(
+    provider_name: String,
+    query: OAuthStartQuery,
+    link_user_id: Option<String>,
+    data: &AppState,
+) -> ActixResult<HttpResponse> {
+    let provider = data
+        .oauth
+        .get(&provider_name)
+        .ok_or_else(|| ErrorBadRequest("Bad request provider here"))?;
+
+    let redirect_to = query.redirect_to.as_deref().unwrap_or("/");
+    if !oauth_state::is_safe_redirect(redirect_to) {
+        return Err(ErrorBadRequest("redirect_to must be a path on the client"));
+    }
+
+    let flow = oauth_state::new_flow(
+        provider.name(),
+        redirect_to,
+        provider.pkce_enabled(),
+        link_user_id,
+    );
+    let signed_flow = oauth_state::sign(&flow, &data.env.jwt_secret)
+        .map_err(|_| ErrorInternalServerError("Failed to start OAuth flow"))?;
+
+    let authorize_url = provider
+        .authorize_url(&flow)
+        .await
+        .map_err(to_bad_gateway)?;
+
+    Ok(HttpResponse::Found()
+        .append_header((LOCATION, authorize_url))
+        .cookie(oauth_state::cookie(signed_flow))
+        .finish())
+}
+
+#[get("/sessions/oauth/{provider}/start")]
+async fn oauth_start_handler(
+    path: web::Path<String>,
+    query: web::Query<OAuthStartQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    start_flow(path.into_inner(), query.into_inner(), None, &data).await
+}
+
+#[get("/sessions/oauth/{provider}/link")]
+async fn oauth_link_handler(
+    auth_guard: AuthenticationGuard,
+    path: web::Path<String>,
+    query: web::Query<OAuthStartQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    start_flow(
+        path.into_inner(),
+        query.into_inner(),
+        Some(auth_guard.user_id),
+        &data,
+    )
+    .await
+}
+
 #[get("/sessions/oauth/{provider}")]
 async fn oauth_handler(
+    req: HttpRequest,
     path: web::Path<String>,
     query: web::Query<QueryCode>,
     data: web::Data<AppState>,
 ) -> ActixResult<impl Responder> {
-    let provider: OAuthProvider = path
-        .into_inner()
-        .parse()
-        .map_err(|_| ErrorBadRequest("Bad request provider here"))?;
+    let provider = data
+        .oauth
+        .get(&path.into_inner())
+        .ok_or_else(|| ErrorBadRequest("Bad request provider here"))?;
+
+    // The state must match the one we generated for this browser in `oauth_start_handler`
+    let flow = req
+        .cookie(oauth_state::OAUTH_STATE_COOKIE)
+        .and_then(|cookie| oauth_state::verify(cookie.value(), &data.env.jwt_secret).ok())
+        .ok_or_else(|| ErrorBadRequest("OAuth state is missing or expired"))?;
+
+    if flow.provider != provider.name() || !constant_time_eq(&flow.state, &query.state) {
+        return Err(ErrorBadRequest("OAuth state mismatch"));
+    }
 
     let code = &query.code;
-    let state = &query.state;
 
     if code.trim().is_empty() {
         return Err(ErrorBadRequest("Authorization code not provided!"));
     }
 
-    let token_response = match provider {
-        OAuthProvider::Google => get_google_oauth_token(code.as_str(), &data)
+    let token = provider
+        .exchange_code(code.as_str(), &flow)
+        .await
+        .map_err(to_bad_gateway)?;
+
+    let user_info = provider
+        .fetch_user(&token, &flow)
+        .await
+        .map_err(to_bad_gateway)?;
+
+    let redirect_url = format!("{}{}", data.env.client_origin, flow.redirect_to);
+
+    // Linking keeps the session the user already has
+    if let Some(user_id) = &flow.link_user_id {
+        link_identity(provider.name(), user_id, &user_info, &data).await?;
+
+        return Ok(HttpResponse::Found()
+            .append_header((LOCATION, redirect_url))
+            .cookie(oauth_state::removal_cookie())
+            .finish());
+    }
+
+    let user = resolve_user(provider.name(), user_info, &data).await?;
+
+    if user.suspended {
+        return Err(ErrorForbidden("Your account has been suspended"));
+    }
+
+    Ok(login_redirect(&user, &flow.redirect_to, &data)
+        .await?
+        .cookie(oauth_state::removal_cookie())
+        .finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_info(subject: &str, email: &str) -> UserInfo {
+        UserInfo {
+            subject: subject.to_string(),
+            name: "Alice".to_string(),
+            email: email.to_string(),
+            email_verified: true,
+            photo: None,
+            provider: "Google".to_string(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn logins_follow_the_subject_when_the_email_changes() {
+        let data = AppState::for_tests();
+
+        let first = resolve_user("google", user_info("g-1", "Alice@Example.com"), &data)
             .await
-            .map(OAuthTokenResponse::Google),
-        OAuthProvider::GitHub => get_github_oauth_token(code.as_str(), &data)
+            .unwrap();
+        assert_eq!(first.email, "alice@example.com");
+
+        let again = resolve_user("google", user_info("g-1", "alice@new.example"), &data)
             .await
-            .map(OAuthTokenResponse::GitHub),
-        OAuthProvider::Kakao => get_kakao_oauth_token(code.as_str(), &data)
+            .unwrap();
+        assert_eq!(again.id, first.id);
+        assert!(data
+            .db
+            .find_by_email("alice@new.example")
             .await
-            .map(OAuthTokenResponse::Kakao),
-        OAuthProvider::Naver => get_naver_oauth_token(code.as_str(), &data)
+            .unwrap()
+            .is_none());
+    }
+
+    #[actix_web::test]
+    async fn an_existing_account_is_matched_by_email_once() {
+        let data = AppState::for_tests();
+        let existing = data
+            .db
+            .insert(User::for_tests("user-1", "alice@example.com"))
             .await
-            .map(OAuthTokenResponse::Naver),
+            .unwrap();
+
+        let user = resolve_user("google", user_info("g-1", "alice@example.com"), &data)
+            .await
+            .unwrap();
+        assert_eq!(user.id, existing.id);
+
+        let identity = data
+            .identities
+            .find("google", "g-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(identity.user_id, "user-1");
     }
-    .map_err(to_bad_gateway)
-    .unwrap();
-
-    // Deal OAuth2 providers
-    let user_info = match token_response {
-        OAuthTokenResponse::Google(token_response) => {
-            fetch_user_info(
-                &provider,
-                &token_response.access_token,
-                Some(&token_response.id_token),
-            )
-            .await?
-        }
-        OAuthTokenResponse::GitHub(token_response) => {
-            fetch_user_info(&provider, &token_response.access_token, None).await?
-        }
-        OAuthTokenResponse::Naver(token_response) => {
-            fetch_user_info(&provider, &token_response.access_token, None).await?
-        }
-        OAuthTokenResponse::Kakao(token_response) => {
-            fetch_user_info(&provider, &token_response.access_token, None).await?
+
+    #[actix_web::test]
+    async fn linking_an_account_of_another_user_is_a_conflict() {
+        let data = AppState::for_tests();
+        for id in ["user-1", "user-2"] {
+            data.db
+                .insert(User::for_tests(id, &format!("{}@example.com", id)))
+                .await
+                .unwrap();
         }
-    };
+        let info = user_info("g-1", "alice@gmail.example");
 
-    let mut vec = data.db.lock().await;
-
-    let user_id = find_or_create_user(user_info, &mut vec).await;
-
-    let now = Utc::now();
-    let token = match encode(
-        &Header::default(),
-        &TokenClaims {
-            sub: user_id,
-            exp: (now + Duration::minutes(data.env.jwt_max_age)).timestamp() as usize,
-            iat: now.timestamp() as usize,
-        },
-        &EncodingKey::from_secret(data.env.jwt_secret.as_ref()),
-    ) {
-        Ok(t) => t,
-        Err(_) => return Ok(HttpResponse::InternalServerError().finish()),
-    };
+        link_identity("google", "user-1", &info, &data)
+            .await
+            .unwrap();
+        // Linking the same account again is a no-op
+        link_identity("google", "user-1", &info, &data)
+            .await
+            .unwrap();
 
-    let cookie = Cookie::build("token", token)
-        .domain("localhost")
-        .same_site(actix_web::cookie::SameSite::Lax) // Secure, Lax, None
-        .secure(true) // Make sure the cookie is secure, https
-        .path("/")
-        .max_age(ActixWebDuration::new(60, 0))
-        // .max_age(ActixWebDuration::new(60 * data.env.jwt_max_age, 0))
-        .http_only(true)
-        .finish();
+        let err = link_identity("google", "user-2", &info, &data)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "This Google account is already linked to another user"
+        );
+    }
 
-    Ok(HttpResponse::Found()
-        .append_header((LOCATION, format!("{}{}", data.env.client_origin, state)))
-        .cookie(cookie)
-        .finish())
+    #[actix_web::test]
+    async fn email_merges_need_both_sides_to_vouch_for_the_address() {
+        let data = AppState::for_tests();
+        let password_account = User {
+            password: "hash".to_string(),
+            ..User::for_tests("user-1", "alice@example.com")
+        };
+        let unverified_account = User {
+            verified: false,
+            ..User::for_tests("user-2", "bob@example.com")
+        };
+        data.db.insert(password_account).await.unwrap();
+        data.db.insert(unverified_account).await.unwrap();
+        data.db
+            .insert(User::for_tests("user-3", "carol@example.com"))
+            .await
+            .unwrap();
+        let unverified_login = UserInfo {
+            email_verified: false,
+            ..user_info("g-3", "carol@example.com")
+        };
+
+        for (subject, email) in [("g-1", "alice@example.com"), ("g-2", "bob@example.com")] {
+            let err = resolve_user("google", user_info(subject, email), &data)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, RepositoryError::Conflict(_)), "{:?}", err);
+        }
+        let err = resolve_user("google", unverified_login, &data)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "An account with this email already exists, log in to it and link Google from there"
+        );
+        assert!(data
+            .identities
+            .list_for_user("user-1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[actix_web::test]
+    async fn new_accounts_are_only_verified_when_the_provider_says_so() {
+        let data = AppState::for_tests();
+        let unverified_login = UserInfo {
+            email_verified: false,
+            ..user_info("gh-1", "dave@example.com")
+        };
+
+        let user = resolve_user("github", unverified_login, &data)
+            .await
+            .unwrap();
+        assert!(!user.verified);
+        let user = resolve_user("google", user_info("g-1", "erin@example.com"), &data)
+            .await
+            .unwrap();
+        assert!(user.verified);
+    }
 }
@@ -0,0 +1,272 @@
+use crate::{
+    auth::{
+        login_throttle,
+        one_time_token::{self, PASSWORD_RESET},
+        password::hash_password,
+        token,
+    },
+    mail::Email,
+    models::{AppState, ForgotPasswordSchema, ResetPasswordSchema, User},
+    repository::RepositoryError,
+};
+use actix_web::{
+    error::{ErrorBadRequest, ErrorInternalServerError},
+    post, web, HttpResponse, Responder, Result as ActixResult,
+};
+use serde_json::json;
+
+async fn send_reset_email(user: User, data: &AppState) -> ActixResult<()> {
+    let user_id = user.id.clone().ok_or(RepositoryError::NotFound)?;
+
+    // Only the latest link works
+    data.one_time_tokens
+        .revoke_user(&user_id, PASSWORD_RESET)
+        .await?;
+    let token = one_time_token::issue(
+        PASSWORD_RESET,
+        Some(&user_id),
+        &user.email,
+        data.env.password_reset_max_age,
+        data,
+    )
+    .await?;
+
+    data.mailer
+        .send(Email {
+            to: user.email.clone(),
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Hi {},\n\nSomeone asked to reset the password of your account. If it was you, choose a new one here:\n\n{}/reset-password?token={}\n\nThe link expires in {} minutes and works once. If it wasn't you, you can ignore this email.\n",
+                user.name, data.env.client_origin, token, data.env.password_reset_max_age
+            ),
+        })
+        .await?;
+    Ok(())
+}
+
+#[post("/auth/forgot-password")]
+async fn forgot_password_handler(
+    body: web::Json<ForgotPasswordSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let user = data.db.find_by_email(&body.email.to_lowercase()).await?;
+
+    // Accounts without a password sign in through their provider
+    if let Some(user) = user.filter(|user| !user.password.is_empty()) {
+        // Mail in the background so the response takes as long whether the account exists or not
+        let data = data.clone();
+        actix_web::rt::spawn(async move {
+            if let Err(e) = send_reset_email(user, &data).await {
+                log::warn!("Failed to send password reset email: {}", e);
+            }
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "If an account uses this email, a link to reset its password is on its way"
+    })))
+}
+
+#[post("/auth/reset-password")]
+async fn reset_password_handler(
+    body: web::Json<ResetPasswordSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let body = body.into_inner();
+    let invalid_token =
+        || ErrorBadRequest(json!({"status": "fail", "message": "Invalid or expired reset token"}));
+
+    let stored = one_time_token::redeem(&body.token, PASSWORD_RESET, &data)
+        .await?
+        .ok_or_else(invalid_token)?;
+    let user_id = stored.user_id.ok_or_else(invalid_token)?;
+
+    let user = data
+        .db
+        .find_by_id(&user_id)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    // The link was sent to an address the account no longer uses
+    if user.email != stored.email {
+        return Err(invalid_token());
+    }
+
+    let params = data.env.argon2_params.clone();
+    let password_hash = web::block(move || hash_password(&body.password, params))
+        .await?
+        .map_err(|_| ErrorInternalServerError("Failed to hash password"))?;
+
+    // Column updates only, anything else changed meanwhile stays. A password changed since
+    // the user was read wins over the reset.
+    if !data
+        .db
+        .replace_password(&user_id, &user.password, &password_hash)
+        .await?
+    {
+        return Err(invalid_token());
+    }
+    // Receiving the link proves the address works
+    data.db.mark_verified(&user_id, &stored.email).await?;
+
+    // Whoever knew the old password may still hold a session
+    token::revoke_all_sessions(&user_id, &data).await?;
+    data.one_time_tokens
+        .revoke_user(&user_id, PASSWORD_RESET)
+        .await?;
+    // The owner just proved who they are, a lockout from guessing must not keep them out
+    login_throttle::unlock_account(&user.email, &data).await?;
+
+    log::info!("User {} reset their password", user_id);
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "Password updated, log in with the new one"
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+    use std::{sync::Arc, time::Duration};
+
+    use super::*;
+    use crate::{handlers::auth_handler::config, mail::FileMailer};
+
+    async fn app_state() -> (web::Data<AppState>, Arc<FileMailer>) {
+        let mailer = Arc::new(FileMailer::temporary());
+        let data = AppState {
+            mailer: mailer.clone(),
+            ..AppState::for_tests()
+        };
+        let password = hash_password("old password", data.env.argon2_params.clone()).unwrap();
+        let user = User {
+            password,
+            verified: false,
+            ..User::for_tests("user-1", "alice@example.com")
+        };
+        data.db.insert(user).await.unwrap();
+        (web::Data::new(data), mailer)
+    }
+
+    // The reset email goes out in the background
+    async fn wait_for_mail(mailer: &FileMailer, count: usize) -> Vec<String> {
+        for _ in 0..100 {
+            let sent = mailer.sent();
+            if sent.len() >= count {
+                return sent;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("expected {} emails", count);
+    }
+
+    fn link_token(mail: &str) -> String {
+        let (_, rest) = mail.split_once("token=").expect("no link in the email");
+        rest.split_whitespace().next().unwrap().to_string()
+    }
+
+    fn post(uri: &str, body: Value) -> test::TestRequest {
+        test::TestRequest::post().uri(uri).set_json(body)
+    }
+
+    #[actix_web::test]
+    async fn the_emailed_link_sets_a_new_password_once() {
+        let (data, mailer) = app_state().await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+        let user = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        let session = token::issue_session(&user, None, &data).await.unwrap();
+
+        let req = post(
+            "/api/auth/forgot-password",
+            json!({"email": "Alice@example.com"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let token = link_token(&wait_for_mail(&mailer, 1).await[0]);
+
+        let reset = || {
+            post(
+                "/api/auth/reset-password",
+                json!({"token": token, "password": "new password"}),
+            )
+            .to_request()
+        };
+        let res = test::call_service(&app, reset()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, reset()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let stored = data.db.find_by_id("user-1").await.unwrap().unwrap();
+        assert!(stored.verified);
+        for (password, status) in [
+            ("old password", StatusCode::BAD_REQUEST),
+            ("new password", StatusCode::OK),
+        ] {
+            let req = post(
+                "/api/auth/login",
+                json!({"email": "alice@example.com", "password": password}),
+            );
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status, "{}", password);
+        }
+
+        // Sessions from before the reset are gone
+        let req = post(
+            "/api/auth/refresh",
+            json!({"refresh_token": session.refresh_token}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn only_the_latest_link_works() {
+        let (data, mailer) = app_state().await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        for count in 1..=2 {
+            let req = post(
+                "/api/auth/forgot-password",
+                json!({"email": "alice@example.com"}),
+            );
+            test::call_service(&app, req.to_request()).await;
+            wait_for_mail(&mailer, count).await;
+        }
+        let sent = mailer.sent();
+
+        for (mail, status) in [
+            (&sent[0], StatusCode::BAD_REQUEST),
+            (&sent[1], StatusCode::OK),
+        ] {
+            let req = post(
+                "/api/auth/reset-password",
+                json!({"token": link_token(mail), "password": "new password"}),
+            );
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status);
+        }
+    }
+
+    #[actix_web::test]
+    async fn unknown_addresses_get_the_same_answer_and_no_mail() {
+        let (data, mailer) = app_state().await;
+        let app = test::init_service(App::new().app_data(data.clone()).configure(config)).await;
+
+        let req = post(
+            "/api/auth/forgot-password",
+            json!({"email": "nobody@example.com"}),
+        );
+        let res = test::call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(
+            body["message"],
+            "If an account uses this email, a link to reset its password is on its way"
+        );
+
+        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+        assert!(mailer.sent().is_empty());
+    }
+}
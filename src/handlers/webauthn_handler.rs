@@ -0,0 +1,677 @@
+use crate::{
+    auth::{
+        one_time_token, token,
+        token_guard::AuthenticationGuard,
+        webauthn::{self, AuthenticationCeremony},
+    },
+    handlers::mfa_handler::{mfa_session_response, pending_claims},
+    models::{
+        AppState, MfaTokenSchema, WebauthnAssertionSchema, WebauthnCredential, WebauthnLoginSchema,
+        WebauthnRegisterSchema,
+    },
+    repository::RepositoryError,
+    responses::{
+        FilteredWebauthnCredential, WebauthnCredentialListData, WebauthnCredentialListResponse,
+    },
+};
+use actix_web::{
+    delete,
+    error::{ErrorBadRequest, ErrorInternalServerError, ErrorUnauthorized},
+    get, post, web, Error as ActixWebError, HttpRequest, HttpResponse, Responder,
+    Result as ActixResult,
+};
+use chrono::prelude::*;
+use serde_json::json;
+use uuid::Uuid;
+use webauthn_rs::prelude::{PasskeyRegistration, PublicKeyCredential, WebauthnError};
+
+fn bad_request(message: &str) -> ActixWebError {
+    ErrorBadRequest(json!({"status": "fail", "message": message}))
+}
+
+fn verification_failed() -> ActixWebError {
+    ErrorUnauthorized(json!({"status": "fail", "message": "Passkey verification failed"}))
+}
+
+fn ceremony_expired() -> ActixWebError {
+    bad_request("The passkey request expired, start again")
+}
+
+fn credential_list_response(credentials: &[WebauthnCredential]) -> HttpResponse {
+    HttpResponse::Ok().json(WebauthnCredentialListResponse {
+        status: "success".to_string(),
+        data: WebauthnCredentialListData {
+            credentials: credentials.iter().map(credential_to_response).collect(),
+        },
+    })
+}
+
+fn credential_to_response(credential: &WebauthnCredential) -> FilteredWebauthnCredential {
+    FilteredWebauthnCredential {
+        id: credential.credential_id.to_owned(),
+        name: credential.name.to_owned(),
+        createdAt: credential.created_at,
+        lastUsedAt: credential.last_used_at,
+    }
+}
+
+fn challenge_response(ceremony: String, options: impl serde::Serialize) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "status": "success",
+        "data": {"ceremony": ceremony, "options": options}
+    }))
+}
+
+/// Checks an assertion against the stored credential it names, which has to belong to
+/// `user_id` when the ceremony was started for a known user.
+async fn verify_assertion(
+    credential: &PublicKeyCredential,
+    ceremony: AuthenticationCeremony,
+    user_id: Option<&str>,
+    data: &AppState,
+) -> ActixResult<WebauthnCredential> {
+    let stored = data
+        .webauthn_credentials
+        .find(&webauthn::credential_id(credential.get_credential_id()))
+        .await?
+        .filter(|stored| user_id.is_none_or(|user_id| stored.user_id == user_id))
+        .ok_or_else(verification_failed)?;
+    let mut passkey = webauthn::passkey(&stored).ok_or_else(verification_failed)?;
+
+    let result = match ceremony {
+        AuthenticationCeremony::Passkey(state) => data
+            .webauthn
+            .finish_passkey_authentication(credential, &state),
+        AuthenticationCeremony::Discoverable(state) => data
+            .webauthn
+            .identify_discoverable_authentication(credential)
+            .and_then(|(user_handle, _)| {
+                // The authenticator names the user the passkey was created for
+                if user_handle.to_string() != stored.user_id {
+                    return Err(WebauthnError::InvalidUserUniqueId);
+                }
+                data.webauthn.finish_discoverable_authentication(
+                    credential,
+                    state,
+                    &[(&passkey).into()],
+                )
+            }),
+    }
+    .map_err(|e| {
+        log::info!("Passkey {} was rejected: {}", stored.credential_id, e);
+        verification_failed()
+    })?;
+
+    // The new signature counter lets the next login spot a cloned authenticator
+    passkey.update_credential(&result);
+    let passkey = serde_json::to_string(&passkey)
+        .map_err(|_| ErrorInternalServerError("Failed to store passkey"))?;
+    data.webauthn_credentials
+        .mark_used(&stored.credential_id, &passkey, Utc::now())
+        .await?;
+
+    Ok(stored)
+}
+
+#[post("/auth/webauthn/register/start")]
+async fn register_start_handler(
+    auth_guard: AuthenticationGuard,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let user = data
+        .db
+        .find_by_id(&auth_guard.user_id)
+        .await?
+        .ok_or(RepositoryError::NotFound)?;
+    let user_handle = Uuid::parse_str(&auth_guard.user_id)
+        .map_err(|_| ErrorInternalServerError("Failed to start passkey registration"))?;
+
+    // Stops the same authenticator from being registered twice
+    let registered = data
+        .webauthn_credentials
+        .list_for_user(&auth_guard.user_id)
+        .await?
+        .iter()
+        .filter_map(webauthn::passkey)
+        .map(|passkey| passkey.cred_id().clone())
+        .collect();
+
+    let (options, state) = data
+        .webauthn
+        .start_passkey_registration(user_handle, &user.email, &user.name, Some(registered))
+        .map_err(|_| ErrorInternalServerError("Failed to start passkey registration"))?;
+    let ceremony = webauthn::save_ceremony(
+        one_time_token::WEBAUTHN_REGISTRATION,
+        Some(&auth_guard.user_id),
+        &state,
+        &data,
+    )
+    .await?;
+
+    Ok(challenge_response(ceremony, options))
+}
+
+#[post("/auth/webauthn/register/finish")]
+async fn register_finish_handler(
+    auth_guard: AuthenticationGuard,
+    body: web::Json<WebauthnRegisterSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let state = match webauthn::take_ceremony::<PasskeyRegistration>(
+        &body.ceremony,
+        one_time_token::WEBAUTHN_REGISTRATION,
+        &data,
+    )
+    .await?
+    {
+        Some((Some(user_id), state)) if user_id == auth_guard.user_id => state,
+        _ => return Err(ceremony_expired()),
+    };
+
+    let passkey = data
+        .webauthn
+        .finish_passkey_registration(&body.credential, &state)
+        .map_err(|e| {
+            log::info!("Passkey registration was rejected: {}", e);
+            bad_request("Passkey registration failed")
+        })?;
+
+    let name = body
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or("Passkey");
+    data.webauthn_credentials
+        .insert(WebauthnCredential {
+            credential_id: webauthn::credential_id(passkey.cred_id()),
+            user_id: auth_guard.user_id.clone(),
+            name: name.to_string(),
+            passkey: serde_json::to_string(&passkey)
+                .map_err(|_| ErrorInternalServerError("Failed to store passkey"))?,
+            created_at: Utc::now(),
+            last_used_at: None,
+        })
+        .await?;
+
+    log::info!("User {} registered a passkey", auth_guard.user_id);
+
+    let credentials = data
+        .webauthn_credentials
+        .list_for_user(&auth_guard.user_id)
+        .await?;
+    Ok(credential_list_response(&credentials))
+}
+
+#[get("/auth/webauthn/credentials")]
+async fn list_credentials_handler(
+    auth_guard: AuthenticationGuard,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let credentials = data
+        .webauthn_credentials
+        .list_for_user(&auth_guard.user_id)
+        .await?;
+    Ok(credential_list_response(&credentials))
+}
+
+#[delete("/auth/webauthn/credentials/{id}")]
+async fn delete_credential_handler(
+    auth_guard: AuthenticationGuard,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let credential_id = path.into_inner();
+    data.webauthn_credentials
+        .delete(&auth_guard.user_id, &credential_id)
+        .await?;
+
+    log::info!("User {} removed a passkey", auth_guard.user_id);
+
+    let credentials = data
+        .webauthn_credentials
+        .list_for_user(&auth_guard.user_id)
+        .await?;
+    Ok(credential_list_response(&credentials))
+}
+
+#[post("/auth/webauthn/login/start")]
+async fn login_start_handler(
+    body: Option<web::Json<WebauthnLoginSchema>>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let email = body
+        .and_then(|body| body.into_inner().email)
+        .map(|email| email.trim().to_lowercase());
+    let user = match &email {
+        Some(email) => data.db.find_by_email(email).await?,
+        None => None,
+    };
+
+    let passkeys: Vec<_> = match &user {
+        Some(user) => data
+            .webauthn_credentials
+            .list_for_user(user.id.as_deref().unwrap_or_default())
+            .await?
+            .iter()
+            .filter_map(webauthn::passkey)
+            .collect(),
+        None => Vec::new(),
+    };
+
+    if !passkeys.is_empty() {
+        let (options, state) = data
+            .webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|_| ErrorInternalServerError("Failed to start passkey sign in"))?;
+        let ceremony = webauthn::save_ceremony(
+            one_time_token::WEBAUTHN_LOGIN,
+            user.and_then(|user| user.id).as_deref(),
+            &AuthenticationCeremony::Passkey(state),
+            &data,
+        )
+        .await?;
+        return Ok(challenge_response(ceremony, options));
+    }
+
+    let (options, state) = data
+        .webauthn
+        .start_discoverable_authentication()
+        .map_err(|_| ErrorInternalServerError("Failed to start passkey sign in"))?;
+    let ceremony = webauthn::save_ceremony(
+        one_time_token::WEBAUTHN_LOGIN,
+        None,
+        &AuthenticationCeremony::Discoverable(state),
+        &data,
+    )
+    .await?;
+
+    // Unknown emails and accounts without passkeys look like any other account
+    match email {
+        Some(email) => {
+            let options = webauthn::decoy_options(options, &email, &data.env)
+                .map_err(|_| ErrorInternalServerError("Failed to start passkey sign in"))?;
+            Ok(challenge_response(ceremony, options))
+        }
+        None => Ok(challenge_response(ceremony, options)),
+    }
+}
+
+#[post("/auth/webauthn/login/finish")]
+async fn login_finish_handler(
+    body: web::Json<WebauthnAssertionSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let (user_id, ceremony) = webauthn::take_ceremony::<AuthenticationCeremony>(
+        &body.ceremony,
+        one_time_token::WEBAUTHN_LOGIN,
+        &data,
+    )
+    .await?
+    .ok_or_else(ceremony_expired)?;
+
+    let credential =
+        verify_assertion(&body.credential, ceremony, user_id.as_deref(), &data).await?;
+
+    let user = data
+        .db
+        .find_by_id(&credential.user_id)
+        .await?
+        .ok_or_else(verification_failed)?;
+    if user.suspended {
+        return Ok(HttpResponse::Forbidden()
+            .json(json!({"status": "fail", "message": "Your account has been suspended"})));
+    }
+
+    // Passkeys require user verification (PIN or biometrics), so they count as both factors
+    let session = token::issue_session(&user, None, &data).await?;
+    let [access_cookie, refresh_cookie] = token::session_cookies(&session, &data);
+
+    Ok(HttpResponse::Ok()
+        .cookie(access_cookie)
+        .cookie(refresh_cookie)
+        .json(json!({
+            "status": "success",
+            "token": session.access_token,
+            "refresh_token": session.refresh_token
+        })))
+}
+
+#[post("/auth/webauthn/mfa/start")]
+async fn mfa_start_handler(
+    req: HttpRequest,
+    body: Option<web::Json<MfaTokenSchema>>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let mfa_token = body.and_then(|body| body.into_inner().mfa_token);
+    let claims = pending_claims(&req, mfa_token, &data)?;
+
+    let passkeys: Vec<_> = data
+        .webauthn_credentials
+        .list_for_user(&claims.sub)
+        .await?
+        .iter()
+        .filter_map(webauthn::passkey)
+        .collect();
+    if passkeys.is_empty() {
+        return Err(bad_request("No passkey registered"));
+    }
+
+    let (options, state) = data
+        .webauthn
+        .start_passkey_authentication(&passkeys)
+        .map_err(|_| ErrorInternalServerError("Failed to start passkey sign in"))?;
+    let ceremony = webauthn::save_ceremony(
+        one_time_token::WEBAUTHN_MFA,
+        Some(&claims.sub),
+        &AuthenticationCeremony::Passkey(state),
+        &data,
+    )
+    .await?;
+
+    Ok(challenge_response(ceremony, options))
+}
+
+#[post("/auth/webauthn/mfa/finish")]
+async fn mfa_finish_handler(
+    req: HttpRequest,
+    body: web::Json<WebauthnAssertionSchema>,
+    data: web::Data<AppState>,
+) -> ActixResult<impl Responder> {
+    let body = body.into_inner();
+    let claims = pending_claims(&req, body.mfa_token, &data)?;
+
+    let ceremony = match webauthn::take_ceremony::<AuthenticationCeremony>(
+        &body.ceremony,
+        one_time_token::WEBAUTHN_MFA,
+        &data,
+    )
+    .await?
+    {
+        Some((Some(user_id), ceremony)) if user_id == claims.sub => ceremony,
+        _ => return Err(ceremony_expired()),
+    };
+
+    verify_assertion(&body.credential, ceremony, Some(&claims.sub), &data).await?;
+
+    let user = data
+        .db
+        .find_by_id(&claims.sub)
+        .await?
+        .ok_or_else(verification_failed)?;
+    if user.suspended {
+        return Ok(HttpResponse::Forbidden()
+            .json(json!({"status": "fail", "message": "Your account has been suspended"})));
+    }
+
+    mfa_session_response(&user, &data).await
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        body::MessageBody,
+        dev::{Service, ServiceResponse},
+        http::{header, StatusCode},
+        test, App,
+    };
+    use serde_json::Value;
+    use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+    use webauthn_rs::prelude::{CreationChallengeResponse, RequestChallengeResponse, Url};
+
+    use super::*;
+    use crate::{auth::mfa, handlers::auth_handler, models::User};
+
+    const ORIGIN: &str = "https://localhost:3000";
+
+    fn app_state() -> web::Data<AppState> {
+        web::Data::new(AppState::for_tests())
+    }
+
+    async fn insert_user(email: &str, data: &AppState) -> User {
+        let id = Uuid::new_v4().to_string();
+        data.db
+            .insert(User {
+                provider: "email".to_string(),
+                ..User::for_tests(&id, email)
+            })
+            .await
+            .unwrap()
+    }
+
+    async fn post<S, B>(
+        app: &S,
+        path: &str,
+        access_token: Option<&str>,
+        body: Value,
+    ) -> (StatusCode, Value)
+    where
+        S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = ActixWebError>,
+        B: MessageBody,
+    {
+        let mut req = test::TestRequest::post().uri(path).set_json(body);
+        if let Some(access_token) = access_token {
+            req = req.insert_header((header::AUTHORIZATION, format!("Bearer {}", access_token)));
+        }
+        let res = test::call_service(app, req.to_request()).await;
+        let status = res.status();
+        (status, test::read_body_json(res).await)
+    }
+
+    /// Registers a passkey of `authenticator` for the user signed in with `access_token`.
+    async fn register<S, B>(
+        app: &S,
+        authenticator: &mut WebauthnAuthenticator<SoftPasskey>,
+        access_token: &str,
+    ) where
+        S: Service<actix_http::Request, Response = ServiceResponse<B>, Error = ActixWebError>,
+        B: MessageBody,
+    {
+        let (status, start) = post(
+            app,
+            "/api/auth/webauthn/register/start",
+            Some(access_token),
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", start);
+        let options: CreationChallengeResponse =
+            serde_json::from_value(start["data"]["options"].clone()).unwrap();
+        let credential = authenticator
+            .do_registration(Url::parse(ORIGIN).unwrap(), options)
+            .unwrap();
+
+        let (status, finish) = post(
+            app,
+            "/api/auth/webauthn/register/finish",
+            Some(access_token),
+            json!({
+                "ceremony": start["data"]["ceremony"],
+                "credential": credential,
+                "name": "Laptop"
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", finish);
+        assert_eq!(finish["data"]["credentials"][0]["name"], "Laptop");
+    }
+
+    #[actix_web::test]
+    async fn passkey_registers_and_signs_in() {
+        let data = app_state();
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .configure(auth_handler::config),
+        )
+        .await;
+        let user = insert_user("passkey@example.com", &data).await;
+        let session = token::issue_session(&user, None, &data).await.unwrap();
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+        register(&app, &mut authenticator, &session.access_token).await;
+
+        let (status, start) = post(
+            &app,
+            "/api/auth/webauthn/login/start",
+            None,
+            json!({"email": "Passkey@Example.com"}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", start);
+        let options: RequestChallengeResponse =
+            serde_json::from_value(start["data"]["options"].clone()).unwrap();
+        let credential = authenticator
+            .do_authentication(Url::parse(ORIGIN).unwrap(), options)
+            .unwrap();
+
+        let assertion = json!({"ceremony": start["data"]["ceremony"], "credential": credential});
+        let (status, finish) = post(
+            &app,
+            "/api/auth/webauthn/login/finish",
+            None,
+            assertion.clone(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", finish);
+        assert!(finish["token"].is_string());
+
+        // The ceremony is single use
+        let (status, _) = post(&app, "/api/auth/webauthn/login/finish", None, assertion).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn login_start_looks_the_same_for_unknown_emails() {
+        let data = app_state();
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .configure(auth_handler::config),
+        )
+        .await;
+        let user = insert_user("known@example.com", &data).await;
+        let session = token::issue_session(&user, None, &data).await.unwrap();
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register(&app, &mut authenticator, &session.access_token).await;
+        insert_user("nopasskey@example.com", &data).await;
+
+        let options = |response: Value| response["data"]["options"].clone();
+        let start = |email: &'static str| {
+            post(
+                &app,
+                "/api/auth/webauthn/login/start",
+                None,
+                json!({"email": email}),
+            )
+        };
+        let known = options(start("known@example.com").await.1);
+        let unknown = options(start("unknown@example.com").await.1);
+        let without_passkey = options(start("nopasskey@example.com").await.1);
+
+        let keys = |options: &Value| {
+            let mut keys: Vec<_> = options["publicKey"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect();
+            keys.sort();
+            keys
+        };
+        for decoy in [&unknown, &without_passkey] {
+            assert_eq!(keys(decoy), keys(&known));
+            assert_eq!(decoy.get("mediation"), known.get("mediation"));
+            let allowed = decoy["publicKey"]["allowCredentials"].as_array().unwrap();
+            assert_eq!(allowed.len(), 1);
+        }
+
+        // Asking again shows the same made up credential
+        let again = options(start("unknown@example.com").await.1);
+        assert_eq!(
+            again["publicKey"]["allowCredentials"],
+            unknown["publicKey"]["allowCredentials"]
+        );
+        assert_ne!(
+            unknown["publicKey"]["allowCredentials"],
+            without_passkey["publicKey"]["allowCredentials"]
+        );
+    }
+
+    #[actix_web::test]
+    async fn passkey_completes_the_second_factor() {
+        let data = app_state();
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .configure(auth_handler::config),
+        )
+        .await;
+        let user = insert_user("mfa@example.com", &data).await;
+        let user_id = user.id.clone().unwrap();
+        let session = token::issue_session(&user, None, &data).await.unwrap();
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+        register(&app, &mut authenticator, &session.access_token).await;
+
+        // What a password login answers with once 2FA is on
+        let mfa_token = mfa::create_pending_token(&user_id, &data).unwrap();
+
+        let (status, start) = post(
+            &app,
+            "/api/auth/webauthn/mfa/start",
+            None,
+            json!({"mfa_token": mfa_token}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", start);
+        let options: RequestChallengeResponse =
+            serde_json::from_value(start["data"]["options"].clone()).unwrap();
+        let credential = authenticator
+            .do_authentication(Url::parse(ORIGIN).unwrap(), options)
+            .unwrap();
+
+        // The ceremony belongs to the pending login it was started for
+        let other = insert_user("other@example.com", &data).await;
+        let other_token = mfa::create_pending_token(other.id.as_deref().unwrap(), &data).unwrap();
+        let (status, _) = post(
+            &app,
+            "/api/auth/webauthn/mfa/finish",
+            None,
+            json!({
+                "ceremony": start["data"]["ceremony"],
+                "credential": credential,
+                "mfa_token": other_token
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, start) = post(
+            &app,
+            "/api/auth/webauthn/mfa/start",
+            None,
+            json!({"mfa_token": mfa_token}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", start);
+        let options: RequestChallengeResponse =
+            serde_json::from_value(start["data"]["options"].clone()).unwrap();
+        let credential = authenticator
+            .do_authentication(Url::parse(ORIGIN).unwrap(), options)
+            .unwrap();
+
+        let (status, finish) = post(
+            &app,
+            "/api/auth/webauthn/mfa/finish",
+            None,
+            json!({
+                "ceremony": start["data"]["ceremony"],
+                "credential": credential,
+                "mfa_token": mfa_token
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", finish);
+        assert!(finish["token"].is_string());
+    }
+}
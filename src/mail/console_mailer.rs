@@ -0,0 +1,19 @@
+use async_trait::async_trait;
+
+use super::{Email, MailError, Mailer};
+
+/// Logs emails instead of sending them, for development.
+pub struct ConsoleMailer;
+
+#[async_trait]
+impl Mailer for ConsoleMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        log::info!(
+            "Email to {}\nSubject: {}\n\n{}",
+            email.to,
+            email.subject,
+            email.body
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use uuid::Uuid;
+
+use super::{mailer::build_message, Email, MailError, Mailer};
+
+/// Drops each email as an `.eml` file into a directory, for development and tests.
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+    // Keeps emails sent in the same millisecond in order
+    sequence: AtomicU64,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str, from: &str) -> Result<Self, MailError> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| MailError::from(format!("can't create {}: {}", dir, e)))?;
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            from: from.to_string(),
+            sequence: AtomicU64::new(0),
+        })
+    }
+}
+
+#[cfg(test)]
+impl FileMailer {
+    /// A mailer writing to a fresh directory under the system temp dir.
+    pub fn temporary() -> Self {
+        let dir = std::env::temp_dir().join(format!("blog-rs-mail-{}", Uuid::new_v4()));
+        Self::new(dir.to_str().unwrap(), "blog-rs <no-reply@localhost>").unwrap()
+    }
+
+    /// The messages written so far, oldest first, with the quoted-printable line wrapping
+    /// of long lines (such as links) undone.
+    pub fn sent(&self) -> Vec<String> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&self.dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| {
+                std::fs::read_to_string(path)
+                    .unwrap()
+                    .replace("=\r\n", "")
+                    .replace("=3D", "=")
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), MailError> {
+        let message = build_message(&self.from, &email)?;
+        // Sortable by time, unique across mailers writing to the same directory
+        let path = self.dir.join(format!(
+            "{}-{:06}-{}.eml",
+            Utc::now().format("%Y%m%dT%H%M%S%3f"),
+            self.sequence.fetch_add(1, Ordering::Relaxed),
+            Uuid::new_v4()
+        ));
+
+        tokio::fs::write(&path, message.formatted())
+            .await
+            .map_err(|e| MailError::from(format!("can't write {}: {}", path.display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn each_email_is_a_separate_message_file() {
+        let dir = std::env::temp_dir().join(format!("mail-{}", Uuid::new_v4()));
+        let mailer =
+            FileMailer::new(dir.to_str().unwrap(), "blog-rs <no-reply@localhost>").unwrap();
+
+        for subject in ["First", "Second"] {
+            mailer
+                .send(Email {
+                    to: "alice@example.com".to_string(),
+                    subject: subject.to_string(),
+                    body: "Hello".to_string(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let sent = mailer.sent();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].contains("Subject: First\r\n"), "{}", sent[0]);
+        assert!(sent[1].contains("To: alice@example.com\r\n"), "{}", sent[1]);
+        assert!(sent[1].contains("From: blog-rs <no-reply@localhost>\r\n"));
+    }
+
+    #[actix_web::test]
+    async fn invalid_recipients_are_refused() {
+        let mailer = FileMailer::temporary();
+
+        let err = mailer
+            .send(Email {
+                to: "not an address".to_string(),
+                subject: "Hi".to_string(),
+                body: "Hello".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().starts_with("Mail error: invalid recipient"),
+            "{}",
+            err
+        );
+        assert!(mailer.sent().is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use async_trait::async_trait;
+use std::fmt;
+
+/// A plain text email.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails, see `MAILER` in `.env sample` for the available transports.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), MailError>;
+}
+
+#[derive(Debug)]
+pub struct MailError(String);
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Mail error: {}", self.0)
+    }
+}
+
+impl std::error::Error for MailError {}
+
+impl From<&str> for MailError {
+    fn from(message: &str) -> Self {
+        MailError(message.to_string())
+    }
+}
+
+impl From<String> for MailError {
+    fn from(message: String) -> Self {
+        MailError(message)
+    }
+}
+
+impl ResponseError for MailError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // The transport's error may name the SMTP host or credentials
+        log::error!("{}", self);
+        HttpResponse::InternalServerError()
+            .json(serde_json::json!({"status": "fail", "message": "Failed to send email"}))
+    }
+}
+
+/// Builds the MIME message shared by the transports that need one.
+pub fn build_message(from: &str, email: &Email) -> Result<lettre::Message, MailError> {
+    let from = from
+        .parse()
+        .map_err(|e| MailError::from(format!("invalid MAIL_FROM: {}", e)))?;
+    let to = email
+        .to
+        .parse()
+        .map_err(|e| MailError::from(format!("invalid recipient: {}", e)))?;
+
+    lettre::Message::builder()
+        .from(from)
+        .to(to)
+        .subject(email.subject.as_str())
+        .header(lettre::message::header::ContentType::TEXT_PLAIN)
+        .body(email.body.clone())
+        .map_err(|e| MailError::from(e.to_string()))
+}
@@ -0,0 +1,48 @@
+pub mod console_mailer;
+pub mod file_mailer;
+pub mod mailer;
+pub mod smtp_mailer;
+
+// Re-export for easier use
+pub use console_mailer::ConsoleMailer;
+pub use file_mailer::FileMailer;
+pub use mailer::{Email, MailError, Mailer};
+pub use smtp_mailer::SmtpMailer;
+
+use std::sync::Arc;
+
+use crate::config::env::{Config, MailerKind};
+
+/// Picks the mailer configured by `MAILER`.
+pub fn from_config(config: &Config) -> Result<Arc<dyn Mailer>, MailError> {
+    Ok(match config.mailer {
+        MailerKind::Console => Arc::new(ConsoleMailer),
+        MailerKind::File => Arc::new(FileMailer::new(&config.mail_dir, &config.mail_from)?),
+        MailerKind::Smtp => {
+            let smtp_url = config
+                .smtp_url
+                .as_deref()
+                .ok_or_else(|| MailError::from("SMTP_URL must be set when MAILER=smtp"))?;
+            Arc::new(SmtpMailer::new(smtp_url, &config.mail_from)?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smtp_needs_a_url() {
+        let config = Config {
+            mailer: MailerKind::Smtp,
+            ..Config::for_tests()
+        };
+
+        let err = from_config(&config).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Mail error: SMTP_URL must be set when MAILER=smtp"
+        );
+    }
+}
@@ -2,7 +2,10 @@ mod config;
 
 mod auth;
 mod handlers;
+mod mail;
+mod middleware;
 mod models;
+mod repository;
 
 mod responses;
 
@@ -11,7 +14,7 @@ use actix_web::middleware::Logger;
 use actix_web::{http::header, web, App, HttpServer};
 use dotenv::dotenv;
 use models::AppState;
-use std::{fs::File, io, io::BufReader};
+use std::{fs::File, io, io::BufReader, time::Duration};
 
 use rustls::{Certificate, PrivateKey, ServerConfig};
 use rustls_pemfile::{certs, pkcs8_private_keys};
@@ -26,21 +29,43 @@ async fn main() -> std::io::Result<()> {
 
     let tls_config = load_rustls_config();
 
-    let db = AppState::init();
+    let db = AppState::init()
+        .await
+        .expect("Failed to initialize the user store");
     let app_data = web::Data::new(db);
+
+    // Revocation entries only matter until the tokens they name expire
+    let gc_data = app_data.clone();
+    actix_web::rt::spawn(async move {
+        let mut interval =
+            actix_web::rt::time::interval(Duration::from_secs(gc_data.env.token_gc_interval));
+        loop {
+            interval.tick().await;
+            auth::token::purge_expired_tokens(&gc_data).await;
+        }
+    });
     let public_dir = std::env::current_dir().unwrap().join("public");
+    // Shared by every worker
+    let rate_limit = middleware::RateLimit::new(app_data.env.rate_limits.clone());
 
     println!("🚀 Server started successfully");
 
     HttpServer::new(move || {
         let cors = Cors::default()
             .allowed_origin(&app_data.env.client_origin)
-            .allowed_methods(vec!["GET", "POST"])
+            .allowed_methods(vec!["GET", "POST", "PATCH", "DELETE"])
             .allowed_headers(vec![
                 header::CONTENT_TYPE,
                 header::AUTHORIZATION,
                 header::ACCEPT,
             ])
+            .expose_headers(vec![
+                "retry-after",
+                "ratelimit-limit",
+                "ratelimit-remaining",
+                "ratelimit-reset",
+                "ratelimit-policy",
+            ])
             .supports_credentials();
 
         // let cors = Cors::permissive();
@@ -48,6 +73,7 @@ async fn main() -> std::io::Result<()> {
             .app_data(app_data.clone())
             .service(actix_files::Files::new("/api/images", &public_dir))
             .configure(handlers::auth_handler::config)
+            .wrap(rate_limit.clone())
             .wrap(cors)
             .wrap(Logger::default())
     })
@@ -0,0 +1,414 @@
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    web, Error, HttpResponse,
+};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{
+    auth::{client_ip::client_ip, crypto::sha256_hex, token_guard::request_token},
+    config::env::{RateLimitConfig, RateLimitKey},
+    models::{AppState, TokenClaims},
+};
+
+const API_KEY_HEADER: &str = "x-api-key";
+
+// Full buckets are dropped once the map grows past this, they'd be recreated the same
+const MIN_SWEEP_SIZE: usize = 1024;
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+#[derive(Default)]
+struct Buckets {
+    // (group index, client key) -> bucket
+    buckets: HashMap<(usize, String), Bucket>,
+    next_sweep: usize,
+}
+
+/// The state of a bucket after a request, sent back as `RateLimit-*` headers.
+struct Quota {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    // Seconds until the bucket is full again
+    reset: u64,
+    // Seconds until the next request is allowed
+    retry_after: u64,
+    // Seconds it takes to refill the whole bucket
+    window: u64,
+}
+
+impl Quota {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        let values = [
+            ("ratelimit-limit", self.limit.to_string()),
+            ("ratelimit-remaining", self.remaining.to_string()),
+            ("ratelimit-reset", self.reset.to_string()),
+            (
+                "ratelimit-policy",
+                format!("{};w={}", self.limit, self.window),
+            ),
+        ];
+        for (name, value) in values {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(HeaderName::from_static(name), value);
+            }
+        }
+    }
+}
+
+/// Token buckets for the groups of `RATE_LIMITS`, in process memory, so each instance counts on its own.
+struct RateLimiter {
+    groups: Vec<RateLimitConfig>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    fn group(&self, path: &str) -> Option<usize> {
+        self.groups.iter().position(|group| {
+            group.paths.iter().any(|prefix| {
+                path.strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+        })
+    }
+
+    fn take(&self, group: usize, key: &str) -> Quota {
+        let config = &self.groups[group];
+        let burst = config.burst as f64;
+        let per_second = config.per_minute as f64 / 60.0;
+        let now = Instant::now();
+
+        let mut store = self.buckets.lock().unwrap();
+        if store.buckets.len() >= store.next_sweep.max(MIN_SWEEP_SIZE) {
+            let groups = &self.groups;
+            store.buckets.retain(|(group, _), bucket| {
+                let per_second = groups[*group].per_minute as f64 / 60.0;
+                let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+                bucket.tokens + elapsed * per_second < groups[*group].burst as f64
+            });
+            store.next_sweep = store.buckets.len() * 2;
+        }
+
+        let bucket = store
+            .buckets
+            .entry((group, key.to_string()))
+            .or_insert_with(|| Bucket {
+                tokens: burst,
+                updated_at: now,
+            });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(burst);
+        bucket.updated_at = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Quota {
+            allowed,
+            limit: config.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset: ((burst - bucket.tokens) / per_second).ceil() as u64,
+            retry_after: ((1.0 - bucket.tokens).max(0.0) / per_second).ceil() as u64,
+            window: (burst / per_second).ceil() as u64,
+        }
+    }
+}
+
+/// Who a request counts against, falling back to the client address.
+fn client_key(key: RateLimitKey, req: &ServiceRequest, data: &AppState) -> String {
+    let found = match key {
+        RateLimitKey::Ip => None,
+        // Only the signature is checked, a revoked token still names the user it was issued to
+        RateLimitKey::User => request_token(req.request())
+            .and_then(|token| data.keyring.decode::<TokenClaims>(&token).ok())
+            .map(|token| format!("user:{}", token.claims.sub)),
+        RateLimitKey::ApiKey => req
+            .headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.is_empty())
+            .map(|value| format!("api-key:{}", sha256_hex(value))),
+    };
+    found.unwrap_or_else(|| format!("ip:{}", client_ip(req.request(), data)))
+}
+
+/// Limits requests with a token bucket per client and route group, see `RATE_LIMITS`.
+///
+/// Build it once outside `HttpServer::new` so all workers share the buckets.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiter: Arc<RateLimiter>,
+}
+
+impl RateLimit {
+    pub fn new(groups: Vec<RateLimitConfig>) -> Self {
+        Self {
+            limiter: Arc::new(RateLimiter {
+                groups,
+                buckets: Mutex::new(Buckets::default()),
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            limiter: self.limiter.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let data = req.app_data::<web::Data<AppState>>().cloned();
+        let quota = match (self.limiter.group(req.path()), data) {
+            (Some(group), Some(data)) => {
+                let config = &self.limiter.groups[group];
+                let key = client_key(config.key, &req, &data);
+                let quota = self.limiter.take(group, &key);
+                if !quota.allowed {
+                    log::debug!("Rate limited {} on the {} routes", key, config.name);
+                }
+                Some(quota)
+            }
+            _ => None,
+        };
+
+        if let Some(quota) = quota.as_ref().filter(|quota| !quota.allowed) {
+            let mut response = HttpResponse::TooManyRequests()
+                .insert_header((RETRY_AFTER, quota.retry_after.to_string()))
+                .json(json!({"status": "fail", "message": "Too many requests, try again later"}));
+            quota.write_headers(response.headers_mut());
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+        }
+
+        let response = self.service.call(req);
+        Box::pin(async move {
+            let mut response = response.await?;
+            if let Some(quota) = quota {
+                quota.write_headers(response.headers_mut());
+            }
+            Ok(response.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, TestRequest},
+        App, HttpResponse,
+    };
+    use std::time::Duration;
+
+    use super::*;
+    use crate::auth::token;
+    use crate::models::User;
+
+    fn group(name: &str, paths: &[&str], burst: u32, per_minute: u32) -> RateLimitConfig {
+        RateLimitConfig {
+            name: name.to_string(),
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            burst,
+            per_minute,
+            key: RateLimitKey::Ip,
+        }
+    }
+
+    fn limiter(groups: Vec<RateLimitConfig>) -> RateLimiter {
+        RateLimiter {
+            groups,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    /// Moves the last update of a bucket back, as if `seconds` had passed.
+    fn rewind(limiter: &RateLimiter, key: &str, seconds: u64) {
+        let mut store = limiter.buckets.lock().unwrap();
+        let bucket = store.buckets.get_mut(&(0, key.to_string())).unwrap();
+        bucket.updated_at -= Duration::from_secs(seconds);
+    }
+
+    #[test]
+    fn requests_count_against_the_first_group_with_a_whole_prefix() {
+        let limiter = limiter(vec![
+            group("auth", &["/api/auth/login"], 1, 1),
+            group("api", &["/api"], 1, 1),
+        ]);
+        assert_eq!(limiter.group("/api/auth/login"), Some(0));
+        assert_eq!(limiter.group("/api/auth/login/"), Some(0));
+        assert_eq!(limiter.group("/api/auth/logout"), Some(1));
+        assert_eq!(limiter.group("/apis"), None);
+        assert_eq!(limiter.group("/"), None);
+    }
+
+    #[test]
+    fn a_bucket_empties_and_refills_over_time() {
+        // One request back every 10 seconds
+        let limiter = limiter(vec![group("auth", &["/"], 3, 6)]);
+
+        for remaining in [2, 1, 0] {
+            let quota = limiter.take(0, "ip:1.1.1.1");
+            assert!(quota.allowed);
+            assert_eq!(quota.remaining, remaining);
+        }
+        let quota = limiter.take(0, "ip:1.1.1.1");
+        assert!(!quota.allowed);
+        assert_eq!(quota.retry_after, 10);
+        assert_eq!(quota.reset, 30);
+        assert_eq!(quota.window, 30);
+        // Other clients have buckets of their own
+        assert!(limiter.take(0, "ip:2.2.2.2").allowed);
+
+        rewind(&limiter, "ip:1.1.1.1", 10);
+        assert!(limiter.take(0, "ip:1.1.1.1").allowed);
+        assert!(!limiter.take(0, "ip:1.1.1.1").allowed);
+
+        // Never more than the burst, however long the client was away
+        rewind(&limiter, "ip:1.1.1.1", 3600);
+        let quota = limiter.take(0, "ip:1.1.1.1");
+        assert_eq!(quota.remaining, 2);
+    }
+
+    #[test]
+    fn full_buckets_are_swept_once_the_map_grows() {
+        let limiter = limiter(vec![group("auth", &["/"], 2, 60)]);
+        for n in 0..MIN_SWEEP_SIZE {
+            limiter.take(0, &format!("ip:{}", n));
+        }
+        // Back to full after a second, so only the client still spending is worth keeping
+        for n in 1..MIN_SWEEP_SIZE {
+            rewind(&limiter, &format!("ip:{}", n), 1);
+        }
+        limiter.take(0, "ip:0");
+
+        let store = limiter.buckets.lock().unwrap();
+        assert_eq!(store.buckets.len(), 1);
+        assert!(store.buckets.contains_key(&(0, "ip:0".to_string())));
+        assert_eq!(store.next_sweep, 2);
+    }
+
+    #[actix_web::test]
+    async fn clients_are_told_apart_by_the_configured_key() {
+        let data = web::Data::new(AppState::for_tests());
+        let user = User::for_tests("user-1", "alice@example.com");
+        let access_token = token::create_access_token(&user, &data).unwrap();
+        let request = |header: Option<(&str, String)>| {
+            let mut req = TestRequest::default()
+                .peer_addr("10.0.0.1:4000".parse().unwrap())
+                .app_data(data.clone());
+            if let Some(header) = header {
+                req = req.insert_header(header);
+            }
+            req.to_srv_request()
+        };
+
+        let bearer = Some(("Authorization", format!("Bearer {}", access_token)));
+        let api_key = Some(("X-API-Key", "secret".to_string()));
+        for (key, header, expected) in [
+            (RateLimitKey::Ip, bearer.clone(), "ip:10.0.0.1".to_string()),
+            (RateLimitKey::User, bearer, "user:user-1".to_string()),
+            (
+                RateLimitKey::User,
+                Some(("Authorization", "Bearer forged".to_string())),
+                "ip:10.0.0.1".to_string(),
+            ),
+            (
+                RateLimitKey::ApiKey,
+                api_key,
+                format!("api-key:{}", sha256_hex("secret")),
+            ),
+            (RateLimitKey::ApiKey, None, "ip:10.0.0.1".to_string()),
+        ] {
+            assert_eq!(client_key(key, &request(header), &data), expected);
+        }
+    }
+
+    #[actix_web::test]
+    async fn an_empty_bucket_answers_429_with_the_quota_headers() {
+        let data = web::Data::new(AppState::for_tests());
+        let app = init_service(
+            App::new()
+                .app_data(data)
+                .wrap(RateLimit::new(vec![group("auth", &["/api/auth"], 2, 6)]))
+                .route("/api/auth/login", web::post().to(HttpResponse::Ok))
+                .route("/health", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let login = || {
+            TestRequest::post()
+                .uri("/api/auth/login")
+                .peer_addr("10.0.0.1:4000".parse().unwrap())
+                .to_request()
+        };
+        let header = |res: &ServiceResponse<_>, name: &str| {
+            res.headers()
+                .get(name)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let res = call_service(&app, login()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(header(&res, "ratelimit-limit"), "2");
+        assert_eq!(header(&res, "ratelimit-remaining"), "1");
+        assert_eq!(header(&res, "ratelimit-policy"), "2;w=20");
+        call_service(&app, login()).await;
+
+        let res = call_service(&app, login()).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&res, "retry-after"), "10");
+        assert_eq!(header(&res, "ratelimit-remaining"), "0");
+        assert_eq!(header(&res, "ratelimit-reset"), "20");
+
+        // Routes outside every group are neither counted nor labelled
+        let req = TestRequest::get().uri("/health").to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!res.headers().contains_key("ratelimit-limit"));
+    }
+}
@@ -1,18 +1,80 @@
+use crate::auth::{
+    keyring::Keyring, role_hierarchy::RoleHierarchy, webauthn, OAuthProviderRegistry,
+};
 use crate::config::env::Config;
-use crate::models::user::User;
+use crate::mail::{self, Mailer};
+use crate::repository::{
+    Database, IdentityRepository, LoginAttemptRepository, OneTimeTokenRepository,
+    RefreshTokenRepository, RepositoryError, TokenRevocationRepository, TwoFactorRepository,
+    UserRepository, WebauthnCredentialRepository,
+};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use webauthn_rs::Webauthn;
 
 pub struct AppState {
-    pub db: Arc<Mutex<Vec<User>>>,
+    pub db: Arc<dyn UserRepository>,
+    pub identities: Arc<dyn IdentityRepository>,
+    pub refresh_tokens: Arc<dyn RefreshTokenRepository>,
+    pub revoked_tokens: Arc<dyn TokenRevocationRepository>,
+    pub one_time_tokens: Arc<dyn OneTimeTokenRepository>,
+    pub two_factor: Arc<dyn TwoFactorRepository>,
+    pub webauthn_credentials: Arc<dyn WebauthnCredentialRepository>,
+    pub login_attempts: Arc<dyn LoginAttemptRepository>,
+    pub keyring: Keyring,
+    pub roles: RoleHierarchy,
+    pub oauth: OAuthProviderRegistry,
+    pub webauthn: Webauthn,
+    pub mailer: Arc<dyn Mailer>,
     pub env: Config,
 }
 
 impl AppState {
-    pub fn init() -> AppState {
+    pub async fn init() -> Result<AppState, RepositoryError> {
+        let env = Config::init();
+        let database =
+            Database::connect(env.database_url.as_deref(), env.database_max_connections).await?;
+
+        Ok(AppState {
+            db: database.user_repository(),
+            identities: database.identity_repository(),
+            refresh_tokens: database.refresh_token_repository(),
+            revoked_tokens: database.token_revocation_repository(),
+            one_time_tokens: database.one_time_token_repository(),
+            two_factor: database.two_factor_repository(),
+            webauthn_credentials: database.webauthn_credential_repository(),
+            login_attempts: database.login_attempt_repository(),
+            keyring: Keyring::from_config(&env),
+            roles: RoleHierarchy::from_config(&env),
+            oauth: OAuthProviderRegistry::from_config(&env),
+            webauthn: webauthn::from_config(&env),
+            mailer: mail::from_config(&env).expect("Failed to configure the mailer"),
+            env,
+        })
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// In-memory state over [`Config::for_tests`] for handler tests.
+    pub fn for_tests() -> AppState {
+        let env = Config::for_tests();
+        let database = Database::Memory;
+
         AppState {
-            db: Arc::new(Mutex::new(Vec::new())),
-            env: Config::init(),
+            db: database.user_repository(),
+            identities: database.identity_repository(),
+            refresh_tokens: database.refresh_token_repository(),
+            revoked_tokens: database.token_revocation_repository(),
+            one_time_tokens: database.one_time_token_repository(),
+            two_factor: database.two_factor_repository(),
+            webauthn_credentials: database.webauthn_credential_repository(),
+            login_attempts: database.login_attempt_repository(),
+            keyring: Keyring::from_config(&env),
+            roles: RoleHierarchy::from_config(&env),
+            oauth: OAuthProviderRegistry::from_config(&env),
+            webauthn: webauthn::from_config(&env),
+            mailer: Arc::new(mail::ConsoleMailer),
+            env,
         }
     }
 }
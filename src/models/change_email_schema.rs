@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeEmailSchema {
+    pub email: String,
+    /// Required when the account has a password, accounts without one approve from their current address.
+    pub password: Option<String>,
+}
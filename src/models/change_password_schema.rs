@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordSchema {
+    pub current_password: String,
+    pub new_password: String,
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// A link sent by email, signed so it can't be forged and bound to the address it was sent to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailTokenClaims {
+    pub sub: String,
+    pub email: String,
+    /// What the link is for, e.g. `verify-email`, a token for one purpose is refused by the others.
+    pub aud: String,
+    pub iat: usize,
+    pub exp: usize,
+}
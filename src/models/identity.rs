@@ -0,0 +1,15 @@
+use chrono::prelude::*;
+
+/// A provider account linked to a [`User`](super::User), found again by
+/// `(provider, subject)` no matter which email the provider reports later.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Identity {
+    /// Registry name of the provider, the `{provider}` path segment.
+    pub provider: String,
+    /// The provider's stable id for the account.
+    pub subject: String,
+    pub user_id: String,
+    /// Email the provider reported when the identity was linked.
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+}
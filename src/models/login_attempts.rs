@@ -0,0 +1,23 @@
+/// Failed logins counted against one key, an account (`account:{email}`), a client (`ip:{addr}`)
+/// or the 2FA step of a user (`mfa:{id}`).
+///
+/// Timestamps are unix seconds.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LoginAttempts {
+    pub key: String,
+    pub failures: i64,
+    pub last_failure_at: i64,
+    /// Logins with this key are refused until then.
+    pub locked_until: Option<i64>,
+}
+
+/// How many attempts a key gets and how long it waits between and after them, in seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct AttemptLimits {
+    pub max_attempts: i64,
+    /// How long a key is locked once over `max_attempts`, older failures are forgotten too.
+    pub lockout: i64,
+    /// Wait after the first attempt, doubling with each one after it, 0 to not back off.
+    pub backoff_base: i64,
+    pub backoff_max: i64,
+}
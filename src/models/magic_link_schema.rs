@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct MagicLinkSchema {
+    pub email: String,
+    /// Path on the client to land on once signed in, `/` by default.
+    pub redirect_to: Option<String>,
+}
@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+/// A second factor, either a code from the authenticator app or a recovery code.
+#[derive(Debug, Deserialize)]
+pub struct MfaCodeSchema {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
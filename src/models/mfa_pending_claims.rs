@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// Handed out instead of a session when the password was right but a second factor is still due.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaPendingClaims {
+    pub sub: String,
+    /// Always `mfa-pending`, so no other token we sign is accepted in its place.
+    pub aud: String,
+    pub iat: usize,
+    pub exp: usize,
+}
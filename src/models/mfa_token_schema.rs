@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct MfaTokenSchema {
+    /// Falls back to the `mfa_pending` cookie.
+    pub mfa_token: Option<String>,
+}
@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct MfaVerifySchema {
+    /// Falls back to the `mfa_pending` cookie.
+    pub mfa_token: Option<String>,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
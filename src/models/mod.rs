@@ -1,14 +1,70 @@
 pub mod app_state;
+pub mod change_email_schema;
+pub mod change_password_schema;
+pub mod confirm_email_query;
+pub mod email_token_claims;
+pub mod forgot_password_schema;
+pub mod identity;
+pub mod login_attempts;
 pub mod login_user_schema;
+pub mod magic_link_schema;
+pub mod mfa_code_schema;
+pub mod mfa_pending_claims;
+pub mod mfa_token_schema;
+pub mod mfa_verify_schema;
+pub mod oauth_start_query;
+pub mod oauth_state_claims;
+pub mod one_time_token;
 pub mod query_code;
+pub mod redeem_magic_link_schema;
+pub mod refresh_token;
+pub mod refresh_token_schema;
 pub mod register_user_schema;
+pub mod resend_verification_schema;
+pub mod reset_password_schema;
 pub mod token_claims;
+pub mod totp_factor;
+pub mod update_user_schema;
 pub mod user;
+pub mod user_list_query;
+pub mod verify_email_query;
+pub mod webauthn_assertion_schema;
+pub mod webauthn_credential;
+pub mod webauthn_login_schema;
+pub mod webauthn_register_schema;
 
 // And then, re-export for easier use
 pub use app_state::AppState;
+pub use change_email_schema::ChangeEmailSchema;
+pub use change_password_schema::ChangePasswordSchema;
+pub use confirm_email_query::ConfirmEmailQuery;
+pub use email_token_claims::EmailTokenClaims;
+pub use forgot_password_schema::ForgotPasswordSchema;
+pub use identity::Identity;
+pub use login_attempts::{AttemptLimits, LoginAttempts};
 pub use login_user_schema::LoginUserSchema;
+pub use magic_link_schema::MagicLinkSchema;
+pub use mfa_code_schema::MfaCodeSchema;
+pub use mfa_pending_claims::MfaPendingClaims;
+pub use mfa_token_schema::MfaTokenSchema;
+pub use mfa_verify_schema::MfaVerifySchema;
+pub use oauth_start_query::OAuthStartQuery;
+pub use oauth_state_claims::OAuthStateClaims;
+pub use one_time_token::OneTimeToken;
 pub use query_code::QueryCode;
+pub use redeem_magic_link_schema::RedeemMagicLinkSchema;
+pub use refresh_token::RefreshToken;
+pub use refresh_token_schema::RefreshTokenSchema;
 pub use register_user_schema::RegisterUserSchema;
+pub use resend_verification_schema::ResendVerificationSchema;
+pub use reset_password_schema::ResetPasswordSchema;
 pub use token_claims::TokenClaims;
+pub use totp_factor::TotpFactor;
+pub use update_user_schema::UpdateUserSchema;
 pub use user::User;
+pub use user_list_query::UserListQuery;
+pub use verify_email_query::VerifyEmailQuery;
+pub use webauthn_assertion_schema::WebauthnAssertionSchema;
+pub use webauthn_credential::WebauthnCredential;
+pub use webauthn_login_schema::WebauthnLoginSchema;
+pub use webauthn_register_schema::WebauthnRegisterSchema;
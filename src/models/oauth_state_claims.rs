@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// Authorization flow bound to the browser through the `oauth_state` cookie.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthStateClaims {
+    pub state: String,
+    pub provider: String,
+    /// Path on `CLIENT_ORIGIN` to land on once logged in.
+    pub redirect_to: String,
+    /// PKCE verifier sent with the token exchange, unset when the provider opted out.
+    pub code_verifier: Option<String>,
+    /// OpenID Connect nonce the provider must echo back in the id_token.
+    pub nonce: String,
+    /// Set when a logged in user links this provider instead of signing in.
+    #[serde(default)]
+    pub link_user_id: Option<String>,
+    pub iat: usize,
+    pub exp: usize,
+}
@@ -0,0 +1,19 @@
+use chrono::prelude::*;
+
+/// A token handed out for a single use, e.g. a password reset link.
+///
+/// Only the SHA-256 hash is stored and redeeming the token deletes it.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct OneTimeToken {
+    pub token_hash: String,
+    /// What the token allows, a token is only redeemed for the purpose it was issued for.
+    pub purpose: String,
+    /// Unset when the token was sent to an address with no account yet.
+    pub user_id: Option<String>,
+    /// The address the token was sent to.
+    pub email: String,
+    /// State the purpose needs back on redemption, e.g. a pending WebAuthn ceremony.
+    pub payload: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
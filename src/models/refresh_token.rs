@@ -0,0 +1,16 @@
+use chrono::prelude::*;
+
+/// A server-side refresh token, only its SHA-256 hash is ever stored.
+///
+/// Every rotation issues a new token in the same `family_id`, so presenting a
+/// token that was already rotated out reveals a replay and burns the whole family.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RefreshToken {
+    pub token_hash: String,
+    pub family_id: String,
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+/// Body of `POST /api/auth/refresh` for clients that don't keep cookies.
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenSchema {
+    pub refresh_token: String,
+}
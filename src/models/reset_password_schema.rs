@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordSchema {
+    pub token: String,
+    pub password: String,
+}
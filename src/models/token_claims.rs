@@ -3,6 +3,11 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub sub: String,
+    /// Unique token id, the key of the revocation list.
+    pub jti: String,
+    /// `User::role` when the token was issued, tokens from before roles existed carry none.
+    #[serde(default)]
+    pub role: String,
     pub iat: usize,
     pub exp: usize,
 }
@@ -0,0 +1,13 @@
+use chrono::prelude::*;
+
+/// A user's authenticator app secret, unusable for login until `enabled` by a first valid code.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TotpFactor {
+    pub user_id: String,
+    /// Base32 shared secret, as shown to the user during enrollment.
+    pub secret: String,
+    pub enabled: bool,
+    /// Time step of the last accepted code, a code is never accepted twice.
+    pub last_used_step: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
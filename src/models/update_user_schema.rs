@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+/// Body of `PATCH /api/admin/users/{id}`, omitted fields are left unchanged.
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserSchema {
+    pub role: Option<String>,
+    pub suspended: Option<bool>,
+}
@@ -11,7 +11,33 @@ pub struct User {
     pub role: String,
     pub photo: String,
     pub verified: bool,
+    /// Suspended accounts can't sign in and their tokens are refused.
+    pub suspended: bool,
     pub provider: String,
+    /// When the last verification email went out, resends are throttled from it.
+    pub verificationSentAt: Option<DateTime<Utc>>,
     pub createdAt: Option<DateTime<Utc>>,
     pub updatedAt: Option<DateTime<Utc>>,
 }
+
+#[cfg(test)]
+impl User {
+    /// A verified local user without a password, for tests.
+    pub fn for_tests(id: &str, email: &str) -> User {
+        let now = Utc::now();
+        User {
+            id: Some(id.to_string()),
+            name: "Alice".to_string(),
+            email: email.to_string(),
+            password: String::new(),
+            role: "user".to_string(),
+            photo: "default.png".to_string(),
+            verified: true,
+            suspended: false,
+            provider: "local".to_string(),
+            verificationSentAt: None,
+            createdAt: Some(now),
+            updatedAt: Some(now),
+        }
+    }
+}
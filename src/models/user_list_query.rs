@@ -0,0 +1,14 @@
+use serde::Deserialize;
+
+/// Query string of `GET /api/admin/users`.
+#[derive(Debug, Deserialize)]
+pub struct UserListQuery {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub provider: Option<String>,
+    pub role: Option<String>,
+    pub verified: Option<bool>,
+    pub suspended: Option<bool>,
+    /// Matches part of the name or email.
+    pub q: Option<String>,
+}
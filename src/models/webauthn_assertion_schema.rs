@@ -0,0 +1,12 @@
+use serde::Deserialize;
+use webauthn_rs::prelude::PublicKeyCredential;
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnAssertionSchema {
+    /// Returned by the matching `start` endpoint.
+    pub ceremony: String,
+    /// What `navigator.credentials.get()` resolved to.
+    pub credential: PublicKeyCredential,
+    /// Only for the 2FA step, falls back to the `mfa_pending` cookie.
+    pub mfa_token: Option<String>,
+}
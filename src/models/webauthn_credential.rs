@@ -0,0 +1,15 @@
+use chrono::prelude::*;
+
+/// A passkey or security key registered by a [`User`](super::User).
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WebauthnCredential {
+    /// Base64url id the authenticator picked for the credential.
+    pub credential_id: String,
+    pub user_id: String,
+    /// Label chosen by the user, e.g. "YubiKey".
+    pub name: String,
+    /// JSON of the `webauthn_rs` `Passkey`: public key and signature counter.
+    pub passkey: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnLoginSchema {
+    /// Lets passkeys the browser can't discover on its own be offered, e.g. security keys.
+    pub email: Option<String>,
+}
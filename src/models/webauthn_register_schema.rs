@@ -0,0 +1,11 @@
+use serde::Deserialize;
+use webauthn_rs::prelude::RegisterPublicKeyCredential;
+
+#[derive(Debug, Deserialize)]
+pub struct WebauthnRegisterSchema {
+    /// Returned by `/api/auth/webauthn/register/start`.
+    pub ceremony: String,
+    /// What `navigator.credentials.create()` resolved to.
+    pub credential: RegisterPublicKeyCredential,
+    pub name: Option<String>,
+}
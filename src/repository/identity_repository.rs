@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+
+use crate::models::Identity;
+
+use super::RepositoryError;
+
+/// Provider accounts linked to users, see [`Identity`].
+///
+/// A provider account belongs to at most one user and a user links at most one
+/// account per provider, `insert` returns [`RepositoryError::Conflict`] otherwise.
+#[async_trait]
+pub trait IdentityRepository: Send + Sync {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Identity>, RepositoryError>;
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Identity>, RepositoryError>;
+    async fn insert(&self, identity: Identity) -> Result<Identity, RepositoryError>;
+    async fn delete(&self, user_id: &str, provider: &str) -> Result<(), RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{models::User, repository::Database};
+
+    async fn backends() -> Vec<Arc<dyn IdentityRepository>> {
+        let mut repos = Vec::new();
+        for database in Database::for_tests().await {
+            let users = database.user_repository();
+            for id in ["user-1", "user-2"] {
+                users
+                    .insert(User::for_tests(id, &format!("{}@example.com", id)))
+                    .await
+                    .unwrap();
+            }
+            repos.push(database.identity_repository());
+        }
+        repos
+    }
+
+    fn identity(n: i64, provider: &str, subject: &str, user_id: &str) -> Identity {
+        Identity {
+            provider: provider.to_string(),
+            subject: subject.to_string(),
+            user_id: user_id.to_string(),
+            email: format!("{}@{}.example", subject, provider),
+            created_at: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(n),
+        }
+    }
+
+    #[actix_web::test]
+    async fn identities_are_found_by_provider_subject() {
+        for repo in backends().await {
+            repo.insert(identity(2, "google", "g-1", "user-1"))
+                .await
+                .unwrap();
+            repo.insert(identity(1, "github", "gh-1", "user-1"))
+                .await
+                .unwrap();
+
+            let found = repo.find("google", "g-1").await.unwrap().unwrap();
+            assert_eq!(found.user_id, "user-1");
+            // Subjects are only unique within their provider
+            assert!(repo.find("github", "g-1").await.unwrap().is_none());
+
+            let providers: Vec<String> = repo
+                .list_for_user("user-1")
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|identity| identity.provider)
+                .collect();
+            assert_eq!(providers, ["github", "google"]);
+            assert!(repo.list_for_user("user-2").await.unwrap().is_empty());
+        }
+    }
+
+    #[actix_web::test]
+    async fn an_account_links_once_and_a_user_once_per_provider() {
+        for repo in backends().await {
+            repo.insert(identity(1, "google", "g-1", "user-1"))
+                .await
+                .unwrap();
+
+            for duplicate in [
+                identity(2, "google", "g-1", "user-2"),
+                identity(3, "google", "g-2", "user-1"),
+            ] {
+                let err = repo.insert(duplicate).await.unwrap_err();
+                assert!(matches!(err, RepositoryError::Conflict(_)), "{:?}", err);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn delete_unlinks_one_provider() {
+        for repo in backends().await {
+            repo.insert(identity(1, "google", "g-1", "user-1"))
+                .await
+                .unwrap();
+            repo.insert(identity(2, "github", "gh-1", "user-1"))
+                .await
+                .unwrap();
+
+            repo.delete("user-1", "google").await.unwrap();
+            assert!(repo.find("google", "g-1").await.unwrap().is_none());
+            assert!(repo.find("github", "gh-1").await.unwrap().is_some());
+
+            let err = repo.delete("user-1", "google").await.unwrap_err();
+            assert!(matches!(err, RepositoryError::NotFound), "{:?}", err);
+        }
+    }
+}
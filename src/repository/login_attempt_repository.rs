@@ -0,0 +1,145 @@
+use async_trait::async_trait;
+
+use crate::models::{AttemptLimits, LoginAttempts};
+
+use super::RepositoryError;
+
+/// Failed login counters, shared by every instance when kept in the database.
+///
+/// All timestamps are unix seconds.
+#[async_trait]
+pub trait LoginAttemptRepository: Send + Sync {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempts>, RepositoryError>;
+    /// Counts an attempt at `now` before its outcome is known and returns the counter, or
+    /// `None` without counting while `key` is locked.
+    ///
+    /// Checking and counting is one step, so concurrent attempts can't all get past the limits.
+    /// The attempt locks `key` for the backoff it earns, or for the lockout once it goes over
+    /// `max_attempts`.
+    async fn reserve(
+        &self,
+        key: &str,
+        now: i64,
+        limits: &AttemptLimits,
+    ) -> Result<Option<LoginAttempts>, RepositoryError>;
+    /// Takes back an attempt of `key` that turned out not to be a failure.
+    async fn release(&self, key: &str) -> Result<(), RepositoryError>;
+    /// Forgets the failures of `key`, returns whether there were any.
+    async fn clear(&self, key: &str) -> Result<bool, RepositoryError>;
+    /// Drops counters without failures since `before` that aren't locked at `now`.
+    async fn purge_expired(&self, before: i64, now: i64) -> Result<u64, RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tokio::task::JoinSet;
+
+    use super::*;
+    use crate::repository::Database;
+
+    async fn backends() -> Vec<Arc<dyn LoginAttemptRepository>> {
+        Database::for_tests()
+            .await
+            .iter()
+            .map(Database::login_attempt_repository)
+            .collect()
+    }
+
+    fn limits(backoff_base: i64) -> AttemptLimits {
+        AttemptLimits {
+            max_attempts: 5,
+            lockout: 900,
+            backoff_base,
+            backoff_max: 60,
+        }
+    }
+
+    /// Reserves `count` attempts at once and returns the counters of the ones let through.
+    async fn burst(
+        repo: &Arc<dyn LoginAttemptRepository>,
+        count: usize,
+        limits: AttemptLimits,
+    ) -> Vec<i64> {
+        let mut tasks = JoinSet::new();
+        for _ in 0..count {
+            let repo = repo.clone();
+            tasks.spawn(async move { repo.reserve("ip:1.2.3.4", 1_000, &limits).await });
+        }
+        let mut failures = Vec::new();
+        while let Some(result) = tasks.join_next().await {
+            if let Some(attempts) = result.unwrap().unwrap() {
+                failures.push(attempts.failures);
+            }
+        }
+        failures.sort();
+        failures
+    }
+
+    #[actix_web::test]
+    async fn a_burst_of_attempts_is_counted_one_by_one() {
+        for repo in backends().await {
+            // The attempt after the fifth locks the key, the rest are refused uncounted
+            assert_eq!(burst(&repo, 20, limits(0)).await, vec![1, 2, 3, 4, 5, 6]);
+            let attempts = repo.find("ip:1.2.3.4").await.unwrap().unwrap();
+            assert_eq!(attempts.locked_until, Some(1_900));
+            assert!(repo
+                .reserve("ip:1.2.3.4", 1_899, &limits(0))
+                .await
+                .unwrap()
+                .is_none());
+
+            // Once the lockout is over the count starts again
+            let attempts = repo.reserve("ip:1.2.3.4", 1_900, &limits(0)).await.unwrap();
+            assert_eq!(attempts.unwrap().failures, 1);
+        }
+    }
+
+    #[actix_web::test]
+    async fn backing_off_lets_one_attempt_through_at_a_time() {
+        for repo in backends().await {
+            assert_eq!(burst(&repo, 10, limits(2)).await, vec![1]);
+            assert!(repo
+                .reserve("ip:1.2.3.4", 1_001, &limits(2))
+                .await
+                .unwrap()
+                .is_none());
+
+            // The wait doubles with each attempt
+            let attempts = repo.reserve("ip:1.2.3.4", 1_002, &limits(2)).await.unwrap();
+            assert_eq!(attempts.unwrap().locked_until, Some(1_006));
+        }
+    }
+
+    #[actix_web::test]
+    async fn release_takes_back_one_attempt() {
+        for repo in backends().await {
+            repo.reserve("ip:1.2.3.4", 1_000, &limits(0)).await.unwrap();
+            repo.reserve("ip:1.2.3.4", 1_000, &limits(0)).await.unwrap();
+            repo.release("ip:1.2.3.4").await.unwrap();
+            let attempts = repo.find("ip:1.2.3.4").await.unwrap().unwrap();
+            assert_eq!(attempts.failures, 1);
+
+            repo.release("ip:1.2.3.4").await.unwrap();
+            repo.release("ip:1.2.3.4").await.unwrap();
+            let attempts = repo.find("ip:1.2.3.4").await.unwrap().unwrap();
+            assert_eq!(attempts.failures, 0);
+        }
+    }
+
+    #[actix_web::test]
+    async fn clear_and_purge_forget_counters() {
+        for repo in backends().await {
+            repo.reserve("ip:1.2.3.4", 1_000, &limits(0)).await.unwrap();
+            repo.reserve("ip:5.6.7.8", 2_000, &limits(0)).await.unwrap();
+
+            // Only the counter without a recent attempt goes
+            assert_eq!(repo.purge_expired(1_500, 2_000).await.unwrap(), 1);
+            assert!(repo.find("ip:1.2.3.4").await.unwrap().is_none());
+
+            assert!(repo.clear("ip:5.6.7.8").await.unwrap());
+            assert!(!repo.clear("ip:5.6.7.8").await.unwrap());
+            assert!(repo.find("ip:5.6.7.8").await.unwrap().is_none());
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use crate::models::Identity;
+
+use super::{IdentityRepository, RepositoryError};
+
+/// Keeps linked identities in process memory, they are lost on restart.
+#[derive(Default)]
+pub struct MemoryIdentityRepository {
+    // (provider, subject) -> identity
+    identities: RwLock<HashMap<(String, String), Identity>>,
+}
+
+impl MemoryIdentityRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IdentityRepository for MemoryIdentityRepository {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Identity>, RepositoryError> {
+        Ok(self
+            .identities
+            .read()
+            .await
+            .get(&(provider.to_string(), subject.to_string()))
+            .cloned())
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Identity>, RepositoryError> {
+        let mut identities: Vec<Identity> = self
+            .identities
+            .read()
+            .await
+            .values()
+            .filter(|identity| identity.user_id == user_id)
+            .cloned()
+            .collect();
+        identities.sort_by_key(|identity| identity.created_at);
+        Ok(identities)
+    }
+
+    async fn insert(&self, identity: Identity) -> Result<Identity, RepositoryError> {
+        let mut identities = self.identities.write().await;
+
+        let key = (identity.provider.clone(), identity.subject.clone());
+        let provider_taken = identities
+            .values()
+            .any(|other| other.user_id == identity.user_id && other.provider == identity.provider);
+        if identities.contains_key(&key) || provider_taken {
+            return Err(RepositoryError::Conflict(
+                "Identity already linked".to_string(),
+            ));
+        }
+
+        identities.insert(key, identity.clone());
+        Ok(identity)
+    }
+
+    async fn delete(&self, user_id: &str, provider: &str) -> Result<(), RepositoryError> {
+        let mut identities = self.identities.write().await;
+        let before = identities.len();
+
+        identities
+            .retain(|_, identity| !(identity.user_id == user_id && identity.provider == provider));
+        if identities.len() == before {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
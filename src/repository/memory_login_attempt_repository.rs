@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use crate::models::{AttemptLimits, LoginAttempts};
+
+use super::{LoginAttemptRepository, RepositoryError};
+
+/// Keeps failed login counters in process memory, each instance counts on its own.
+#[derive(Default)]
+pub struct MemoryLoginAttemptRepository {
+    attempts: RwLock<HashMap<String, LoginAttempts>>,
+}
+
+impl MemoryLoginAttemptRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for MemoryLoginAttemptRepository {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempts>, RepositoryError> {
+        Ok(self.attempts.read().await.get(key).cloned())
+    }
+
+    async fn reserve(
+        &self,
+        key: &str,
+        now: i64,
+        limits: &AttemptLimits,
+    ) -> Result<Option<LoginAttempts>, RepositoryError> {
+        // Held from the check to the update, so concurrent attempts are counted one by one
+        let mut attempts = self.attempts.write().await;
+        let entry = attempts
+            .entry(key.to_string())
+            .or_insert_with(|| LoginAttempts {
+                key: key.to_string(),
+                failures: 0,
+                last_failure_at: now,
+                locked_until: None,
+            });
+
+        if entry.locked_until.is_some_and(|until| until > now) {
+            return Ok(None);
+        }
+        if entry.last_failure_at <= now - limits.lockout {
+            entry.failures = 0;
+        }
+        entry.failures += 1;
+        entry.last_failure_at = now;
+        entry.locked_until = if entry.failures > limits.max_attempts {
+            Some(now + limits.lockout)
+        } else if limits.backoff_base > 0 {
+            let doublings = (entry.failures - 1).min(30) as u32;
+            Some(
+                now + limits
+                    .backoff_base
+                    .saturating_mul(1 << doublings)
+                    .min(limits.backoff_max),
+            )
+        } else {
+            None
+        };
+        Ok(Some(entry.clone()))
+    }
+
+    async fn release(&self, key: &str) -> Result<(), RepositoryError> {
+        if let Some(entry) = self.attempts.write().await.get_mut(key) {
+            entry.failures = (entry.failures - 1).max(0);
+        }
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<bool, RepositoryError> {
+        Ok(self.attempts.write().await.remove(key).is_some())
+    }
+
+    async fn purge_expired(&self, before: i64, now: i64) -> Result<u64, RepositoryError> {
+        let mut attempts = self.attempts.write().await;
+        let count = attempts.len();
+
+        attempts.retain(|_, entry| {
+            entry.last_failure_at >= before || entry.locked_until.is_some_and(|until| until > now)
+        });
+        Ok((count - attempts.len()) as u64)
+    }
+}
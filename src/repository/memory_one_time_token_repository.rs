@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use crate::models::OneTimeToken;
+
+use super::{OneTimeTokenRepository, RepositoryError};
+
+/// Keeps one-time tokens in process memory, outstanding links stop working on restart.
+#[derive(Default)]
+pub struct MemoryOneTimeTokenRepository {
+    // token hash -> token
+    tokens: RwLock<HashMap<String, OneTimeToken>>,
+}
+
+impl MemoryOneTimeTokenRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl OneTimeTokenRepository for MemoryOneTimeTokenRepository {
+    async fn insert(&self, token: OneTimeToken) -> Result<(), RepositoryError> {
+        let mut tokens = self.tokens.write().await;
+
+        if tokens.contains_key(&token.token_hash) {
+            return Err(RepositoryError::Conflict(
+                "One-time token already exist".to_string(),
+            ));
+        }
+        tokens.insert(token.token_hash.clone(), token);
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+    ) -> Result<Option<OneTimeToken>, RepositoryError> {
+        let mut tokens = self.tokens.write().await;
+
+        match tokens.get(token_hash) {
+            Some(token) if token.purpose == purpose => Ok(tokens.remove(token_hash)),
+            _ => Ok(None),
+        }
+    }
+
+    async fn revoke_user(&self, user_id: &str, purpose: &str) -> Result<(), RepositoryError> {
+        self.tokens.write().await.retain(|_, token| {
+            !(token.user_id.as_deref() == Some(user_id) && token.purpose == purpose)
+        });
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, RepositoryError> {
+        let now = Utc::now();
+        let mut tokens = self.tokens.write().await;
+        let before = tokens.len();
+
+        tokens.retain(|_, token| token.expires_at > now);
+        Ok((before - tokens.len()) as u64)
+    }
+}
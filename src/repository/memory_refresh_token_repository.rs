@@ -0,0 +1,86 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use crate::models::RefreshToken;
+
+use super::{RefreshTokenRepository, RepositoryError};
+
+/// Keeps refresh tokens in process memory, every session is lost on restart.
+#[derive(Default)]
+pub struct MemoryRefreshTokenRepository {
+    // token hash -> token
+    tokens: RwLock<HashMap<String, RefreshToken>>,
+}
+
+impl MemoryRefreshTokenRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for MemoryRefreshTokenRepository {
+    async fn insert(&self, token: RefreshToken) -> Result<(), RepositoryError> {
+        let mut tokens = self.tokens.write().await;
+
+        if tokens.contains_key(&token.token_hash) {
+            return Err(RepositoryError::Conflict(
+                "Refresh token already exist".to_string(),
+            ));
+        }
+        tokens.insert(token.token_hash.clone(), token);
+        Ok(())
+    }
+
+    async fn find_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, RepositoryError> {
+        Ok(self.tokens.read().await.get(token_hash).cloned())
+    }
+
+    async fn mark_rotated(&self, token_hash: &str) -> Result<bool, RepositoryError> {
+        let mut tokens = self.tokens.write().await;
+
+        match tokens.get_mut(token_hash) {
+            Some(token) if token.rotated_at.is_none() && token.revoked_at.is_none() => {
+                token.rotated_at = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RepositoryError> {
+        let now = Utc::now();
+        let mut tokens = self.tokens.write().await;
+
+        tokens
+            .values_mut()
+            .filter(|token| token.family_id == family_id && token.revoked_at.is_none())
+            .for_each(|token| token.revoked_at = Some(now));
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: &str) -> Result<(), RepositoryError> {
+        let now = Utc::now();
+        let mut tokens = self.tokens.write().await;
+
+        tokens
+            .values_mut()
+            .filter(|token| token.user_id == user_id && token.revoked_at.is_none())
+            .for_each(|token| token.revoked_at = Some(now));
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, RepositoryError> {
+        let now = Utc::now();
+        let mut tokens = self.tokens.write().await;
+        let before = tokens.len();
+
+        tokens.retain(|_, token| token.expires_at > now);
+        Ok((before - tokens.len()) as u64)
+    }
+}
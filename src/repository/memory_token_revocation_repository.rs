@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use crate::models::TokenClaims;
+
+use super::{RepositoryError, TokenRevocationRepository};
+
+#[derive(Default)]
+struct Store {
+    // jti -> expires_at
+    tokens: HashMap<String, i64>,
+    // user id -> (issued_before, expires_at)
+    sessions: HashMap<String, (i64, i64)>,
+}
+
+/// Keeps the revocation list in process memory, it is lost on restart.
+#[derive(Default)]
+pub struct MemoryTokenRevocationRepository {
+    store: RwLock<Store>,
+}
+
+impl MemoryTokenRevocationRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TokenRevocationRepository for MemoryTokenRevocationRepository {
+    async fn revoke(&self, jti: &str, expires_at: i64) -> Result<(), RepositoryError> {
+        self.store
+            .write()
+            .await
+            .tokens
+            .insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+
+    async fn revoke_user(
+        &self,
+        user_id: &str,
+        issued_before: i64,
+        expires_at: i64,
+    ) -> Result<(), RepositoryError> {
+        self.store
+            .write()
+            .await
+            .sessions
+            .insert(user_id.to_string(), (issued_before, expires_at));
+        Ok(())
+    }
+
+    async fn is_revoked(&self, claims: &TokenClaims) -> Result<bool, RepositoryError> {
+        let store = self.store.read().await;
+
+        if store.tokens.contains_key(&claims.jti) {
+            return Ok(true);
+        }
+        Ok(store
+            .sessions
+            .get(&claims.sub)
+            .is_some_and(|(issued_before, _)| (claims.iat as i64) <= *issued_before))
+    }
+
+    async fn purge_expired(&self, now: i64) -> Result<u64, RepositoryError> {
+        let mut store = self.store.write().await;
+        let before = store.tokens.len() + store.sessions.len();
+
+        store.tokens.retain(|_, expires_at| *expires_at > now);
+        store
+            .sessions
+            .retain(|_, (_, expires_at)| *expires_at > now);
+        Ok((before - store.tokens.len() - store.sessions.len()) as u64)
+    }
+}
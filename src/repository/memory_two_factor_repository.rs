@@ -0,0 +1,89 @@
+use async_trait::async_trait;
+use std::collections::{HashMap, HashSet};
+use tokio::sync::RwLock;
+
+use crate::models::TotpFactor;
+
+use super::{RepositoryError, TwoFactorRepository};
+
+/// Keeps second factors in process memory, enrollments are lost on restart.
+#[derive(Default)]
+pub struct MemoryTwoFactorRepository {
+    // user id -> factor
+    totp: RwLock<HashMap<String, TotpFactor>>,
+    // user id -> recovery code hashes
+    recovery_codes: RwLock<HashMap<String, HashSet<String>>>,
+}
+
+impl MemoryTwoFactorRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepository for MemoryTwoFactorRepository {
+    async fn find_totp(&self, user_id: &str) -> Result<Option<TotpFactor>, RepositoryError> {
+        Ok(self.totp.read().await.get(user_id).cloned())
+    }
+
+    async fn save_totp(&self, factor: TotpFactor) -> Result<(), RepositoryError> {
+        self.totp
+            .write()
+            .await
+            .insert(factor.user_id.clone(), factor);
+        Ok(())
+    }
+
+    async fn mark_totp_used(&self, user_id: &str, step: i64) -> Result<bool, RepositoryError> {
+        let mut totp = self.totp.write().await;
+
+        match totp.get_mut(user_id) {
+            Some(factor) if factor.last_used_step.is_none_or(|last| last < step) => {
+                factor.last_used_step = Some(step);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError> {
+        self.totp.write().await.remove(user_id);
+        self.recovery_codes.write().await.remove(user_id);
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: &str,
+        code_hashes: Vec<String>,
+    ) -> Result<(), RepositoryError> {
+        self.recovery_codes
+            .write()
+            .await
+            .insert(user_id.to_string(), code_hashes.into_iter().collect());
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_id: &str,
+        code_hash: &str,
+    ) -> Result<bool, RepositoryError> {
+        Ok(self
+            .recovery_codes
+            .write()
+            .await
+            .get_mut(user_id)
+            .is_some_and(|codes| codes.remove(code_hash)))
+    }
+
+    async fn count_recovery_codes(&self, user_id: &str) -> Result<i64, RepositoryError> {
+        Ok(self
+            .recovery_codes
+            .read()
+            .await
+            .get(user_id)
+            .map_or(0, |codes| codes.len() as i64))
+    }
+}
@@ -0,0 +1,216 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::models::User;
+
+use super::{RepositoryError, UserFilter, UserPage, UserRepository};
+
+#[derive(Default)]
+struct Store {
+    users: HashMap<String, User>,
+    // lowercased email -> user id
+    emails: HashMap<String, String>,
+}
+
+/// Keeps every user in process memory, all data is lost on restart.
+#[derive(Default)]
+pub struct MemoryUserRepository {
+    store: RwLock<Store>,
+}
+
+impl MemoryUserRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn email_conflict() -> RepositoryError {
+    RepositoryError::Conflict("Email already exist".to_string())
+}
+
+#[async_trait]
+impl UserRepository for MemoryUserRepository {
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError> {
+        Ok(self.store.read().await.users.get(id).cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        let store = self.store.read().await;
+        Ok(store
+            .emails
+            .get(&email.to_lowercase())
+            .and_then(|id| store.users.get(id))
+            .cloned())
+    }
+
+    async fn insert(&self, mut user: User) -> Result<User, RepositoryError> {
+        let mut store = self.store.write().await;
+
+        user.email = user.email.to_lowercase();
+        if store.emails.contains_key(&user.email) {
+            return Err(email_conflict());
+        }
+
+        let id = user
+            .id
+            .get_or_insert_with(|| Uuid::new_v4().to_string())
+            .clone();
+        if store.users.contains_key(&id) {
+            return Err(RepositoryError::Conflict("User already exist".to_string()));
+        }
+
+        store.emails.insert(user.email.clone(), id.clone());
+        store.users.insert(id, user.clone());
+        Ok(user)
+    }
+
+    async fn update(&self, mut user: User) -> Result<User, RepositoryError> {
+        let mut store = self.store.write().await;
+
+        let id = user.id.clone().ok_or(RepositoryError::NotFound)?;
+        let old_email = match store.users.get(&id) {
+            Some(existing) => existing.email.clone(),
+            None => return Err(RepositoryError::NotFound),
+        };
+
+        user.email = user.email.to_lowercase();
+        if user.email != old_email {
+            if store.emails.contains_key(&user.email) {
+                return Err(email_conflict());
+            }
+            store.emails.remove(&old_email);
+            store.emails.insert(user.email.clone(), id.clone());
+        }
+
+        store.users.insert(id, user.clone());
+        Ok(user)
+    }
+
+    async fn replace_password(
+        &self,
+        id: &str,
+        current: &str,
+        new: &str,
+    ) -> Result<bool, RepositoryError> {
+        let mut store = self.store.write().await;
+
+        match store.users.get_mut(id) {
+            Some(user) if user.password == current => {
+                user.password = new.to_string();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn set_role(&self, id: &str, role: &str) -> Result<(), RepositoryError> {
+        let mut store = self.store.write().await;
+
+        let user = store.users.get_mut(id).ok_or(RepositoryError::NotFound)?;
+        user.role = role.to_string();
+        user.updatedAt = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn set_suspended(&self, id: &str, suspended: bool) -> Result<(), RepositoryError> {
+        let mut store = self.store.write().await;
+
+        let user = store.users.get_mut(id).ok_or(RepositoryError::NotFound)?;
+        user.suspended = suspended;
+        user.updatedAt = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn mark_verification_sent(
+        &self,
+        id: &str,
+        sent_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let mut store = self.store.write().await;
+
+        let user = store.users.get_mut(id).ok_or(RepositoryError::NotFound)?;
+        user.verificationSentAt = Some(sent_at);
+        Ok(())
+    }
+
+    async fn mark_verified(&self, id: &str, email: &str) -> Result<bool, RepositoryError> {
+        let mut store = self.store.write().await;
+
+        match store.users.get_mut(id) {
+            Some(user) if user.email == email => {
+                user.verified = true;
+                user.updatedAt = Some(Utc::now());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn change_email(&self, id: &str, email: &str) -> Result<(), RepositoryError> {
+        let mut store = self.store.write().await;
+
+        let email = email.to_lowercase();
+        let old_email = match store.users.get(id) {
+            Some(existing) => existing.email.clone(),
+            None => return Err(RepositoryError::NotFound),
+        };
+        if email != old_email {
+            if store.emails.contains_key(&email) {
+                return Err(email_conflict());
+            }
+            store.emails.remove(&old_email);
+            store.emails.insert(email.clone(), id.to_string());
+        }
+
+        let user = store.users.get_mut(id).ok_or(RepositoryError::NotFound)?;
+        user.email = email;
+        user.verified = true;
+        user.updatedAt = Some(Utc::now());
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        let mut store = self.store.write().await;
+
+        let user = store.users.remove(id).ok_or(RepositoryError::NotFound)?;
+        store.emails.remove(&user.email);
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        filter: &UserFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<UserPage, RepositoryError> {
+        let store = self.store.read().await;
+        let search = filter.search.as_ref().map(|search| search.to_lowercase());
+
+        let mut users: Vec<User> = store
+            .users
+            .values()
+            .filter(|user| filter.provider.as_ref().is_none_or(|p| &user.provider == p))
+            .filter(|user| filter.role.as_ref().is_none_or(|r| &user.role == r))
+            .filter(|user| filter.verified.is_none_or(|v| user.verified == v))
+            .filter(|user| filter.suspended.is_none_or(|s| user.suspended == s))
+            .filter(|user| {
+                search.as_ref().is_none_or(|search| {
+                    user.name.to_lowercase().contains(search) || user.email.contains(search)
+                })
+            })
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| (a.createdAt, &a.id).cmp(&(b.createdAt, &b.id)));
+
+        let total = users.len() as i64;
+        let users = users
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .collect();
+        Ok(UserPage { users, total })
+    }
+}
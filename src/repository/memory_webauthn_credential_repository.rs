@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+use chrono::prelude::*;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+use crate::models::WebauthnCredential;
+
+use super::{RepositoryError, WebauthnCredentialRepository};
+
+/// Keeps WebAuthn credentials in process memory, they are lost on restart.
+#[derive(Default)]
+pub struct MemoryWebauthnCredentialRepository {
+    // credential id -> credential
+    credentials: RwLock<HashMap<String, WebauthnCredential>>,
+}
+
+impl MemoryWebauthnCredentialRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl WebauthnCredentialRepository for MemoryWebauthnCredentialRepository {
+    async fn find(
+        &self,
+        credential_id: &str,
+    ) -> Result<Option<WebauthnCredential>, RepositoryError> {
+        Ok(self.credentials.read().await.get(credential_id).cloned())
+    }
+
+    async fn list_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<WebauthnCredential>, RepositoryError> {
+        let mut credentials: Vec<WebauthnCredential> = self
+            .credentials
+            .read()
+            .await
+            .values()
+            .filter(|credential| credential.user_id == user_id)
+            .cloned()
+            .collect();
+        credentials.sort_by_key(|credential| credential.created_at);
+        Ok(credentials)
+    }
+
+    async fn insert(&self, credential: WebauthnCredential) -> Result<(), RepositoryError> {
+        let mut credentials = self.credentials.write().await;
+
+        if credentials.contains_key(&credential.credential_id) {
+            return Err(RepositoryError::Conflict(
+                "Credential already registered".to_string(),
+            ));
+        }
+        credentials.insert(credential.credential_id.clone(), credential);
+        Ok(())
+    }
+
+    async fn mark_used(
+        &self,
+        credential_id: &str,
+        passkey: &str,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let mut credentials = self.credentials.write().await;
+
+        let credential = credentials
+            .get_mut(credential_id)
+            .ok_or(RepositoryError::NotFound)?;
+        credential.passkey = passkey.to_string();
+        credential.last_used_at = Some(used_at);
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str, credential_id: &str) -> Result<(), RepositoryError> {
+        let mut credentials = self.credentials.write().await;
+
+        match credentials.get(credential_id) {
+            Some(credential) if credential.user_id == user_id => {
+                credentials.remove(credential_id);
+                Ok(())
+            }
+            _ => Err(RepositoryError::NotFound),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+pub mod database;
+pub mod identity_repository;
+pub mod login_attempt_repository;
+pub mod memory_identity_repository;
+pub mod memory_login_attempt_repository;
+pub mod memory_one_time_token_repository;
+pub mod memory_refresh_token_repository;
+pub mod memory_token_revocation_repository;
+pub mod memory_two_factor_repository;
+pub mod memory_user_repository;
+pub mod memory_webauthn_credential_repository;
+pub mod one_time_token_repository;
+pub mod postgres_identity_repository;
+pub mod postgres_login_attempt_repository;
+pub mod postgres_one_time_token_repository;
+pub mod postgres_refresh_token_repository;
+pub mod postgres_token_revocation_repository;
+pub mod postgres_two_factor_repository;
+pub mod postgres_user_repository;
+pub mod postgres_webauthn_credential_repository;
+pub mod refresh_token_repository;
+pub mod repository_error;
+pub mod sqlite_identity_repository;
+pub mod sqlite_login_attempt_repository;
+pub mod sqlite_one_time_token_repository;
+pub mod sqlite_refresh_token_repository;
+pub mod sqlite_token_revocation_repository;
+pub mod sqlite_two_factor_repository;
+pub mod sqlite_user_repository;
+pub mod sqlite_webauthn_credential_repository;
+pub mod token_revocation_repository;
+pub mod two_factor_repository;
+pub mod user_repository;
+mod user_row;
+pub mod webauthn_credential_repository;
+
+// Re-export for easier use
+pub use database::Database;
+pub use identity_repository::IdentityRepository;
+pub use login_attempt_repository::LoginAttemptRepository;
+pub use memory_identity_repository::MemoryIdentityRepository;
+pub use memory_login_attempt_repository::MemoryLoginAttemptRepository;
+pub use memory_one_time_token_repository::MemoryOneTimeTokenRepository;
+pub use memory_refresh_token_repository::MemoryRefreshTokenRepository;
+pub use memory_token_revocation_repository::MemoryTokenRevocationRepository;
+pub use memory_two_factor_repository::MemoryTwoFactorRepository;
+pub use memory_user_repository::MemoryUserRepository;
+pub use memory_webauthn_credential_repository::MemoryWebauthnCredentialRepository;
+pub use one_time_token_repository::OneTimeTokenRepository;
+pub use postgres_identity_repository::PostgresIdentityRepository;
+pub use postgres_login_attempt_repository::PostgresLoginAttemptRepository;
+pub use postgres_one_time_token_repository::PostgresOneTimeTokenRepository;
+pub use postgres_refresh_token_repository::PostgresRefreshTokenRepository;
+pub use postgres_token_revocation_repository::PostgresTokenRevocationRepository;
+pub use postgres_two_factor_repository::PostgresTwoFactorRepository;
+pub use postgres_user_repository::PostgresUserRepository;
+pub use postgres_webauthn_credential_repository::PostgresWebauthnCredentialRepository;
+pub use refresh_token_repository::RefreshTokenRepository;
+pub use repository_error::RepositoryError;
+pub use sqlite_identity_repository::SqliteIdentityRepository;
+pub use sqlite_login_attempt_repository::SqliteLoginAttemptRepository;
+pub use sqlite_one_time_token_repository::SqliteOneTimeTokenRepository;
+pub use sqlite_refresh_token_repository::SqliteRefreshTokenRepository;
+pub use sqlite_token_revocation_repository::SqliteTokenRevocationRepository;
+pub use sqlite_two_factor_repository::SqliteTwoFactorRepository;
+pub use sqlite_user_repository::SqliteUserRepository;
+pub use sqlite_webauthn_credential_repository::SqliteWebauthnCredentialRepository;
+pub use token_revocation_repository::TokenRevocationRepository;
+pub use two_factor_repository::TwoFactorRepository;
+pub use user_repository::{UserFilter, UserPage, UserRepository};
+pub use webauthn_credential_repository::WebauthnCredentialRepository;
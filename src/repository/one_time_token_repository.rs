@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+
+use crate::models::OneTimeToken;
+
+use super::RepositoryError;
+
+#[async_trait]
+pub trait OneTimeTokenRepository: Send + Sync {
+    async fn insert(&self, token: OneTimeToken) -> Result<(), RepositoryError>;
+    /// Deletes and returns the token if it was issued for `purpose`, so it can only be redeemed
+    /// once even by concurrent requests. The caller still has to check `expires_at`.
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+    ) -> Result<Option<OneTimeToken>, RepositoryError>;
+    /// Deletes every outstanding token of the user for `purpose`.
+    async fn revoke_user(&self, user_id: &str, purpose: &str) -> Result<(), RepositoryError>;
+    /// Deletes tokens past their expiry, returns how many were removed.
+    async fn purge_expired(&self) -> Result<u64, RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{models::User, repository::Database};
+
+    async fn backends() -> Vec<Arc<dyn OneTimeTokenRepository>> {
+        let mut repos = Vec::new();
+        for database in Database::for_tests().await {
+            database
+                .user_repository()
+                .insert(User::for_tests("user-1", "alice@example.com"))
+                .await
+                .unwrap();
+            repos.push(database.one_time_token_repository());
+        }
+        repos
+    }
+
+    fn token(hash: &str, purpose: &str, expires_in: Duration) -> OneTimeToken {
+        let now = Utc::now();
+        OneTimeToken {
+            token_hash: hash.to_string(),
+            purpose: purpose.to_string(),
+            user_id: Some("user-1".to_string()),
+            email: "alice@example.com".to_string(),
+            payload: None,
+            created_at: now,
+            expires_at: now + expires_in,
+        }
+    }
+
+    #[actix_web::test]
+    async fn tokens_are_consumed_once_and_only_for_their_purpose() {
+        for repo in backends().await {
+            repo.insert(token("hash-1", "password-reset", Duration::minutes(5)))
+                .await
+                .unwrap();
+
+            assert!(repo
+                .consume("hash-1", "magic-link")
+                .await
+                .unwrap()
+                .is_none());
+            let stored = repo.consume("hash-1", "password-reset").await.unwrap();
+            assert_eq!(stored.unwrap().email, "alice@example.com");
+            assert!(repo
+                .consume("hash-1", "password-reset")
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[actix_web::test]
+    async fn the_payload_comes_back_with_the_token() {
+        for repo in backends().await {
+            let mut ceremony = token("hash-1", "webauthn-login", Duration::minutes(5));
+            ceremony.payload = Some(r#"{"challenge":"abc"}"#.to_string());
+            repo.insert(ceremony).await.unwrap();
+
+            let stored = repo.consume("hash-1", "webauthn-login").await.unwrap();
+            assert_eq!(
+                stored.unwrap().payload.as_deref(),
+                Some(r#"{"challenge":"abc"}"#)
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn revoke_user_only_drops_the_given_purpose() {
+        for repo in backends().await {
+            repo.insert(token("hash-1", "password-reset", Duration::minutes(5)))
+                .await
+                .unwrap();
+            repo.insert(token("hash-2", "magic-link", Duration::minutes(5)))
+                .await
+                .unwrap();
+
+            repo.revoke_user("user-1", "password-reset").await.unwrap();
+            assert!(repo
+                .consume("hash-1", "password-reset")
+                .await
+                .unwrap()
+                .is_none());
+            assert!(repo
+                .consume("hash-2", "magic-link")
+                .await
+                .unwrap()
+                .is_some());
+        }
+    }
+
+    #[actix_web::test]
+    async fn purge_drops_expired_tokens() {
+        for repo in backends().await {
+            repo.insert(token("old", "password-reset", -Duration::minutes(1)))
+                .await
+                .unwrap();
+            repo.insert(token("fresh", "password-reset", Duration::minutes(5)))
+                .await
+                .unwrap();
+
+            assert_eq!(repo.purge_expired().await.unwrap(), 1);
+            assert!(repo
+                .consume("fresh", "password-reset")
+                .await
+                .unwrap()
+                .is_some());
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::models::OneTimeToken;
+
+use super::{OneTimeTokenRepository, RepositoryError};
+
+/// Persists one-time token hashes in PostgreSQL, see `migrations/postgres`.
+pub struct PostgresOneTimeTokenRepository {
+    pool: PgPool,
+}
+
+impl PostgresOneTimeTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl OneTimeTokenRepository for PostgresOneTimeTokenRepository {
+    async fn insert(&self, token: OneTimeToken) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO one_time_tokens (token_hash, purpose, user_id, email, payload, created_at, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(&token.token_hash)
+        .bind(&token.purpose)
+        .bind(&token.user_id)
+        .bind(&token.email)
+        .bind(&token.payload)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+    ) -> Result<Option<OneTimeToken>, RepositoryError> {
+        let token = sqlx::query_as::<_, OneTimeToken>(
+            "DELETE FROM one_time_tokens WHERE token_hash = $1 AND purpose = $2 RETURNING *",
+        )
+        .bind(token_hash)
+        .bind(purpose)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    async fn revoke_user(&self, user_id: &str, purpose: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM one_time_tokens WHERE user_id = $1 AND purpose = $2")
+            .bind(user_id)
+            .bind(purpose)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, RepositoryError> {
+        let result = sqlx::query("DELETE FROM one_time_tokens WHERE expires_at <= $1")
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,75 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::models::TokenClaims;
+
+use super::{RepositoryError, TokenRevocationRepository};
+
+/// Persists the revocation list in PostgreSQL, see `migrations/postgres`.
+pub struct PostgresTokenRevocationRepository {
+    pool: PgPool,
+}
+
+impl PostgresTokenRevocationRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TokenRevocationRepository for PostgresTokenRevocationRepository {
+    async fn revoke(&self, jti: &str, expires_at: i64) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO revoked_tokens (jti, expires_at) VALUES ($1, $2) ON CONFLICT (jti) DO NOTHING",
+        )
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn revoke_user(
+        &self,
+        user_id: &str,
+        issued_before: i64,
+        expires_at: i64,
+    ) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO revoked_sessions (user_id, issued_before, expires_at) VALUES ($1, $2, $3)
+             ON CONFLICT (user_id) DO UPDATE
+             SET issued_before = excluded.issued_before, expires_at = excluded.expires_at",
+        )
+        .bind(user_id)
+        .bind(issued_before)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn is_revoked(&self, claims: &TokenClaims) -> Result<bool, RepositoryError> {
+        let revoked: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $1)
+                 OR EXISTS (SELECT 1 FROM revoked_sessions WHERE user_id = $2 AND issued_before >= $3)",
+        )
+        .bind(&claims.jti)
+        .bind(&claims.sub)
+        .bind(claims.iat as i64)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(revoked)
+    }
+
+    async fn purge_expired(&self, now: i64) -> Result<u64, RepositoryError> {
+        let tokens = sqlx::query("DELETE FROM revoked_tokens WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        let sessions = sqlx::query("DELETE FROM revoked_sessions WHERE expires_at <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(tokens.rows_affected() + sessions.rows_affected())
+    }
+}
@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use crate::models::TotpFactor;
+
+use super::{RepositoryError, TwoFactorRepository};
+
+/// Persists second factors in PostgreSQL, see `migrations/postgres`.
+pub struct PostgresTwoFactorRepository {
+    pool: PgPool,
+}
+
+impl PostgresTwoFactorRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepository for PostgresTwoFactorRepository {
+    async fn find_totp(&self, user_id: &str) -> Result<Option<TotpFactor>, RepositoryError> {
+        let factor =
+            sqlx::query_as::<_, TotpFactor>("SELECT * FROM totp_factors WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(factor)
+    }
+
+    async fn save_totp(&self, factor: TotpFactor) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO totp_factors (user_id, secret, enabled, last_used_step, created_at)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (user_id) DO UPDATE
+             SET secret = excluded.secret, enabled = excluded.enabled,
+                 last_used_step = excluded.last_used_step, created_at = excluded.created_at",
+        )
+        .bind(&factor.user_id)
+        .bind(&factor.secret)
+        .bind(factor.enabled)
+        .bind(factor.last_used_step)
+        .bind(factor.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn mark_totp_used(&self, user_id: &str, step: i64) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE totp_factors SET last_used_step = $1
+             WHERE user_id = $2 AND (last_used_step IS NULL OR last_used_step < $1)",
+        )
+        .bind(step)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM totp_factors WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        user_id: &str,
+        code_hashes: Vec<String>,
+    ) -> Result<(), RepositoryError> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        for code_hash in code_hashes {
+            sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_id: &str,
+        code_hash: &str,
+    ) -> Result<bool, RepositoryError> {
+        let result =
+            sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1 AND code_hash = $2")
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn count_recovery_codes(&self, user_id: &str) -> Result<i64, RepositoryError> {
+        let count =
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM recovery_codes WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(count)
+    }
+}
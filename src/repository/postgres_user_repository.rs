@@ -0,0 +1,266 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use crate::models::User;
+
+use super::{
+    repository_error::is_unique_violation, user_repository::like_pattern, user_row::UserRow,
+    RepositoryError, UserFilter, UserPage, UserRepository,
+};
+
+/// Persists users in PostgreSQL, see `migrations/postgres` for the schema.
+pub struct PostgresUserRepository {
+    pool: PgPool,
+}
+
+impl PostgresUserRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn push_filter<'a>(query: &mut QueryBuilder<'a, Postgres>, filter: &'a UserFilter) {
+    query.push(" WHERE 1 = 1");
+    if let Some(provider) = &filter.provider {
+        query.push(" AND provider = ").push_bind(provider);
+    }
+    if let Some(role) = &filter.role {
+        query.push(" AND role = ").push_bind(role);
+    }
+    if let Some(verified) = filter.verified {
+        query.push(" AND verified = ").push_bind(verified);
+    }
+    if let Some(suspended) = filter.suspended {
+        query.push(" AND suspended = ").push_bind(suspended);
+    }
+    if let Some(search) = &filter.search {
+        let pattern = like_pattern(search);
+        query
+            .push(" AND (name ILIKE ")
+            .push_bind(pattern.clone())
+            .push(" ESCAPE '\\' OR email ILIKE ")
+            .push_bind(pattern)
+            .push(" ESCAPE '\\')");
+    }
+}
+
+fn map_write_error(err: sqlx::Error) -> RepositoryError {
+    if is_unique_violation(&err, "users_email_lower_idx") {
+        RepositoryError::Conflict("Email already exist".to_string())
+    } else {
+        err.into()
+    }
+}
+
+#[async_trait]
+impl UserRepository for PostgresUserRepository {
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError> {
+        let user = sqlx::query_as::<_, UserRow>("SELECT * FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(user.map(User::from))
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError> {
+        let user =
+            sqlx::query_as::<_, UserRow>("SELECT * FROM users WHERE LOWER(email) = LOWER($1)")
+                .bind(email)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(user.map(User::from))
+    }
+
+    async fn insert(&self, mut user: User) -> Result<User, RepositoryError> {
+        let now = Utc::now();
+        user.email = user.email.to_lowercase();
+        user.id.get_or_insert_with(|| Uuid::new_v4().to_string());
+        user.createdAt.get_or_insert(now);
+        user.updatedAt.get_or_insert(now);
+
+        // The unique index on LOWER(email) makes concurrent registrations safe,
+        // the loser gets a unique violation instead of a duplicated account.
+        sqlx::query(
+            "INSERT INTO users (id, name, email, password, role, photo, verified, suspended, provider, verification_sent_at, created_at, updated_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)",
+        )
+        .bind(&user.id)
+        .bind(&user.name)
+        .bind(&user.email)
+        .bind(&user.password)
+        .bind(&user.role)
+        .bind(&user.photo)
+        .bind(user.verified)
+        .bind(user.suspended)
+        .bind(&user.provider)
+        .bind(user.verificationSentAt)
+        .bind(user.createdAt)
+        .bind(user.updatedAt)
+        .execute(&self.pool)
+        .await
+        .map_err(map_write_error)?;
+
+        Ok(user)
+    }
+
+    async fn update(&self, mut user: User) -> Result<User, RepositoryError> {
+        let id = user.id.clone().ok_or(RepositoryError::NotFound)?;
+        user.email = user.email.to_lowercase();
+
+        let result = sqlx::query(
+            "UPDATE users
+             SET name = $1, email = $2, password = $3, role = $4, photo = $5, verified = $6, suspended = $7, provider = $8, verification_sent_at = $9, updated_at = $10
+             WHERE id = $11",
+        )
+        .bind(&user.name)
+        .bind(&user.email)
+        .bind(&user.password)
+        .bind(&user.role)
+        .bind(&user.photo)
+        .bind(user.verified)
+        .bind(user.suspended)
+        .bind(&user.provider)
+        .bind(user.verificationSentAt)
+        .bind(user.updatedAt.unwrap_or_else(Utc::now))
+        .bind(&id)
+        .execute(&self.pool)
+        .await
+        .map_err(map_write_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(user)
+    }
+
+    async fn replace_password(
+        &self,
+        id: &str,
+        current: &str,
+        new: &str,
+    ) -> Result<bool, RepositoryError> {
+        let result = sqlx::query("UPDATE users SET password = $1 WHERE id = $2 AND password = $3")
+            .bind(new)
+            .bind(id)
+            .bind(current)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_role(&self, id: &str, role: &str) -> Result<(), RepositoryError> {
+        let result = sqlx::query("UPDATE users SET role = $1, updated_at = $2 WHERE id = $3")
+            .bind(role)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn set_suspended(&self, id: &str, suspended: bool) -> Result<(), RepositoryError> {
+        let result = sqlx::query("UPDATE users SET suspended = $1, updated_at = $2 WHERE id = $3")
+            .bind(suspended)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn mark_verification_sent(
+        &self,
+        id: &str,
+        sent_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let result = sqlx::query("UPDATE users SET verification_sent_at = $1 WHERE id = $2")
+            .bind(sent_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn mark_verified(&self, id: &str, email: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE users SET verified = TRUE, updated_at = $1 WHERE id = $2 AND email = $3",
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .bind(email)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn change_email(&self, id: &str, email: &str) -> Result<(), RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE users SET email = $1, verified = TRUE, updated_at = $2 WHERE id = $3",
+        )
+        .bind(email.to_lowercase())
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(map_write_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError> {
+        let result = sqlx::query("DELETE FROM users WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        filter: &UserFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<UserPage, RepositoryError> {
+        let mut count = QueryBuilder::<Postgres>::new("SELECT COUNT(*) FROM users");
+        push_filter(&mut count, filter);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.pool).await?;
+
+        let mut select = QueryBuilder::<Postgres>::new("SELECT * FROM users");
+        push_filter(&mut select, filter);
+        select
+            .push(" ORDER BY created_at, id LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        let users = select
+            .build_query_as::<UserRow>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(UserPage {
+            users: users.into_iter().map(User::from).collect(),
+            total,
+        })
+    }
+}
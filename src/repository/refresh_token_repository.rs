@@ -0,0 +1,112 @@
+use async_trait::async_trait;
+
+use crate::models::RefreshToken;
+
+use super::RepositoryError;
+
+#[async_trait]
+pub trait RefreshTokenRepository: Send + Sync {
+    async fn insert(&self, token: RefreshToken) -> Result<(), RepositoryError>;
+    async fn find_by_hash(&self, token_hash: &str)
+        -> Result<Option<RefreshToken>, RepositoryError>;
+    /// Marks a live token as rotated out, returns `false` if it was already
+    /// rotated or revoked so that concurrent refreshes can't both succeed.
+    async fn mark_rotated(&self, token_hash: &str) -> Result<bool, RepositoryError>;
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RepositoryError>;
+    async fn revoke_user(&self, user_id: &str) -> Result<(), RepositoryError>;
+    /// Deletes tokens past their expiry, returns how many were removed.
+    async fn purge_expired(&self) -> Result<u64, RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use std::sync::Arc;
+    use tokio::task::JoinSet;
+
+    use super::*;
+    use crate::{models::User, repository::Database};
+
+    async fn backends() -> Vec<Arc<dyn RefreshTokenRepository>> {
+        let mut repos = Vec::new();
+        for database in Database::for_tests().await {
+            database
+                .user_repository()
+                .insert(User::for_tests("user-1", "alice@example.com"))
+                .await
+                .unwrap();
+            repos.push(database.refresh_token_repository());
+        }
+        repos
+    }
+
+    fn token(hash: &str, family_id: &str) -> RefreshToken {
+        let now = Utc::now();
+        RefreshToken {
+            token_hash: hash.to_string(),
+            family_id: family_id.to_string(),
+            user_id: "user-1".to_string(),
+            created_at: now,
+            expires_at: now + Duration::days(1),
+            rotated_at: None,
+            revoked_at: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn tokens_are_found_by_hash_and_never_stored_twice() {
+        for repo in backends().await {
+            repo.insert(token("hash-1", "family-1")).await.unwrap();
+
+            let stored = repo.find_by_hash("hash-1").await.unwrap().unwrap();
+            assert_eq!(stored.family_id, "family-1");
+            assert!(stored.rotated_at.is_none() && stored.revoked_at.is_none());
+            assert!(repo.find_by_hash("hash-2").await.unwrap().is_none());
+
+            let err = repo.insert(token("hash-1", "family-2")).await.unwrap_err();
+            assert!(matches!(err, RepositoryError::Conflict(_)), "{:?}", err);
+        }
+    }
+
+    #[actix_web::test]
+    async fn only_one_of_concurrent_rotations_wins() {
+        for repo in backends().await {
+            repo.insert(token("hash-1", "family-1")).await.unwrap();
+
+            let mut rotations = JoinSet::new();
+            for _ in 0..8 {
+                let repo = repo.clone();
+                rotations.spawn(async move { repo.mark_rotated("hash-1").await.unwrap() });
+            }
+            let mut won = 0;
+            while let Some(rotated) = rotations.join_next().await {
+                won += rotated.unwrap() as usize;
+            }
+            assert_eq!(won, 1);
+
+            let stored = repo.find_by_hash("hash-1").await.unwrap().unwrap();
+            assert!(stored.rotated_at.is_some());
+            assert!(!repo.mark_rotated("unknown").await.unwrap());
+        }
+    }
+
+    #[actix_web::test]
+    async fn revoking_a_family_leaves_other_families_alone() {
+        for repo in backends().await {
+            repo.insert(token("hash-1", "family-1")).await.unwrap();
+            repo.insert(token("hash-2", "family-1")).await.unwrap();
+            repo.insert(token("hash-3", "family-2")).await.unwrap();
+
+            repo.revoke_family("family-1").await.unwrap();
+
+            for hash in ["hash-1", "hash-2"] {
+                let stored = repo.find_by_hash(hash).await.unwrap().unwrap();
+                assert!(stored.revoked_at.is_some(), "{}", hash);
+                // A revoked token can't be rotated anymore
+                assert!(!repo.mark_rotated(hash).await.unwrap());
+            }
+            let other = repo.find_by_hash("hash-3").await.unwrap().unwrap();
+            assert!(other.revoked_at.is_none());
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// A unique constraint (e.g. the user email) was violated.
+    Conflict(String),
+    NotFound,
+    Backend(String),
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::Conflict(message) => write!(f, "{}", message),
+            RepositoryError::NotFound => write!(f, "Record not found"),
+            RepositoryError::Backend(message) => write!(f, "Storage error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<sqlx::Error> for RepositoryError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::RowNotFound => RepositoryError::NotFound,
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                RepositoryError::Conflict("Record already exist".to_string())
+            }
+            _ => RepositoryError::Backend(err.to_string()),
+        }
+    }
+}
+
+/// Whether `err` violates the unique constraint `name`.
+///
+/// PostgreSQL reports the constraint or index name (`users_email_lower_idx`), SQLite
+/// only the columns it covers (`users.email`), so pass whichever the backend uses.
+pub fn is_unique_violation(err: &sqlx::Error, name: &str) -> bool {
+    match err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            db_err.constraint() == Some(name)
+                || db_err
+                    .message()
+                    .strip_suffix(name)
+                    .is_some_and(|rest| rest.ends_with(": "))
+        }
+        _ => false,
+    }
+}
+
+impl ResponseError for RepositoryError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RepositoryError::Conflict(_) => StatusCode::CONFLICT,
+            RepositoryError::NotFound => StatusCode::NOT_FOUND,
+            RepositoryError::Backend(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            RepositoryError::Backend(message) => {
+                // Never leak storage details to the client
+                log::error!("{}", message);
+                HttpResponse::InternalServerError().json(
+                    serde_json::json!({"status": "error", "message": "Internal Server Error"}),
+                )
+            }
+            _ => HttpResponse::build(self.status_code())
+                .json(serde_json::json!({"status": "fail", "message": self.to_string()})),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+use crate::models::Identity;
+
+use super::{IdentityRepository, RepositoryError};
+
+/// Persists linked identities in SQLite, see `migrations/sqlite`.
+pub struct SqliteIdentityRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteIdentityRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_write_error(err: sqlx::Error) -> RepositoryError {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            RepositoryError::Conflict("Identity already linked".to_string())
+        }
+        _ => err.into(),
+    }
+}
+
+#[async_trait]
+impl IdentityRepository for SqliteIdentityRepository {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<Identity>, RepositoryError> {
+        let identity = sqlx::query_as::<_, Identity>(
+            "SELECT * FROM identities WHERE provider = ? AND subject = ?",
+        )
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(identity)
+    }
+
+    async fn list_for_user(&self, user_id: &str) -> Result<Vec<Identity>, RepositoryError> {
+        let identities = sqlx::query_as::<_, Identity>(
+            "SELECT * FROM identities WHERE user_id = ? ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(identities)
+    }
+
+    async fn insert(&self, identity: Identity) -> Result<Identity, RepositoryError> {
+        sqlx::query(
+            "INSERT INTO identities (provider, subject, user_id, email, created_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&identity.provider)
+        .bind(&identity.subject)
+        .bind(&identity.user_id)
+        .bind(&identity.email)
+        .bind(identity.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(map_write_error)?;
+        Ok(identity)
+    }
+
+    async fn delete(&self, user_id: &str, provider: &str) -> Result<(), RepositoryError> {
+        let result = sqlx::query("DELETE FROM identities WHERE user_id = ? AND provider = ?")
+            .bind(user_id)
+            .bind(provider)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+use crate::models::{AttemptLimits, LoginAttempts};
+
+use super::{LoginAttemptRepository, RepositoryError};
+
+/// Persists failed login counters in SQLite, see `migrations/sqlite`.
+pub struct SqliteLoginAttemptRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteLoginAttemptRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepository for SqliteLoginAttemptRepository {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempts>, RepositoryError> {
+        let attempts = sqlx::query_as::<_, LoginAttempts>(
+            "SELECT key, failures, last_failure_at, locked_until FROM login_attempts WHERE key = ?",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(attempts)
+    }
+
+    async fn reserve(
+        &self,
+        key: &str,
+        now: i64,
+        limits: &AttemptLimits,
+    ) -> Result<Option<LoginAttempts>, RepositoryError> {
+        // A single statement, so concurrent attempts are counted one by one. `previous` is the
+        // number of attempts before this one that still count
+        let previous =
+            "CASE WHEN login_attempts.last_failure_at <= ?3 THEN 0 ELSE login_attempts.failures END";
+        let query = format!(
+            "INSERT INTO login_attempts (key, failures, last_failure_at, locked_until)
+             VALUES (?1, 1, ?2, CASE WHEN 0 >= ?4 THEN ?5 WHEN ?6 > 0 THEN ?2 + MIN(?6, ?7) END)
+             ON CONFLICT (key) DO UPDATE
+             SET failures = {previous} + 1,
+                 last_failure_at = excluded.last_failure_at,
+                 locked_until = CASE WHEN {previous} >= ?4 THEN ?5
+                                     WHEN ?6 > 0 THEN ?2 + MIN(?6 << MIN({previous}, 30), ?7) END
+             WHERE login_attempts.locked_until IS NULL OR login_attempts.locked_until <= ?2
+             RETURNING key, failures, last_failure_at, locked_until"
+        );
+        let attempts = sqlx::query_as::<_, LoginAttempts>(&query)
+            .bind(key)
+            .bind(now)
+            .bind(now - limits.lockout)
+            .bind(limits.max_attempts)
+            .bind(now + limits.lockout)
+            .bind(limits.backoff_base)
+            .bind(limits.backoff_max)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(attempts)
+    }
+
+    async fn release(&self, key: &str) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE login_attempts SET failures = failures - 1 WHERE key = ? AND failures > 0",
+        )
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query("DELETE FROM login_attempts WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn purge_expired(&self, before: i64, now: i64) -> Result<u64, RepositoryError> {
+        let result = sqlx::query(
+            "DELETE FROM login_attempts
+             WHERE last_failure_at < ? AND (locked_until IS NULL OR locked_until <= ?)",
+        )
+        .bind(before)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::models::OneTimeToken;
+
+use super::{OneTimeTokenRepository, RepositoryError};
+
+/// Persists one-time token hashes in SQLite, see `migrations/sqlite`.
+pub struct SqliteOneTimeTokenRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteOneTimeTokenRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl OneTimeTokenRepository for SqliteOneTimeTokenRepository {
+    async fn insert(&self, token: OneTimeToken) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO one_time_tokens (token_hash, purpose, user_id, email, payload, created_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&token.token_hash)
+        .bind(&token.purpose)
+        .bind(&token.user_id)
+        .bind(&token.email)
+        .bind(&token.payload)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn consume(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+    ) -> Result<Option<OneTimeToken>, RepositoryError> {
+        let token = sqlx::query_as::<_, OneTimeToken>(
+            "DELETE FROM one_time_tokens WHERE token_hash = ? AND purpose = ? RETURNING *",
+        )
+        .bind(token_hash)
+        .bind(purpose)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    async fn revoke_user(&self, user_id: &str, purpose: &str) -> Result<(), RepositoryError> {
+        sqlx::query("DELETE FROM one_time_tokens WHERE user_id = ? AND purpose = ?")
+            .bind(user_id)
+            .bind(purpose)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, RepositoryError> {
+        let result = sqlx::query("DELETE FROM one_time_tokens WHERE expires_at <= ?")
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
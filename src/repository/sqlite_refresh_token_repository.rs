@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use sqlx::SqlitePool;
+
+use crate::models::RefreshToken;
+
+use super::{RefreshTokenRepository, RepositoryError};
+
+/// Persists refresh token hashes in SQLite, see `migrations/sqlite`.
+pub struct SqliteRefreshTokenRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteRefreshTokenRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for SqliteRefreshTokenRepository {
+    async fn insert(&self, token: RefreshToken) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens (token_hash, family_id, user_id, created_at, expires_at, rotated_at, revoked_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&token.token_hash)
+        .bind(&token.family_id)
+        .bind(&token.user_id)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .bind(token.rotated_at)
+        .bind(token.revoked_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn find_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> Result<Option<RefreshToken>, RepositoryError> {
+        let token =
+            sqlx::query_as::<_, RefreshToken>("SELECT * FROM refresh_tokens WHERE token_hash = ?")
+                .bind(token_hash)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(token)
+    }
+
+    async fn mark_rotated(&self, token_hash: &str) -> Result<bool, RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE refresh_tokens SET rotated_at = ?
+             WHERE token_hash = ? AND rotated_at IS NULL AND revoked_at IS NULL",
+        )
+        .bind(Utc::now())
+        .bind(token_hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = ? WHERE family_id = ? AND revoked_at IS NULL",
+        )
+        .bind(Utc::now())
+        .bind(family_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: &str) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = ? WHERE user_id = ? AND revoked_at IS NULL",
+        )
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, RepositoryError> {
+        let result = sqlx::query("DELETE FROM refresh_tokens WHERE expires_at <= ?")
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
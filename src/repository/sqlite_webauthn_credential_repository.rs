@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use chrono::prelude::*;
+use sqlx::SqlitePool;
+
+use crate::models::WebauthnCredential;
+
+use super::{RepositoryError, WebauthnCredentialRepository};
+
+/// Persists WebAuthn credentials in SQLite, see `migrations/sqlite`.
+pub struct SqliteWebauthnCredentialRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteWebauthnCredentialRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+fn map_write_error(err: sqlx::Error) -> RepositoryError {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            RepositoryError::Conflict("Credential already registered".to_string())
+        }
+        _ => err.into(),
+    }
+}
+
+#[async_trait]
+impl WebauthnCredentialRepository for SqliteWebauthnCredentialRepository {
+    async fn find(
+        &self,
+        credential_id: &str,
+    ) -> Result<Option<WebauthnCredential>, RepositoryError> {
+        let credential = sqlx::query_as::<_, WebauthnCredential>(
+            "SELECT * FROM webauthn_credentials WHERE credential_id = ?",
+        )
+        .bind(credential_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(credential)
+    }
+
+    async fn list_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<WebauthnCredential>, RepositoryError> {
+        let credentials = sqlx::query_as::<_, WebauthnCredential>(
+            "SELECT * FROM webauthn_credentials WHERE user_id = ? ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(credentials)
+    }
+
+    async fn insert(&self, credential: WebauthnCredential) -> Result<(), RepositoryError> {
+        sqlx::query(
+            "INSERT INTO webauthn_credentials (credential_id, user_id, name, passkey, created_at, last_used_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&credential.credential_id)
+        .bind(&credential.user_id)
+        .bind(&credential.name)
+        .bind(&credential.passkey)
+        .bind(credential.created_at)
+        .bind(credential.last_used_at)
+        .execute(&self.pool)
+        .await
+        .map_err(map_write_error)?;
+        Ok(())
+    }
+
+    async fn mark_used(
+        &self,
+        credential_id: &str,
+        passkey: &str,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError> {
+        let result = sqlx::query(
+            "UPDATE webauthn_credentials SET passkey = ?, last_used_at = ? WHERE credential_id = ?",
+        )
+        .bind(passkey)
+        .bind(used_at)
+        .bind(credential_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, user_id: &str, credential_id: &str) -> Result<(), RepositoryError> {
+        let result =
+            sqlx::query("DELETE FROM webauthn_credentials WHERE user_id = ? AND credential_id = ?")
+                .bind(user_id)
+                .bind(credential_id)
+                .execute(&self.pool)
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound);
+        }
+        Ok(())
+    }
+}
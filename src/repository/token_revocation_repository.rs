@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+
+use crate::models::TokenClaims;
+
+use super::RepositoryError;
+
+/// Access tokens that must be refused before their `exp`.
+///
+/// All timestamps are unix seconds, like the JWT claims they are compared with.
+#[async_trait]
+pub trait TokenRevocationRepository: Send + Sync {
+    /// Revokes a single token by `jti`, the entry can be dropped once `expires_at` has passed.
+    async fn revoke(&self, jti: &str, expires_at: i64) -> Result<(), RepositoryError>;
+    /// Revokes every token of `user_id` issued at or before `issued_before`.
+    ///
+    /// `iat` only has whole seconds, so a token from the same second as the revocation is
+    /// refused as well.
+    async fn revoke_user(
+        &self,
+        user_id: &str,
+        issued_before: i64,
+        expires_at: i64,
+    ) -> Result<(), RepositoryError>;
+    async fn is_revoked(&self, claims: &TokenClaims) -> Result<bool, RepositoryError>;
+    /// Drops entries whose tokens have expired anyway, returns how many were removed.
+    async fn purge_expired(&self, now: i64) -> Result<u64, RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::repository::Database;
+
+    async fn backends() -> Vec<Arc<dyn TokenRevocationRepository>> {
+        Database::for_tests()
+            .await
+            .iter()
+            .map(Database::token_revocation_repository)
+            .collect()
+    }
+
+    fn claims(jti: &str, sub: &str, iat: i64) -> TokenClaims {
+        TokenClaims {
+            sub: sub.to_string(),
+            jti: jti.to_string(),
+            role: "user".to_string(),
+            iat: iat as usize,
+            exp: (iat + 3600) as usize,
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_revoked_jti_is_refused_on_its_own() {
+        for repo in backends().await {
+            repo.revoke("jti-1", 5000).await.unwrap();
+
+            assert!(repo
+                .is_revoked(&claims("jti-1", "user-1", 1000))
+                .await
+                .unwrap());
+            assert!(!repo
+                .is_revoked(&claims("jti-2", "user-1", 1000))
+                .await
+                .unwrap());
+        }
+    }
+
+    #[actix_web::test]
+    async fn revoking_a_user_covers_tokens_up_to_the_same_second() {
+        for repo in backends().await {
+            repo.revoke_user("user-1", 1000, 5000).await.unwrap();
+
+            assert!(repo.is_revoked(&claims("a", "user-1", 999)).await.unwrap());
+            // Issued in the same second as the revocation, `iat` cannot tell it apart
+            assert!(repo.is_revoked(&claims("b", "user-1", 1000)).await.unwrap());
+            assert!(!repo.is_revoked(&claims("c", "user-1", 1001)).await.unwrap());
+            assert!(!repo.is_revoked(&claims("d", "user-2", 999)).await.unwrap());
+
+            // A later revocation moves the cut-off instead of adding a second entry
+            repo.revoke_user("user-1", 2000, 6000).await.unwrap();
+            assert!(repo.is_revoked(&claims("c", "user-1", 1001)).await.unwrap());
+        }
+    }
+
+    #[actix_web::test]
+    async fn purge_drops_only_expired_entries() {
+        for repo in backends().await {
+            repo.revoke("old", 1000).await.unwrap();
+            repo.revoke("fresh", 3000).await.unwrap();
+            repo.revoke_user("user-1", 500, 1000).await.unwrap();
+
+            assert_eq!(repo.purge_expired(2000).await.unwrap(), 2);
+            assert!(!repo
+                .is_revoked(&claims("old", "user-1", 500))
+                .await
+                .unwrap());
+            assert!(repo
+                .is_revoked(&claims("fresh", "user-1", 500))
+                .await
+                .unwrap());
+            assert_eq!(repo.purge_expired(2000).await.unwrap(), 0);
+        }
+    }
+}
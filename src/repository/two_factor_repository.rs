@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+
+use crate::models::TotpFactor;
+
+use super::RepositoryError;
+
+/// Second factors of users: their TOTP secret and recovery code hashes.
+#[async_trait]
+pub trait TwoFactorRepository: Send + Sync {
+    async fn find_totp(&self, user_id: &str) -> Result<Option<TotpFactor>, RepositoryError>;
+    /// Inserts or replaces the user's TOTP factor.
+    async fn save_totp(&self, factor: TotpFactor) -> Result<(), RepositoryError>;
+    /// Records `step` as used, returns `false` if it or a later step was already used so a
+    /// code can't be replayed, even by concurrent requests.
+    async fn mark_totp_used(&self, user_id: &str, step: i64) -> Result<bool, RepositoryError>;
+    /// Removes the TOTP factor and the recovery codes.
+    async fn delete(&self, user_id: &str) -> Result<(), RepositoryError>;
+    async fn replace_recovery_codes(
+        &self,
+        user_id: &str,
+        code_hashes: Vec<String>,
+    ) -> Result<(), RepositoryError>;
+    /// Deletes the recovery code, returns `false` if the user has no such code.
+    async fn use_recovery_code(
+        &self,
+        user_id: &str,
+        code_hash: &str,
+    ) -> Result<bool, RepositoryError>;
+    async fn count_recovery_codes(&self, user_id: &str) -> Result<i64, RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{models::User, repository::Database};
+
+    async fn backends() -> Vec<Arc<dyn TwoFactorRepository>> {
+        let mut repos = Vec::new();
+        for database in Database::for_tests().await {
+            database
+                .user_repository()
+                .insert(User::for_tests("user-1", "alice@example.com"))
+                .await
+                .unwrap();
+            repos.push(database.two_factor_repository());
+        }
+        repos
+    }
+
+    fn factor(enabled: bool) -> TotpFactor {
+        TotpFactor {
+            user_id: "user-1".to_string(),
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            enabled,
+            last_used_step: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_step_is_never_accepted_twice() {
+        for repo in backends().await {
+            repo.save_totp(factor(true)).await.unwrap();
+
+            assert!(repo.mark_totp_used("user-1", 100).await.unwrap());
+            assert!(!repo.mark_totp_used("user-1", 100).await.unwrap());
+            // An older code that is still within the skew window is refused as well
+            assert!(!repo.mark_totp_used("user-1", 99).await.unwrap());
+            assert!(repo.mark_totp_used("user-1", 101).await.unwrap());
+
+            let stored = repo.find_totp("user-1").await.unwrap().unwrap();
+            assert_eq!(stored.last_used_step, Some(101));
+            assert!(stored.enabled);
+        }
+    }
+
+    #[actix_web::test]
+    async fn saving_replaces_an_unconfirmed_secret() {
+        for repo in backends().await {
+            repo.save_totp(factor(false)).await.unwrap();
+            let mut confirmed = factor(true);
+            confirmed.secret = "KRSXG5CTMVRXEZLU".to_string();
+            repo.save_totp(confirmed).await.unwrap();
+
+            let stored = repo.find_totp("user-1").await.unwrap().unwrap();
+            assert_eq!(stored.secret, "KRSXG5CTMVRXEZLU");
+            assert!(stored.enabled);
+            assert!(repo.find_totp("user-2").await.unwrap().is_none());
+        }
+    }
+
+    #[actix_web::test]
+    async fn recovery_codes_are_single_use_and_replaced_as_a_set() {
+        for repo in backends().await {
+            repo.save_totp(factor(true)).await.unwrap();
+            let codes = vec!["hash-1".to_string(), "hash-2".to_string()];
+            repo.replace_recovery_codes("user-1", codes).await.unwrap();
+
+            assert!(repo.use_recovery_code("user-1", "hash-1").await.unwrap());
+            assert!(!repo.use_recovery_code("user-1", "hash-1").await.unwrap());
+            assert_eq!(repo.count_recovery_codes("user-1").await.unwrap(), 1);
+
+            repo.replace_recovery_codes("user-1", vec!["hash-3".to_string()])
+                .await
+                .unwrap();
+            assert!(!repo.use_recovery_code("user-1", "hash-2").await.unwrap());
+            assert_eq!(repo.count_recovery_codes("user-1").await.unwrap(), 1);
+
+            // Turning 2FA off drops the codes with the secret
+            repo.delete("user-1").await.unwrap();
+            assert!(repo.find_totp("user-1").await.unwrap().is_none());
+            assert_eq!(repo.count_recovery_codes("user-1").await.unwrap(), 0);
+        }
+    }
+}
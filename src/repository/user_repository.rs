@@ -0,0 +1,400 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::models::User;
+
+use super::RepositoryError;
+
+/// Criteria for [`UserRepository::list`], `None` fields match every user.
+#[derive(Debug, Default)]
+pub struct UserFilter {
+    pub provider: Option<String>,
+    pub role: Option<String>,
+    pub verified: Option<bool>,
+    pub suspended: Option<bool>,
+    /// Case-insensitive substring of the name or email.
+    pub search: Option<String>,
+}
+
+/// One page of [`UserRepository::list`] plus the number of users matching the filter.
+pub struct UserPage {
+    pub users: Vec<User>,
+    pub total: i64,
+}
+
+/// `LIKE` pattern matching `search` anywhere, with `\` escaping the wildcards.
+pub fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Storage for [`User`] records.
+///
+/// Emails are stored lowercased and must be unique; `insert` and `update`
+/// return [`RepositoryError::Conflict`] when another user already owns the email.
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn find_by_id(&self, id: &str) -> Result<Option<User>, RepositoryError>;
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, RepositoryError>;
+    async fn insert(&self, user: User) -> Result<User, RepositoryError>;
+    #[allow(dead_code)]
+    async fn update(&self, user: User) -> Result<User, RepositoryError>;
+    /// Swaps the password hash only while it is still `current`, so a password changed in
+    /// the meantime isn't overwritten. Returns whether it was replaced.
+    async fn replace_password(
+        &self,
+        id: &str,
+        current: &str,
+        new: &str,
+    ) -> Result<bool, RepositoryError>;
+    /// Changes only the role, leaving concurrent updates of other columns alone.
+    async fn set_role(&self, id: &str, role: &str) -> Result<(), RepositoryError>;
+    /// Changes only the suspended flag, leaving concurrent updates of other columns alone.
+    async fn set_suspended(&self, id: &str, suspended: bool) -> Result<(), RepositoryError>;
+    /// Records when the last verification email went out, leaving the rest of the user alone.
+    async fn mark_verification_sent(
+        &self,
+        id: &str,
+        sent_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+    /// Marks the user verified while their email is still `email`, the address a link was
+    /// sent to. Returns whether it still was.
+    async fn mark_verified(&self, id: &str, email: &str) -> Result<bool, RepositoryError>;
+    /// Moves the user to a confirmed `email`, marking it verified and leaving the other
+    /// columns alone. Returns a conflict when another user owns the address.
+    async fn change_email(&self, id: &str, email: &str) -> Result<(), RepositoryError>;
+    async fn delete(&self, id: &str) -> Result<(), RepositoryError>;
+    /// Users matching `filter`, oldest first.
+    async fn list(
+        &self,
+        filter: &UserFilter,
+        offset: i64,
+        limit: i64,
+    ) -> Result<UserPage, RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, ResponseError};
+    use chrono::{Duration, TimeZone, Utc};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::repository::Database;
+
+    async fn backends() -> Vec<Arc<dyn UserRepository>> {
+        Database::for_tests()
+            .await
+            .iter()
+            .map(Database::user_repository)
+            .collect()
+    }
+
+    fn user(n: i64, email: &str) -> User {
+        let created_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(n);
+        User {
+            id: Some(format!("user-{}", n)),
+            name: format!("User {}", n),
+            email: email.to_string(),
+            password: String::new(),
+            role: "user".to_string(),
+            photo: "default.png".to_string(),
+            verified: false,
+            suspended: false,
+            provider: "local".to_string(),
+            verificationSentAt: None,
+            createdAt: Some(created_at),
+            updatedAt: Some(created_at),
+        }
+    }
+
+    #[actix_web::test]
+    async fn duplicate_email_is_a_conflict_regardless_of_case() {
+        for repo in backends().await {
+            repo.insert(user(1, "Alice@Example.com")).await.unwrap();
+            let stored = repo.find_by_email("ALICE@example.COM").await.unwrap();
+            assert_eq!(stored.unwrap().email, "alice@example.com");
+
+            let err = repo.insert(user(2, "alice@EXAMPLE.com")).await.unwrap_err();
+            assert!(matches!(err, RepositoryError::Conflict(_)), "{:?}", err);
+            assert_eq!(err.status_code(), StatusCode::CONFLICT);
+
+            // Taking the email of another user on update is refused the same way
+            repo.insert(user(3, "bob@example.com")).await.unwrap();
+            let mut bob = user(3, "ALICE@example.com");
+            bob.name = "Bob".to_string();
+            let err = repo.update(bob).await.unwrap_err();
+            assert!(matches!(err, RepositoryError::Conflict(_)), "{:?}", err);
+        }
+    }
+
+    #[actix_web::test]
+    async fn update_moves_the_email_index() {
+        for repo in backends().await {
+            repo.insert(user(1, "alice@example.com")).await.unwrap();
+            repo.update(user(1, "Alice@New.example")).await.unwrap();
+
+            assert!(repo
+                .find_by_email("alice@example.com")
+                .await
+                .unwrap()
+                .is_none());
+            let stored = repo.find_by_email("alice@new.example").await.unwrap();
+            assert_eq!(stored.unwrap().id.as_deref(), Some("user-1"));
+
+            // The old address is free again
+            repo.insert(user(2, "alice@example.com")).await.unwrap();
+        }
+    }
+
+    #[actix_web::test]
+    async fn only_the_email_index_reports_a_taken_email() {
+        for repo in backends().await {
+            repo.insert(user(1, "alice@example.com")).await.unwrap();
+
+            let err = repo.insert(user(2, "ALICE@example.com")).await.unwrap_err();
+            assert_eq!(err.to_string(), "Email already exist");
+
+            // Another unique column gets a conflict of its own
+            let err = repo.insert(user(1, "bob@example.com")).await.unwrap_err();
+            assert!(matches!(err, RepositoryError::Conflict(_)), "{:?}", err);
+            assert_ne!(err.to_string(), "Email already exist");
+        }
+    }
+
+    #[actix_web::test]
+    async fn update_and_delete_of_missing_id_are_not_found() {
+        for repo in backends().await {
+            let err = repo.update(user(1, "ghost@example.com")).await.unwrap_err();
+            assert!(matches!(err, RepositoryError::NotFound), "{:?}", err);
+            assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+
+            let err = repo.delete("user-1").await.unwrap_err();
+            assert!(matches!(err, RepositoryError::NotFound), "{:?}", err);
+
+            repo.insert(user(1, "ghost@example.com")).await.unwrap();
+            repo.delete("user-1").await.unwrap();
+            assert!(repo.find_by_id("user-1").await.unwrap().is_none());
+            assert!(matches!(
+                repo.delete("user-1").await,
+                Err(RepositoryError::NotFound)
+            ));
+        }
+    }
+
+    #[actix_web::test]
+    async fn replace_password_only_swaps_the_expected_hash() {
+        for repo in backends().await {
+            let mut alice = user(1, "alice@example.com");
+            alice.password = "old-hash".to_string();
+            repo.insert(alice).await.unwrap();
+
+            // Someone else's change since the hash was read wins
+            assert!(!repo
+                .replace_password("user-1", "stale-hash", "rehashed")
+                .await
+                .unwrap());
+            assert!(repo
+                .replace_password("user-1", "old-hash", "rehashed")
+                .await
+                .unwrap());
+            assert!(!repo
+                .replace_password("user-2", "old-hash", "rehashed")
+                .await
+                .unwrap());
+
+            let stored = repo.find_by_id("user-1").await.unwrap().unwrap();
+            assert_eq!(stored.password, "rehashed");
+            assert_eq!(stored.name, "User 1");
+        }
+    }
+
+    #[actix_web::test]
+    async fn mark_verification_sent_keeps_concurrent_changes() {
+        for repo in backends().await {
+            let stale = repo.insert(user(1, "alice@example.com")).await.unwrap();
+
+            let mut verified = stale.clone();
+            verified.verified = true;
+            verified.suspended = true;
+            repo.update(verified).await.unwrap();
+
+            let sent_at = Utc.with_ymd_and_hms(2024, 2, 1, 12, 0, 0).unwrap();
+            repo.mark_verification_sent("user-1", sent_at)
+                .await
+                .unwrap();
+
+            let stored = repo.find_by_id("user-1").await.unwrap().unwrap();
+            assert_eq!(stored.verificationSentAt, Some(sent_at));
+            assert!(stored.verified && stored.suspended);
+            assert!(matches!(
+                repo.mark_verification_sent("user-2", sent_at).await,
+                Err(RepositoryError::NotFound)
+            ));
+        }
+    }
+
+    #[actix_web::test]
+    async fn mark_verified_needs_the_email_the_link_went_to() {
+        for repo in backends().await {
+            repo.insert(user(1, "alice@example.com")).await.unwrap();
+
+            assert!(!repo
+                .mark_verified("user-1", "old@example.com")
+                .await
+                .unwrap());
+            assert!(!repo.find_by_id("user-1").await.unwrap().unwrap().verified);
+
+            assert!(repo
+                .mark_verified("user-1", "alice@example.com")
+                .await
+                .unwrap());
+            // A second click on the same link is fine
+            assert!(repo
+                .mark_verified("user-1", "alice@example.com")
+                .await
+                .unwrap());
+            assert!(repo.find_by_id("user-1").await.unwrap().unwrap().verified);
+            assert!(!repo
+                .mark_verified("user-2", "alice@example.com")
+                .await
+                .unwrap());
+        }
+    }
+
+    #[actix_web::test]
+    async fn change_email_verifies_the_new_address_and_keeps_the_rest() {
+        for repo in backends().await {
+            let mut alice = user(1, "alice@example.com");
+            alice.password = "alice-hash".to_string();
+            repo.insert(alice).await.unwrap();
+            repo.insert(user(2, "bob@example.com")).await.unwrap();
+
+            let err = repo
+                .change_email("user-1", "BOB@example.com")
+                .await
+                .unwrap_err();
+            assert_eq!(err.to_string(), "Email already exist");
+
+            repo.change_email("user-1", "Alice@New.example")
+                .await
+                .unwrap();
+            let stored = repo
+                .find_by_email("alice@new.example")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.id.as_deref(), Some("user-1"));
+            assert!(stored.verified);
+            assert_eq!(stored.password, "alice-hash");
+            assert!(repo
+                .find_by_email("alice@example.com")
+                .await
+                .unwrap()
+                .is_none());
+
+            assert!(matches!(
+                repo.change_email("user-3", "carol@example.com").await,
+                Err(RepositoryError::NotFound)
+            ));
+        }
+    }
+
+    #[actix_web::test]
+    async fn list_is_ordered_by_creation() {
+        for repo in backends().await {
+            for n in [3, 1, 2] {
+                repo.insert(user(n, &format!("user{}@example.com", n)))
+                    .await
+                    .unwrap();
+            }
+
+            let page = repo.list(&UserFilter::default(), 0, 10).await.unwrap();
+            assert_eq!(page.total, 3);
+            let emails: Vec<&str> = page.users.iter().map(|user| user.email.as_str()).collect();
+            assert_eq!(
+                emails,
+                [
+                    "user1@example.com",
+                    "user2@example.com",
+                    "user3@example.com"
+                ]
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn list_filters_searches_and_pages() {
+        for repo in backends().await {
+            for n in 1..=5 {
+                let mut user = user(n, &format!("user{}@example.com", n));
+                user.role = if n == 1 { "admin" } else { "user" }.to_string();
+                user.suspended = n == 2;
+                repo.insert(user).await.unwrap();
+            }
+            let mut percent = user(6, "100%@example.com");
+            percent.name = "Percent".to_string();
+            repo.insert(percent).await.unwrap();
+
+            let ids = |page: UserPage| -> Vec<String> {
+                page.users
+                    .into_iter()
+                    .map(|user| user.id.unwrap())
+                    .collect()
+            };
+
+            let filter = UserFilter {
+                role: Some("user".to_string()),
+                suspended: Some(false),
+                ..Default::default()
+            };
+            let page = repo.list(&filter, 1, 2).await.unwrap();
+            assert_eq!(page.total, 4);
+            assert_eq!(ids(page), ["user-4", "user-5"]);
+
+            // Wildcards in the search are literal
+            let filter = UserFilter {
+                search: Some("%@".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(ids(repo.list(&filter, 0, 10).await.unwrap()), ["user-6"]);
+
+            let filter = UserFilter {
+                search: Some("USER 3".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(ids(repo.list(&filter, 0, 10).await.unwrap()), ["user-3"]);
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_role_change_keeps_a_concurrent_password_change() {
+        for repo in backends().await {
+            let mut alice = user(1, "alice@example.com");
+            alice.password = "old-hash".to_string();
+            repo.insert(alice).await.unwrap();
+
+            // The admin loaded the user, then the password changed before the role is saved
+            let loaded = repo.find_by_id("user-1").await.unwrap().unwrap();
+            assert!(repo
+                .replace_password("user-1", &loaded.password, "new-hash")
+                .await
+                .unwrap());
+            repo.set_role("user-1", "admin").await.unwrap();
+            repo.set_suspended("user-1", true).await.unwrap();
+
+            let stored = repo.find_by_id("user-1").await.unwrap().unwrap();
+            assert_eq!(stored.password, "new-hash");
+            assert_eq!(stored.role, "admin");
+            assert!(stored.suspended);
+
+            let err = repo.set_role("user-2", "admin").await.unwrap_err();
+            assert!(matches!(err, RepositoryError::NotFound), "{:?}", err);
+            let err = repo.set_suspended("user-2", true).await.unwrap_err();
+            assert!(matches!(err, RepositoryError::NotFound), "{:?}", err);
+        }
+    }
+}
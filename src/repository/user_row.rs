@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+
+use crate::models::User;
+
+/// Column layout of the `users` table shared by the SQL backends.
+#[derive(sqlx::FromRow)]
+pub struct UserRow {
+    pub id: String,
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    pub role: String,
+    pub photo: String,
+    pub verified: bool,
+    pub suspended: bool,
+    pub provider: String,
+    pub verification_sent_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<UserRow> for User {
+    fn from(row: UserRow) -> Self {
+        User {
+            id: Some(row.id),
+            name: row.name,
+            email: row.email,
+            password: row.password,
+            role: row.role,
+            photo: row.photo,
+            verified: row.verified,
+            suspended: row.suspended,
+            provider: row.provider,
+            verificationSentAt: row.verification_sent_at,
+            createdAt: Some(row.created_at),
+            updatedAt: Some(row.updated_at),
+        }
+    }
+}
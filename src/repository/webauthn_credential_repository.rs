@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use chrono::prelude::*;
+
+use crate::models::WebauthnCredential;
+
+use super::RepositoryError;
+
+/// WebAuthn credentials of users, see [`WebauthnCredential`].
+///
+/// A credential id belongs to a single user, `insert` returns
+/// [`RepositoryError::Conflict`] for one that is already registered.
+#[async_trait]
+pub trait WebauthnCredentialRepository: Send + Sync {
+    async fn find(
+        &self,
+        credential_id: &str,
+    ) -> Result<Option<WebauthnCredential>, RepositoryError>;
+    async fn list_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<WebauthnCredential>, RepositoryError>;
+    async fn insert(&self, credential: WebauthnCredential) -> Result<(), RepositoryError>;
+    /// Stores the passkey again after a login moved its counter.
+    async fn mark_used(
+        &self,
+        credential_id: &str,
+        passkey: &str,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), RepositoryError>;
+    async fn delete(&self, user_id: &str, credential_id: &str) -> Result<(), RepositoryError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{models::User, repository::Database};
+
+    async fn backends() -> Vec<Arc<dyn WebauthnCredentialRepository>> {
+        let mut repos = Vec::new();
+        for database in Database::for_tests().await {
+            let users = database.user_repository();
+            for (id, email) in [
+                ("user-1", "alice@example.com"),
+                ("user-2", "bob@example.com"),
+            ] {
+                users.insert(User::for_tests(id, email)).await.unwrap();
+            }
+            repos.push(database.webauthn_credential_repository());
+        }
+        repos
+    }
+
+    fn credential(credential_id: &str, user_id: &str) -> WebauthnCredential {
+        WebauthnCredential {
+            credential_id: credential_id.to_string(),
+            user_id: user_id.to_string(),
+            name: "Laptop".to_string(),
+            passkey: "{}".to_string(),
+            created_at: Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap(),
+            last_used_at: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_credential_id_belongs_to_one_user() {
+        for repo in backends().await {
+            repo.insert(credential("cred-1", "user-1")).await.unwrap();
+            repo.insert(credential("cred-2", "user-1")).await.unwrap();
+
+            let err = repo
+                .insert(credential("cred-1", "user-2"))
+                .await
+                .unwrap_err();
+            assert!(matches!(err, RepositoryError::Conflict(_)), "{:?}", err);
+
+            assert_eq!(repo.list_for_user("user-1").await.unwrap().len(), 2);
+            assert!(repo.list_for_user("user-2").await.unwrap().is_empty());
+            let stored = repo.find("cred-1").await.unwrap().unwrap();
+            assert_eq!(stored.user_id, "user-1");
+            assert!(repo.find("cred-3").await.unwrap().is_none());
+        }
+    }
+
+    #[actix_web::test]
+    async fn mark_used_stores_the_moved_counter() {
+        for repo in backends().await {
+            repo.insert(credential("cred-1", "user-1")).await.unwrap();
+
+            let used_at = Utc.with_ymd_and_hms(2024, 2, 1, 12, 0, 0).unwrap();
+            repo.mark_used("cred-1", r#"{"counter":1}"#, used_at)
+                .await
+                .unwrap();
+            let stored = repo.find("cred-1").await.unwrap().unwrap();
+            assert_eq!(stored.passkey, r#"{"counter":1}"#);
+            assert_eq!(stored.last_used_at, Some(used_at));
+
+            assert!(matches!(
+                repo.mark_used("cred-2", "{}", used_at).await,
+                Err(RepositoryError::NotFound)
+            ));
+        }
+    }
+
+    #[actix_web::test]
+    async fn only_the_owner_deletes_a_credential() {
+        for repo in backends().await {
+            repo.insert(credential("cred-1", "user-1")).await.unwrap();
+
+            assert!(matches!(
+                repo.delete("user-2", "cred-1").await,
+                Err(RepositoryError::NotFound)
+            ));
+            repo.delete("user-1", "cred-1").await.unwrap();
+            assert!(repo.find("cred-1").await.unwrap().is_none());
+        }
+    }
+}
@@ -10,6 +10,7 @@ pub struct FilteredUser {
     pub role: String,
     pub photo: String,
     pub verified: bool,
+    pub suspended: bool,
     pub provider: String,
     pub createdAt: DateTime<Utc>,
     pub updatedAt: DateTime<Utc>,
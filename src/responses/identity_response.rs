@@ -0,0 +1,21 @@
+use chrono::prelude::*;
+use serde::Serialize;
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize)]
+pub struct FilteredIdentity {
+    pub provider: String,
+    pub email: String,
+    pub createdAt: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IdentityListData {
+    pub identities: Vec<FilteredIdentity>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct IdentityListResponse {
+    pub status: String,
+    pub data: IdentityListData,
+}
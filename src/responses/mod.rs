@@ -1,6 +1,14 @@
 pub mod filtered_user;
+pub mod identity_response;
+pub mod user_list_response;
 pub mod user_response;
+pub mod webauthn_credential_response;
 
 // Re-export for easier use
 pub use filtered_user::FilteredUser;
+pub use identity_response::{FilteredIdentity, IdentityListData, IdentityListResponse};
+pub use user_list_response::{UserListData, UserListResponse};
 pub use user_response::{UserData, UserResponse};
+pub use webauthn_credential_response::{
+    FilteredWebauthnCredential, WebauthnCredentialListData, WebauthnCredentialListResponse,
+};
@@ -0,0 +1,16 @@
+use crate::responses::filtered_user::FilteredUser;
+use serde::Serialize;
+
+#[derive(Serialize, Debug)]
+pub struct UserListData {
+    pub users: Vec<FilteredUser>,
+    pub page: i64,
+    pub limit: i64,
+    pub total: i64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct UserListResponse {
+    pub status: String,
+    pub data: UserListData,
+}
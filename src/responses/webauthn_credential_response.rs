@@ -0,0 +1,22 @@
+use chrono::prelude::*;
+use serde::Serialize;
+
+#[allow(non_snake_case)]
+#[derive(Debug, Serialize)]
+pub struct FilteredWebauthnCredential {
+    pub id: String,
+    pub name: String,
+    pub createdAt: DateTime<Utc>,
+    pub lastUsedAt: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebauthnCredentialListData {
+    pub credentials: Vec<FilteredWebauthnCredential>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WebauthnCredentialListResponse {
+    pub status: String,
+    pub data: WebauthnCredentialListData,
+}